@@ -0,0 +1,79 @@
+use crate::camera::FAR_PLANE;
+use serde::{Deserialize, Serialize};
+
+/// The closest the fog can get, so the selection is never hidden.
+pub const MIN_FOG_DISTANCE: f32 = 10.0;
+
+/// The furthest the fog can be pushed back. Fog thickens by distance and the far plane
+/// clips by depth, so voxels are fully fogged before they reach the clip.
+pub const MAX_FOG_DISTANCE: f32 = FAR_PLANE;
+
+/// What is drawn behind the voxels.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    /// A single color, the horizon color.
+    Solid,
+    /// Fade from the horizon color up to the sky color.
+    Gradient,
+    /// An image wrapped around the scene.
+    CubeMap,
+}
+
+impl Background {
+    /// Get the background that follows this one.
+    pub fn next(&self) -> Background {
+        match self {
+            Background::Solid => Background::Gradient,
+            Background::Gradient => Background::CubeMap,
+            Background::CubeMap => Background::Solid,
+        }
+    }
+}
+
+/// Background and fog settings. These are saved with the scene.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Atmosphere {
+    /// What is drawn behind the voxels.
+    pub background: Background,
+    /// The color at the top of a gradient background.
+    pub sky_color: [f32; 3],
+    /// The color at the horizon. This is also the solid background color and the fog color.
+    pub horizon_color: [f32; 3],
+    /// Path to a PNG holding the six faces of a cube map side by side,
+    /// in the order +x, -x, +y, -y, +z, -z.
+    pub cube_map: Option<String>,
+    /// Fade distant voxels into the horizon color?
+    pub fog: bool,
+    /// Fog starts at half this distance and hides everything beyond it.
+    pub fog_distance: f32,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Atmosphere::new()
+    }
+}
+
+impl Atmosphere {
+    /// Create the default atmosphere, a plain background without fog.
+    pub const fn new() -> Atmosphere {
+        Atmosphere {
+            background: Background::Solid,
+            sky_color: [0.25, 0.35, 0.75],
+            horizon_color: [0.5, 0.5, 0.7],
+            cube_map: None,
+            fog: false,
+            fog_distance: 190.0,
+        }
+    }
+
+    /// The background as a number the sky shader understands.
+    pub fn background_index(&self) -> i32 {
+        match self.background {
+            Background::Solid => 0,
+            Background::Gradient => 1,
+            Background::CubeMap => 2,
+        }
+    }
+}
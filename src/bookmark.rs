@@ -0,0 +1,90 @@
+use crate::camera::Camera;
+use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{PI, TAU};
+use std::time::Instant;
+
+/// How long the camera takes to glide to a bookmark, in seconds.
+pub const BOOKMARK_TRANSITION_TIME: f32 = 0.6;
+
+/// A named camera position saved with the scene.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    /// Shown when the bookmark is recalled.
+    pub name: String,
+    /// The number key that recalls the bookmark.
+    pub slot: u32,
+    /// Where the camera was.
+    pub eye: [f32; 3],
+    /// What it was looking at.
+    pub target: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Bookmark {
+    /// Remember where the camera is.
+    pub fn from_camera(name: String, slot: u32, camera: &Camera) -> Bookmark {
+        Bookmark {
+            name,
+            slot,
+            eye: camera.eye.into(),
+            target: camera.target.into(),
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+        }
+    }
+
+    /// Move a copy of the camera to the bookmark, keeping its other settings.
+    pub fn camera(&self, camera: &Camera) -> Camera {
+        Camera {
+            eye: Point3::from(self.eye),
+            target: Point3::from(self.target),
+            yaw: self.yaw,
+            pitch: self.pitch,
+            ..*camera
+        }
+    }
+}
+
+/// A camera gliding from one place to another.
+#[derive(Copy, Clone)]
+pub struct CameraTransition {
+    from: Camera,
+    to: Camera,
+    start: Instant,
+}
+
+impl CameraTransition {
+    /// Start gliding now.
+    pub fn new(from: Camera, to: Camera) -> CameraTransition {
+        CameraTransition {
+            from,
+            to,
+            start: Instant::now(),
+        }
+    }
+
+    /// Where the camera is now, and whether it has arrived.
+    pub fn camera(&self) -> (Camera, bool) {
+        let progress = self.start.elapsed().as_secs_f32() / BOOKMARK_TRANSITION_TIME;
+        if progress >= 1.0 {
+            return (self.to, true);
+        }
+        // Ease in and out so the camera does not lurch.
+        let amount = progress * progress * (3.0 - 2.0 * progress);
+        (interpolate(&self.from, &self.to, amount), false)
+    }
+}
+
+/// Blend between two cameras. The yaw turns the short way round.
+fn interpolate(from: &Camera, to: &Camera, amount: f32) -> Camera {
+    let turn = (to.yaw - from.yaw + PI).rem_euclid(TAU) - PI;
+    Camera {
+        eye: from.eye + (to.eye - from.eye) * amount,
+        target: from.target + (to.target - from.target) * amount,
+        yaw: from.yaw + turn * amount,
+        pitch: from.pitch + (to.pitch - from.pitch) * amount,
+        view_height: from.view_height + (to.view_height - from.view_height) * amount,
+    }
+}
@@ -1,10 +1,14 @@
 use crate::canvas::Canvas;
 use crate::command::{Command, CommandType};
+use crate::icon_cache::IconCache;
 use crate::key_bindings::KeyBindings;
 use glium::Frame;
 use glium::backend::glutin::Display;
 use glutin::surface::WindowSurface;
 
+/// How far the icon sits inside the edge of a button, as a share of the button's size.
+pub const ICON_INSET: f32 = 0.1;
+
 pub struct ButtonState {
     pub icon_path: String,
 }
@@ -15,6 +19,10 @@ pub struct Button {
     pub states: Vec<ButtonState>,
     pub current_state: usize,
     pub mapped_key: u32,
+    /// Shown above the button while the mouse is over it.
+    pub tooltip: Option<String>,
+    /// Is the mouse over the button?
+    hovered: bool,
 }
 
 impl Button {
@@ -25,9 +33,15 @@ impl Button {
             states: Vec::new(),
             current_state: 0,
             mapped_key,
+            tooltip: None,
+            hovered: false,
         }
     }
 
+    pub fn set_tooltip(&mut self, tooltip: String) {
+        self.tooltip = Some(tooltip);
+    }
+
     pub fn add_state(&mut self, icon_path: String) {
         if self.states.len() == 0 {
             self.current_state = 0;
@@ -39,8 +53,8 @@ impl Button {
 use crate::widget::Widget;
 
 impl Widget for Button {
-    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame) {
-        let mut canvas = Canvas::new(display, frame);
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache) {
+        let mut canvas = Canvas::new(display, frame, icons);
 
         let border_color = [0.1, 0.1, 0.1, 0.8];
         let color = [0.6, 0.6, 0.6, 1.0];
@@ -49,17 +63,20 @@ impl Widget for Button {
         if self.states.len() > 0 {
             let current = self.states.get(self.current_state).unwrap();
 
+            let inset = (self.size.0 * ICON_INSET, self.size.1 * ICON_INSET);
             canvas.draw_image(
-                (
-                    self.position.0 + border * 2.0,
-                    self.position.1 + border * 2.0,
-                ),
-                (self.size.0 - border * 4.0, self.size.1 - border * 4.0),
+                (self.position.0 + inset.0, self.position.1 + inset.1),
+                (self.size.0 - inset.0 * 2.0, self.size.1 - inset.1 * 2.0),
                 current.icon_path.as_str(),
             );
         }
     }
 
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32)) {
+        self.position = position;
+        self.size = size;
+    }
+
     fn process_command(&mut self, command: &Command) -> Vec<Command> {
         let mut translated_commands = Vec::new();
         // Process window event.
@@ -76,16 +93,24 @@ impl Widget for Button {
                     translated_commands.push(Command {
                         command_type: CommandType::KeyDown,
                         data1: self.mapped_key,
-                        data2: self.mapped_key,
+                        data2: 0,
                     })
                 }
             },
+            CommandType::MouseHover => {
+                let x = f32::from_bits(command.data1);
+                let y = f32::from_bits(command.data2);
+                self.hovered = x >= self.position.0
+                    && x <= self.position.0 + self.size.0
+                    && y >= self.position.1
+                    && y <= self.position.1 + self.size.1;
+            }
             CommandType::KeyDown => {
                 let key = command.data1;
 
                 println!("UI Key pressed: {}", key);
                 let bindings: KeyBindings = KeyBindings::new();
-                let action_opt = bindings.action(key);
+                let action_opt = bindings.action(key, 0, false);
                 let virtual_opt = bindings.virtual_key(action_opt);
 
                 match virtual_opt {
@@ -97,8 +122,29 @@ impl Widget for Button {
                     _ => ()
                 }
             }
+            CommandType::SetButtonState => {
+                if command.data1 == self.mapped_key && command.data2 < self.states.len() as u32 {
+                    self.current_state = command.data2 as usize;
+                }
+            }
             _ => (),
         }
         translated_commands
     }
+
+    fn draw_overlay(
+        &mut self,
+        display: &Display<WindowSurface>,
+        frame: &mut Frame,
+        icons: &mut IconCache,
+    ) {
+        let Some(tooltip) = &self.tooltip else {
+            return;
+        };
+        if !self.hovered {
+            return;
+        }
+        let mut canvas = Canvas::new(display, frame, icons);
+        canvas.draw_tooltip(self.position, self.size, tooltip);
+    }
 }
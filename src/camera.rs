@@ -1,6 +1,46 @@
-use na::Point3;
+use na::{Isometry3, Matrix4, Orthographic3, Perspective3, Point3, Vector3};
 use nalgebra as na;
 
+/// The vertical field of view for the perspective projection.
+pub const FIELD_OF_VIEW: f32 = std::f32::consts::PI / 4.0; // 45 degrees
+
+/// The closest an orbiting camera can get to its target.
+pub const MIN_ORBIT_DISTANCE: f32 = 2.0;
+
+/// The furthest framing will pull the camera back, so the target stays in front of the far plane.
+pub const MAX_FRAME_DISTANCE: f32 = 150.0;
+
+/// Nothing closer than this is drawn in a perspective view.
+pub const NEAR_PLANE: f32 = 1.0;
+
+/// Nothing further than this is drawn.
+pub const FAR_PLANE: f32 = 200.0;
+
+/// The steepest the camera can look up or down, just short of straight so the view never flips.
+pub const MAX_PITCH: f32 = 1.54;
+
+/// The smallest orthographic view, a few voxels tall.
+pub const MIN_VIEW_HEIGHT: f32 = 2.0;
+
+/// How the camera flattens the world onto the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Distant things look smaller, like a real camera.
+    Perspective,
+    /// Everything keeps its size however far away it is, for lining up voxels precisely.
+    Orthographic,
+}
+
+impl Projection {
+    /// Get the projection that follows this one.
+    pub fn next(&self) -> Projection {
+        match self {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        }
+    }
+}
+
 /// A camera contains 2 points, the location and the target
 #[derive(Copy, Clone)]
 pub struct Camera {
@@ -10,6 +50,8 @@ pub struct Camera {
     pub target: Point3<f32>,
     pub yaw: f32,
     pub pitch: f32,
+    /// How many voxels fit from the bottom to the top of an orthographic view.
+    pub view_height: f32,
 }
 
 impl Camera {
@@ -20,6 +62,140 @@ impl Camera {
             target: Point3::new(0.0, 0.0, 0.0),
             yaw: -2.381,
             pitch: -0.282,
+            view_height: 46.0,
         }
     }
+
+    /// The unit vector the yaw and pitch point along.
+    pub fn direction(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    /// The unit vector pointing to the right of the screen. This stays level even when
+    /// looking straight up or down.
+    pub fn right(&self) -> Vector3<f32> {
+        Vector3::new(-self.yaw.cos(), 0.0, self.yaw.sin())
+    }
+
+    /// The unit vector pointing to the top of the screen.
+    pub fn up(&self) -> Vector3<f32> {
+        self.right().cross(&self.direction())
+    }
+
+    /// Get the transform from the world into the camera.
+    pub fn view(&self) -> Isometry3<f32> {
+        Isometry3::look_at_rh(&self.eye, &self.target, &self.up())
+    }
+
+    /// Get the projection for the camera. The aspect is the width of the screen over its height.
+    pub fn projection_matrix(&self, projection: Projection, aspect: f32) -> Matrix4<f32> {
+        match projection {
+            Projection::Perspective => {
+                Perspective3::new(aspect, FIELD_OF_VIEW, NEAR_PLANE, FAR_PLANE).into_inner()
+            }
+            Projection::Orthographic => {
+                let half_height = self.view_height / 2.0;
+                let half_width = half_height * aspect;
+                // The eye can be anywhere along the view direction, so keep everything
+                // behind it too rather than cutting the model in half.
+                Orthographic3::new(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    -FAR_PLANE,
+                    FAR_PLANE,
+                )
+                .into_inner()
+            }
+        }
+    }
+
+    /// Size the orthographic view to show about as much as the perspective view does
+    /// at this distance.
+    pub fn match_perspective(&mut self, distance: f32) {
+        self.view_height = (2.0 * distance * (FIELD_OF_VIEW / 2.0).tan()).max(MIN_VIEW_HEIGHT);
+    }
+
+    /// How far the eye is from the target.
+    pub fn distance(&self) -> f32 {
+        (self.target - self.eye).norm()
+    }
+
+    /// Turn the eye to face a new target without moving it.
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = target - self.eye;
+        if direction.norm() < f32::EPSILON {
+            return;
+        }
+        let direction = direction.normalize();
+        self.target = target;
+        self.yaw = direction.x.atan2(direction.z);
+        self.pitch = direction.y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Swing the eye around the target, keeping the same distance.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let distance = self.distance();
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+        self.eye = self.target - self.direction() * distance;
+    }
+
+    /// Slide the eye and target across the view. The amounts are in screen units,
+    /// so a drag across the whole screen moves about as far as the target is away,
+    /// or as far as the orthographic view is tall.
+    pub fn pan(&mut self, dx: f32, dy: f32, projection: Projection) {
+        let scale = match projection {
+            Projection::Perspective => self.distance() * 0.5,
+            Projection::Orthographic => self.view_height * 0.5,
+        };
+        let offset = (self.right() * -dx + self.up() * -dy) * scale;
+        self.eye += offset;
+        self.target += offset;
+    }
+
+    /// Move the eye towards the target by a factor of the distance between them.
+    pub fn zoom(&mut self, factor: f32) {
+        let distance = (self.distance() * factor).max(MIN_ORBIT_DISTANCE);
+        self.eye = self.target - self.direction() * distance;
+    }
+
+    /// Shrink or grow the orthographic view by a factor, leaving the eye where it is.
+    pub fn zoom_view(&mut self, factor: f32) {
+        self.view_height = (self.view_height * factor).max(MIN_VIEW_HEIGHT);
+    }
+
+    /// Move the eye and target together along the view direction.
+    pub fn dolly(&mut self, amount: f32) {
+        let offset = self.direction() * amount;
+        self.eye += offset;
+        self.target += offset;
+    }
+
+    /// Aim at the middle of a box and back away until all of it fits on screen.
+    pub fn frame(&mut self, min: [f32; 3], max: [f32; 3]) {
+        let min = Point3::from(min);
+        let max = Point3::from(max);
+        let center = na::center(&min, &max);
+        let radius = ((max - min).norm() / 2.0).max(1.0);
+        let distance =
+            (radius / (FIELD_OF_VIEW / 2.0).sin()).clamp(MIN_ORBIT_DISTANCE, MAX_FRAME_DISTANCE);
+        self.target = center;
+        self.eye = center - self.direction() * distance;
+        self.view_height = (radius * 2.0).max(MIN_VIEW_HEIGHT);
+    }
+
+    /// Look along an axis at a point from the given distance.
+    /// Looking straight up or down stops at the same pitch as orbiting.
+    pub fn look_along(&mut self, yaw: f32, pitch: f32, target: Point3<f32>, distance: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        self.target = target;
+        self.eye = target - self.direction() * distance;
+    }
 }
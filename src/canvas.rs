@@ -1,3 +1,5 @@
+use crate::color_vertex::ColorVertex;
+use crate::icon_cache::IconCache;
 use crate::image_vertex::ImageVertex;
 use crate::vertex::Vertex;
 use glium::Frame;
@@ -5,17 +7,35 @@ use glium::Surface;
 use glium::backend::glutin::Display;
 use glium::uniform;
 use glutin::surface::WindowSurface;
-use std::fs::File;
-use std::io::BufReader;
+
+/// A bitmap of DejaVu Sans Mono holding the printable ASCII characters from space to
+/// tilde, in rows of 16 cells.
+pub const FONT_PATH: &str = "resources/font.png";
+/// How many character cells fit across and down the font image.
+const FONT_COLUMNS: u32 = 16;
+const FONT_ROWS: u32 = 6;
+/// The width of a character cell over its height.
+const GLYPH_ASPECT: f32 = 20.0 / 32.0;
+/// How far the pen moves after each character, as a fraction of the cell width.
+const GLYPH_ADVANCE: f32 = 0.78;
 
 pub struct Canvas<'a> {
     display: &'a Display<WindowSurface>,
     frame: &'a mut Frame,
+    icons: &'a mut IconCache,
 }
 
 impl<'a> Canvas<'a> {
-    pub fn new(display: &'a Display<WindowSurface>, frame: &'a mut Frame) -> Self {
-        Canvas { display, frame }
+    pub fn new(
+        display: &'a Display<WindowSurface>,
+        frame: &'a mut Frame,
+        icons: &'a mut IconCache,
+    ) -> Self {
+        Canvas {
+            display,
+            frame,
+            icons,
+        }
     }
 
     pub fn draw_rectangle(&mut self, position: (f32, f32), size: (f32, f32), color: [f32; 4]) {
@@ -23,42 +43,36 @@ impl<'a> Canvas<'a> {
         let vertex1 = Vertex {
             position: [position.0, position.1, 0.0],
             normal: [0.0, 0.0, 1.0],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let vertex2 = Vertex {
             position: [position.0, position.1 + size.1, 0.0],
             normal: [0.0, 0.0, 1.0],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let vertex3 = Vertex {
             position: [position.0 + size.0, position.1 + size.1, 0.0],
             normal: [0.0, 0.0, 1.0],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let vertex4 = Vertex {
             position: [position.0 + size.0, position.1, 0.0],
             normal: [0.0, 0.0, 1.0],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let shape = vec![vertex1, vertex2, vertex3, vertex1, vertex3, vertex4];
 
         let vertex_buffer = glium::VertexBuffer::new(self.display, &shape).unwrap();
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-        let vertex_shader_src = r#"
-            #version 140
-
-            in vec3 position;
-            void main() {
-                gl_Position = vec4(position, 1.0);
-            }
-        "#;
-        let fragment_shader_src = r#"
-            #version 140
-            uniform vec4 u_color;
-            out vec4 color;
-            void main() {
-                color = u_color;
-            }
-        "#;
-        let program =
-            glium::Program::from_source(self.display, vertex_shader_src, fragment_shader_src, None)
-                .unwrap();
+        let program = self.icons.shape_program.as_ref().unwrap();
 
         let uniforms = uniform! {
         u_color: color,
@@ -70,7 +84,7 @@ impl<'a> Canvas<'a> {
         };
 
         self.frame
-            .draw(&vertex_buffer, &indices, &program, &uniforms, &params)
+            .draw(&vertex_buffer, &indices, program, &uniforms, &params)
             .unwrap();
     }
 
@@ -102,14 +116,23 @@ impl<'a> Canvas<'a> {
             vertices.push(Vertex {
                 position: [position.0, position.1, 0.0],
                 normal: [0.0, 0.0, 1.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             });
             vertices.push(Vertex {
                 position: [position.0 + x, position.1 + y, 0.0],
                 normal: [0.0, 0.0, 1.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             });
             vertices.push(Vertex {
                 position: [position.0 + x2, position.1 + y2, 0.0],
                 normal: [0.0, 0.0, 1.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             });
             angle += pie_angle;
             end_angle = angle + pie_angle;
@@ -117,25 +140,7 @@ impl<'a> Canvas<'a> {
 
         let vertex_buffer = glium::VertexBuffer::new(self.display, &vertices).unwrap();
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-        let vertex_shader_src = r#"
-            #version 140
-
-            in vec3 position;
-            void main() {
-                gl_Position = vec4(position, 1.0);
-            }
-        "#;
-        let fragment_shader_src = r#"
-            #version 140
-            uniform vec4 u_color;
-            out vec4 color;
-            void main() {
-                color = u_color;
-            }
-        "#;
-        let program =
-            glium::Program::from_source(self.display, vertex_shader_src, fragment_shader_src, None)
-                .unwrap();
+        let program = self.icons.shape_program.as_ref().unwrap();
 
         let uniforms = uniform! {
             u_color: color,
@@ -147,7 +152,7 @@ impl<'a> Canvas<'a> {
         };
 
         self.frame
-            .draw(&vertex_buffer, &indices, &program, &uniforms, &params)
+            .draw(&vertex_buffer, &indices, program, &uniforms, &params)
             .unwrap();
     }
 
@@ -216,17 +221,8 @@ impl<'a> Canvas<'a> {
     }
 
     pub fn draw_image(&mut self, position: (f32, f32), size: (f32, f32), icon_path: &str) {
-        let image_file = File::open(icon_path).unwrap();
-        let buffered_reader = BufReader::new(image_file);
-        let image = image::load(buffered_reader, image::ImageFormat::Png)
-            .unwrap()
-            .to_rgba8();
-        let image_dimensions = image.dimensions();
-        let image =
-            glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-
-        // 3. Create a glium texture
-        let texture = glium::texture::SrgbTexture2d::new(self.display, image).unwrap(); // Use SrgbTexture2d for correct color handling
+        self.icons.load(self.display, icon_path);
+        let texture = self.icons.texture(icon_path).unwrap();
 
         // 4. Define the quad vertices (full screen)
         let shape = vec![
@@ -257,46 +253,155 @@ impl<'a> Canvas<'a> {
         ];
         let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
         let vertex_buffer = glium::VertexBuffer::new(self.display, &shape).unwrap();
-        let vertex_shader_src = r#"
-                #version 140
+        let program = self.icons.image_program.as_ref().unwrap();
+
+        let uniforms = uniform! {
+            tex: texture,
+        };
+        let params = glium::DrawParameters {
+            line_width: Some(2.0),
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        self.frame
+            .draw(&vertex_buffer, &indices, program, &uniforms, &params)
+            .unwrap();
+    }
+
+    /// Draw a list of triangles, blending the colors of their corners.
+    pub fn draw_triangles(&mut self, vertices: &[ColorVertex]) {
+        let vertex_buffer = glium::VertexBuffer::new(self.display, vertices).unwrap();
+        let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+        let program = self.icons.gradient_program.as_ref().unwrap();
+        let params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        };
 
-                in vec2 position;
-                in vec2 tex_coords;
-                out vec2 v_tex_coords;
+        self.frame
+            .draw(
+                &vertex_buffer,
+                indices,
+                program,
+                &glium::uniforms::EmptyUniforms,
+                &params,
+            )
+            .unwrap();
+    }
 
-                void main() {
-                    v_tex_coords = tex_coords;
+    /// The height of the window over its width, for drawing shapes that should stay round.
+    pub fn aspect(&self) -> f32 {
+        let (width, height) = self.frame.get_dimensions();
+        height as f32 / width.max(1) as f32
+    }
 
-                    gl_Position = vec4(position, 0.0, 1.0);
-                }
-            "#;
-        let fragment_shader_src = r#"
-                #version 140
+    /// How wide a line of text is, measured the same way as positions.
+    pub fn text_width(&self, height: f32, text: &str) -> f32 {
+        let (width, window_height) = self.frame.get_dimensions();
+        let aspect = window_height as f32 / width.max(1) as f32;
+        height * GLYPH_ASPECT * GLYPH_ADVANCE * aspect * text.chars().count() as f32
+    }
 
-                in vec2 v_tex_coords;
-                out vec4 color;
+    /// Draw one line of text with its bottom left corner at the position. The height
+    /// is the height of a character cell, which leaves room for descenders.
+    pub fn draw_text(&mut self, position: (f32, f32), height: f32, text: &str, color: [f32; 4]) {
+        self.icons.load(self.display, FONT_PATH);
+        let texture = self.icons.texture(FONT_PATH).unwrap();
 
-                uniform sampler2D tex;
+        // Keep the characters the same shape however the window is stretched.
+        let (width, window_height) = self.frame.get_dimensions();
+        let cell_width = height * GLYPH_ASPECT * window_height as f32 / width.max(1) as f32;
+        let advance = cell_width * GLYPH_ADVANCE;
 
-                void main() {
-                    color = texture(tex, v_tex_coords);
-                }
-            "#;
-        let program =
-            glium::Program::from_source(self.display, vertex_shader_src, fragment_shader_src, None)
-                .unwrap();
+        let mut shape = Vec::with_capacity(text.len() * 6);
+        let mut x = position.0;
+        for character in text.chars() {
+            // Anything the font does not have is drawn as a question mark.
+            let code = match character {
+                ' '..='~' => character as u32,
+                _ => '?' as u32,
+            } - ' ' as u32;
+            let column = (code % FONT_COLUMNS) as f32;
+            let row = (code / FONT_COLUMNS) as f32;
+            let left = column / FONT_COLUMNS as f32;
+            let right = (column + 1.0) / FONT_COLUMNS as f32;
+            // The image is flipped when it is loaded, so the first row is at the top.
+            let top = 1.0 - row / FONT_ROWS as f32;
+            let bottom = 1.0 - (row + 1.0) / FONT_ROWS as f32;
+            let (x0, y0) = (x, position.1);
+            let (x1, y1) = (x + cell_width, position.1 + height);
+            shape.extend([
+                ImageVertex {
+                    position: [x0, y0],
+                    tex_coords: [left, bottom],
+                },
+                ImageVertex {
+                    position: [x1, y0],
+                    tex_coords: [right, bottom],
+                },
+                ImageVertex {
+                    position: [x1, y1],
+                    tex_coords: [right, top],
+                },
+                ImageVertex {
+                    position: [x1, y1],
+                    tex_coords: [right, top],
+                },
+                ImageVertex {
+                    position: [x0, y1],
+                    tex_coords: [left, top],
+                },
+                ImageVertex {
+                    position: [x0, y0],
+                    tex_coords: [left, bottom],
+                },
+            ]);
+            x += advance;
+        }
+        if shape.is_empty() {
+            return;
+        }
+
+        let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+        let vertex_buffer = glium::VertexBuffer::new(self.display, &shape).unwrap();
+        let program = self.icons.text_program.as_ref().unwrap();
 
         let uniforms = uniform! {
-            tex: &texture,
+            tex: texture,
+            u_color: color,
         };
         let params = glium::DrawParameters {
-            line_width: Some(2.0),
             blend: glium::Blend::alpha_blending(),
             ..Default::default()
         };
 
         self.frame
-            .draw(&vertex_buffer, &indices, &program, &uniforms, &params)
+            .draw(&vertex_buffer, indices, program, &uniforms, &params)
             .unwrap();
     }
+
+    /// Draw a line of text in a box just above the area at position and size, kept
+    /// inside the window.
+    pub fn draw_tooltip(&mut self, position: (f32, f32), size: (f32, f32), text: &str) {
+        let text_height = 0.045;
+        let padding = 0.015;
+        let width = self.text_width(text_height, text) + padding * 2.0;
+        let height = text_height + padding * 2.0;
+        let x = position.0.min(1.0 - width).max(-1.0);
+        let y = position.1 + size.1 + 0.01;
+        self.draw_rectangle_with_border(
+            (x, y),
+            (width, height),
+            [0.15, 0.15, 0.15, 0.9],
+            0.005,
+            [0.6, 0.6, 0.6, 0.9],
+        );
+        self.draw_text(
+            (x + padding, y + padding),
+            text_height,
+            text,
+            [1.0, 1.0, 1.0, 1.0],
+        );
+    }
 }
@@ -0,0 +1,548 @@
+use crate::button::ICON_INSET;
+use crate::canvas::Canvas;
+use crate::color_vertex::ColorVertex;
+use crate::command::{Command, CommandType, EYEDROPPER};
+use crate::icon_cache::IconCache;
+use glium::Frame;
+use glium::backend::glutin::Display;
+use glutin::surface::WindowSurface;
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+/// The radius of the hue ring, measured the same way as heights.
+const WHEEL_RADIUS: f32 = 0.2;
+/// The inside of the hue ring as a fraction of its outside.
+const RING_INNER: f32 = 0.78;
+/// How many pieces the hue ring is drawn with.
+const RING_SEGMENTS: usize = 48;
+/// The corners of the triangle sit just inside the ring.
+const TRIANGLE_RADIUS: f32 = WHEEL_RADIUS * RING_INNER * 0.95;
+/// The space around everything in the panel.
+const MARGIN: f32 = 0.03;
+/// The height of the hex field and the eyedropper button.
+const FIELD_HEIGHT: f32 = 0.07;
+
+/// Turn a hue from 0 to 1, saturation and value into red, green and blue.
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let chroma = value * saturation;
+    let rising = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let [red, green, blue] = match sector as u32 {
+        0 => [chroma, rising, 0.0],
+        1 => [rising, chroma, 0.0],
+        2 => [0.0, chroma, rising],
+        3 => [0.0, rising, chroma],
+        4 => [rising, 0.0, chroma],
+        _ => [chroma, 0.0, rising],
+    };
+    let lowest = value - chroma;
+    [red + lowest, green + lowest, blue + lowest]
+}
+
+/// Turn red, green and blue into a hue from 0 to 1, saturation and value.
+pub fn rgb_to_hsv(color: [f32; 3]) -> (f32, f32, f32) {
+    let [red, green, blue] = color.map(|channel| channel.clamp(0.0, 1.0));
+    let value = red.max(green).max(blue);
+    let chroma = value - red.min(green).min(blue);
+    if chroma <= 0.0 {
+        return (0.0, 0.0, value);
+    }
+    let sector = if value == red {
+        (green - blue) / chroma
+    } else if value == green {
+        (blue - red) / chroma + 2.0
+    } else {
+        (red - green) / chroma + 4.0
+    };
+    ((sector / 6.0).rem_euclid(1.0), chroma / value, value)
+}
+
+/// Read a hex code with six or eight digits. Six digits keep the alpha given.
+pub fn parse_hex(text: &str, alpha: f32) -> Option<[f32; 4]> {
+    let text = text.trim_start_matches('#');
+    if text.len() != 6 && text.len() != 8 {
+        return None;
+    }
+    let channel = |index: usize| {
+        u8::from_str_radix(text.get(index * 2..index * 2 + 2)?, 16)
+            .ok()
+            .map(|value| value as f32 / 255.0)
+    };
+    let alpha = if text.len() == 8 { channel(3)? } else { alpha };
+    Some([channel(0)?, channel(1)?, channel(2)?, alpha])
+}
+
+/// The part of the picker being dragged.
+#[derive(Copy, Clone, PartialEq)]
+enum Drag {
+    Hue,
+    SaturationValue,
+}
+
+/// A button that opens a panel for choosing the material color with a hue ring and a
+/// saturation and value triangle, typing a hex code or sampling a voxel.
+pub struct ColorPicker {
+    /// Where the button that opens the panel is.
+    pub position: (f32, f32),
+    pub size: (f32, f32),
+    /// Where the panel is drawn while it is open.
+    pub panel_position: (f32, f32),
+    pub panel_size: (f32, f32),
+    open: bool,
+    /// The color as sent to the scene.
+    color: [f32; 4],
+    /// Kept apart from the color so the hue survives greys.
+    hue: f32,
+    saturation: f32,
+    value: f32,
+    dragging: Option<Drag>,
+    /// The digits typed so far, while the hex field has the keyboard.
+    hex_entry: Option<String>,
+    /// Is the next click on a voxel going to sample its color?
+    eyedropper: bool,
+    /// The window height over its width from the last draw, so the wheel stays round.
+    aspect: f32,
+}
+
+impl ColorPicker {
+    pub fn new(position: (f32, f32), size: (f32, f32), color: [f32; 4]) -> Self {
+        let (hue, saturation, value) = rgb_to_hsv([color[0], color[1], color[2]]);
+        let mut color_picker = ColorPicker {
+            position,
+            size,
+            panel_position: (0.0, 0.0),
+            panel_size: (0.46, MARGIN * 3.0 + WHEEL_RADIUS * 2.0 + FIELD_HEIGHT),
+            open: false,
+            color,
+            hue,
+            saturation,
+            value,
+            dragging: None,
+            hex_entry: None,
+            eyedropper: false,
+            aspect: 1.0,
+        };
+        color_picker.place_panel();
+        color_picker
+    }
+
+    /// Open the panel just above the button, kept inside the window.
+    fn place_panel(&mut self) {
+        self.panel_position = (
+            self.position.0.min(1.0 - MARGIN - self.panel_size.0),
+            (self.position.1 + self.size.1 + MARGIN).min(1.0 - self.panel_size.1),
+        );
+    }
+
+    /// The color as a hex code with alpha.
+    fn hex(&self) -> String {
+        let [red, green, blue, alpha] = self
+            .color
+            .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        format!("{red:02X}{green:02X}{blue:02X}{alpha:02X}")
+    }
+
+    fn wheel_center(&self) -> (f32, f32) {
+        (
+            self.panel_position.0 + self.panel_size.0 / 2.0,
+            self.panel_position.1 + self.panel_size.1 - MARGIN - WHEEL_RADIUS,
+        )
+    }
+
+    /// Convert window coordinates to coordinates around the wheel center that are
+    /// the same size across and down.
+    fn window_to_wheel(&self, x: f32, y: f32) -> (f32, f32) {
+        let center = self.wheel_center();
+        ((x - center.0) / self.aspect, y - center.1)
+    }
+
+    /// Convert coordinates around the wheel center back to window coordinates.
+    fn wheel_to_window(&self, point: (f32, f32)) -> (f32, f32) {
+        let center = self.wheel_center();
+        (center.0 + point.0 * self.aspect, center.1 + point.1)
+    }
+
+    /// The corners of the triangle around the wheel center: full color, white and black.
+    fn triangle(&self) -> [(f32, f32); 3] {
+        [FRAC_PI_2, FRAC_PI_2 - TAU / 3.0, FRAC_PI_2 + TAU / 3.0]
+            .map(|angle| (angle.cos() * TRIANGLE_RADIUS, angle.sin() * TRIANGLE_RADIUS))
+    }
+
+    /// How much of each corner of the triangle makes up a point.
+    fn triangle_weights(&self, point: (f32, f32)) -> [f32; 3] {
+        let [a, b, c] = self.triangle();
+        let v0 = (b.0 - a.0, b.1 - a.1);
+        let v1 = (c.0 - a.0, c.1 - a.1);
+        let v2 = (point.0 - a.0, point.1 - a.1);
+        let d00 = v0.0 * v0.0 + v0.1 * v0.1;
+        let d01 = v0.0 * v1.0 + v0.1 * v1.1;
+        let d11 = v1.0 * v1.0 + v1.1 * v1.1;
+        let d20 = v2.0 * v0.0 + v2.1 * v0.1;
+        let d21 = v2.0 * v1.0 + v2.1 * v1.1;
+        let denominator = d00 * d11 - d01 * d01;
+        let white = (d11 * d20 - d01 * d21) / denominator;
+        let black = (d00 * d21 - d01 * d20) / denominator;
+        [1.0 - white - black, white, black]
+    }
+
+    fn hex_field(&self) -> ((f32, f32), (f32, f32)) {
+        let button_width = FIELD_HEIGHT * self.aspect;
+        (
+            (
+                self.panel_position.0 + MARGIN,
+                self.panel_position.1 + MARGIN,
+            ),
+            (
+                self.panel_size.0 - MARGIN * 3.0 - button_width,
+                FIELD_HEIGHT,
+            ),
+        )
+    }
+
+    fn eyedropper_button(&self) -> ((f32, f32), (f32, f32)) {
+        let button_width = FIELD_HEIGHT * self.aspect;
+        (
+            (
+                self.panel_position.0 + self.panel_size.0 - MARGIN - button_width,
+                self.panel_position.1 + MARGIN,
+            ),
+            (button_width, FIELD_HEIGHT),
+        )
+    }
+
+    /// Pick the hue from the angle around the wheel.
+    fn drag_hue(&mut self, x: f32, y: f32) -> Vec<Command> {
+        let (dx, dy) = self.window_to_wheel(x, y);
+        self.hue = dy.atan2(dx).rem_euclid(TAU) / TAU;
+        self.apply_hsv()
+    }
+
+    /// Pick the saturation and value from a point in the triangle, holding the point
+    /// to the triangle while dragging outside it.
+    fn drag_saturation_value(&mut self, x: f32, y: f32) -> Vec<Command> {
+        let weights = self.triangle_weights(self.window_to_wheel(x, y));
+        let weights = weights.map(|weight| weight.max(0.0));
+        let total: f32 = weights.iter().sum();
+        let [full, white, _] = weights.map(|weight| weight / total);
+        self.value = (full + white).clamp(0.0, 1.0);
+        self.saturation = if self.value > 0.0 {
+            (full / self.value).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self.apply_hsv()
+    }
+
+    fn apply_hsv(&mut self) -> Vec<Command> {
+        let [red, green, blue] = hsv_to_rgb(self.hue, self.saturation, self.value);
+        self.color = [red, green, blue, self.color[3]];
+        self.color_commands()
+    }
+
+    /// Follow a color chosen somewhere else, keeping the hue for greys.
+    fn set_channel(&mut self, channel: usize, value: f32) {
+        if (self.color[channel] - value).abs() < 0.0001 {
+            return;
+        }
+        self.color[channel] = value;
+        let (hue, saturation, value) = rgb_to_hsv([self.color[0], self.color[1], self.color[2]]);
+        if saturation > 0.0 {
+            self.hue = hue;
+        }
+        self.saturation = saturation;
+        self.value = value;
+    }
+
+    /// Send the color to the scene the same way the sliders do.
+    fn color_commands(&self) -> Vec<Command> {
+        [
+            CommandType::SetMaterialRed,
+            CommandType::SetMaterialGreen,
+            CommandType::SetMaterialBlue,
+            CommandType::SetMaterialAlpha,
+        ]
+        .iter()
+        .zip(self.color)
+        .enumerate()
+        .map(|(channel, (command_type, value))| Command {
+            command_type: *command_type,
+            data1: value.to_bits(),
+            data2: channel as u32,
+        })
+        .collect()
+    }
+
+    fn handle_text_input(&mut self, character: char) -> Vec<Command> {
+        let Some(entry) = &mut self.hex_entry else {
+            return Vec::new();
+        };
+        match character {
+            '\u{8}' => {
+                entry.pop();
+            }
+            '\u{1b}' => self.hex_entry = None,
+            '\r' => {
+                let color = parse_hex(entry, self.color[3]);
+                self.hex_entry = None;
+                if let Some(color) = color {
+                    self.color = color;
+                    let (hue, saturation, value) = rgb_to_hsv([color[0], color[1], color[2]]);
+                    if saturation > 0.0 {
+                        self.hue = hue;
+                    }
+                    self.saturation = saturation;
+                    self.value = value;
+                    return self.color_commands();
+                }
+            }
+            _ if character.is_ascii_hexdigit() && entry.len() < 8 => {
+                entry.push(character.to_ascii_uppercase());
+            }
+            _ => (),
+        }
+        Vec::new()
+    }
+}
+
+/// Is the point inside the rectangle?
+fn inside(x: f32, y: f32, position: (f32, f32), size: (f32, f32)) -> bool {
+    x >= position.0 && x <= position.0 + size.0 && y >= position.1 && y <= position.1 + size.1
+}
+
+use crate::widget::Widget;
+
+impl Widget for ColorPicker {
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache) {
+        let mut canvas = Canvas::new(display, frame, icons);
+
+        let border_color = [0.1, 0.1, 0.1, 0.8];
+        let border = 0.01;
+        let color = if self.open {
+            [0.8, 0.8, 0.8, 1.0]
+        } else {
+            [0.6, 0.6, 0.6, 1.0]
+        };
+        canvas.draw_rectangle_with_border(self.position, self.size, color, border, border_color);
+        let inset = (self.size.0 * ICON_INSET, self.size.1 * ICON_INSET);
+        canvas.draw_image(
+            (self.position.0 + inset.0, self.position.1 + inset.1),
+            (self.size.0 - inset.0 * 2.0, self.size.1 - inset.1 * 2.0),
+            "resources/color-wheel.png",
+        );
+        if !self.open {
+            return;
+        }
+        self.aspect = canvas.aspect();
+
+        canvas.draw_rectangle_with_border(
+            self.panel_position,
+            self.panel_size,
+            [0.2, 0.2, 0.2, 0.9],
+            border,
+            border_color,
+        );
+
+        // The hue ring.
+        let mut vertices = Vec::with_capacity(RING_SEGMENTS * 6);
+        for segment in 0..RING_SEGMENTS {
+            let start = segment as f32 / RING_SEGMENTS as f32;
+            let end = (segment + 1) as f32 / RING_SEGMENTS as f32;
+            let corner = |hue: f32, radius: f32| {
+                let angle = hue * TAU;
+                let [red, green, blue] = hsv_to_rgb(hue, 1.0, 1.0);
+                let (x, y) = self.wheel_to_window((angle.cos() * radius, angle.sin() * radius));
+                ColorVertex {
+                    position: [x, y],
+                    color: [red, green, blue, 1.0],
+                }
+            };
+            let inner = WHEEL_RADIUS * RING_INNER;
+            vertices.extend([
+                corner(start, inner),
+                corner(start, WHEEL_RADIUS),
+                corner(end, WHEEL_RADIUS),
+                corner(start, inner),
+                corner(end, WHEEL_RADIUS),
+                corner(end, inner),
+            ]);
+        }
+
+        // The triangle blends from the full hue to white and black.
+        let [red, green, blue] = hsv_to_rgb(self.hue, 1.0, 1.0);
+        let colors = [
+            [red, green, blue, 1.0],
+            [1.0, 1.0, 1.0, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        for (point, color) in self.triangle().iter().zip(colors) {
+            let (x, y) = self.wheel_to_window(*point);
+            vertices.push(ColorVertex {
+                position: [x, y],
+                color,
+            });
+        }
+        canvas.draw_triangles(&vertices);
+
+        // Mark the chosen hue, saturation and value.
+        let angle = self.hue * TAU;
+        let middle = WHEEL_RADIUS * (1.0 + RING_INNER) / 2.0;
+        let hue_marker = self.wheel_to_window((angle.cos() * middle, angle.sin() * middle));
+        let [full, white, black] = self.triangle();
+        let weights = [
+            self.saturation * self.value,
+            self.value * (1.0 - self.saturation),
+            1.0 - self.value,
+        ];
+        let value_marker = self.wheel_to_window((
+            full.0 * weights[0] + white.0 * weights[1] + black.0 * weights[2],
+            full.1 * weights[0] + white.1 * weights[1] + black.1 * weights[2],
+        ));
+        for marker in [hue_marker, value_marker] {
+            canvas.draw_circle(marker, 0.018, [1.0, 1.0, 1.0, 1.0], 0.0, TAU);
+            canvas.draw_circle(marker, 0.01, [0.0, 0.0, 0.0, 1.0], 0.0, TAU);
+        }
+
+        // The hex field shows what is being typed, with a cursor.
+        let (field_position, field_size) = self.hex_field();
+        let (field_color, text) = match &self.hex_entry {
+            Some(entry) => ([0.1, 0.1, 0.1, 1.0], format!("#{entry}_")),
+            None => ([0.3, 0.3, 0.3, 1.0], format!("#{}", self.hex())),
+        };
+        canvas.draw_rectangle_with_border(
+            field_position,
+            field_size,
+            field_color,
+            border / 2.0,
+            border_color,
+        );
+        let text_height = field_size.1 * 0.7;
+        canvas.draw_text(
+            (
+                field_position.0 + field_size.1 * 0.15,
+                field_position.1 + field_size.1 * 0.15,
+            ),
+            text_height,
+            &text,
+            [1.0, 1.0, 1.0, 1.0],
+        );
+
+        let (button_position, button_size) = self.eyedropper_button();
+        let button_color = if self.eyedropper {
+            [1.0, 0.9, 0.5, 1.0]
+        } else {
+            [0.6, 0.6, 0.6, 1.0]
+        };
+        canvas.draw_rectangle_with_border(
+            button_position,
+            button_size,
+            button_color,
+            border / 2.0,
+            border_color,
+        );
+        canvas.draw_image(
+            (button_position.0 + border, button_position.1 + border),
+            (button_size.0 - border * 2.0, button_size.1 - border * 2.0),
+            "resources/color-picker.png",
+        );
+    }
+
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32)) {
+        self.position = position;
+        self.size = size;
+        self.place_panel();
+    }
+
+    fn process_command(&mut self, command: &Command) -> Vec<Command> {
+        match command.command_type {
+            CommandType::MouseDown => {
+                let x = f32::from_bits(command.data1);
+                let y = f32::from_bits(command.data2);
+                if inside(x, y, self.position, self.size) {
+                    self.open = !self.open;
+                    self.hex_entry = None;
+                    return Vec::new();
+                }
+                if !self.open || !inside(x, y, self.panel_position, self.panel_size) {
+                    self.hex_entry = None;
+                    return Vec::new();
+                }
+                let (field_position, field_size) = self.hex_field();
+                if inside(x, y, field_position, field_size) {
+                    self.hex_entry = Some(String::new());
+                    return Vec::new();
+                }
+                self.hex_entry = None;
+                let (button_position, button_size) = self.eyedropper_button();
+                if inside(x, y, button_position, button_size) {
+                    self.eyedropper = true;
+                    return vec![Command {
+                        command_type: CommandType::PickMaterial,
+                        data1: EYEDROPPER,
+                        data2: EYEDROPPER,
+                    }];
+                }
+                let (dx, dy) = self.window_to_wheel(x, y);
+                let distance = (dx * dx + dy * dy).sqrt();
+                if (WHEEL_RADIUS * RING_INNER..=WHEEL_RADIUS).contains(&distance) {
+                    self.dragging = Some(Drag::Hue);
+                    return self.drag_hue(x, y);
+                }
+                if self
+                    .triangle_weights((dx, dy))
+                    .iter()
+                    .all(|weight| *weight >= 0.0)
+                {
+                    self.dragging = Some(Drag::SaturationValue);
+                    return self.drag_saturation_value(x, y);
+                }
+            }
+            CommandType::MouseMoved => {
+                let x = f32::from_bits(command.data1);
+                let y = f32::from_bits(command.data2);
+                match self.dragging {
+                    Some(Drag::Hue) => return self.drag_hue(x, y),
+                    Some(Drag::SaturationValue) => return self.drag_saturation_value(x, y),
+                    None => (),
+                }
+            }
+            CommandType::MouseUp => {
+                self.dragging = None;
+            }
+            CommandType::TextInput => {
+                if let Some(character) = char::from_u32(command.data1) {
+                    return self.handle_text_input(character);
+                }
+            }
+            CommandType::SetMaterialRed => self.set_channel(0, f32::from_bits(command.data1)),
+            CommandType::SetMaterialGreen => self.set_channel(1, f32::from_bits(command.data1)),
+            CommandType::SetMaterialBlue => self.set_channel(2, f32::from_bits(command.data1)),
+            CommandType::SetMaterialAlpha => self.set_channel(3, f32::from_bits(command.data1)),
+            // The scene answers the eyedropper one channel at a time.
+            CommandType::CurrentMaterialRed
+            | CommandType::CurrentMaterialGreen
+            | CommandType::CurrentMaterialBlue
+            | CommandType::CurrentMaterialAlpha
+                if command.data2 == EYEDROPPER =>
+            {
+                self.eyedropper = false;
+                let channel = match command.command_type {
+                    CommandType::CurrentMaterialRed => 0,
+                    CommandType::CurrentMaterialGreen => 1,
+                    CommandType::CurrentMaterialBlue => 2,
+                    _ => 3,
+                };
+                self.set_channel(channel, f32::from_bits(command.data1));
+                return vec![self.color_commands()[channel]];
+            }
+            _ => (),
+        }
+        Vec::new()
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        self.open && inside(x, y, self.panel_position, self.panel_size)
+    }
+
+    fn has_focus(&self) -> bool {
+        self.hex_entry.is_some()
+    }
+}
@@ -0,0 +1,9 @@
+use glium::implement_vertex;
+
+#[derive(Copy, Clone)]
+pub struct ColorVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+implement_vertex!(ColorVertex, position, color);
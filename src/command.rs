@@ -2,10 +2,18 @@
 #[derive(Copy, Clone, Debug)]
 pub enum CommandType {
     MouseMoved,
+    /// The mouse moved, whether or not a button is held. Only sent to the UI.
+    MouseHover,
+    /// The pointer moved onto or off the UI. data1 is 1 over the UI. Only sent to the scene.
+    PointerOverUi,
     MouseDown,
     MouseUp,
+    MiddleMouseDown,
+    MiddleMouseUp,
     MouseClick,
     KeyDown,
+    /// data1 and data2 are the horizontal and vertical scroll as f32 bits, positive
+    /// for scrolling up.
     MouseScroll,
     SliderMoved,
     SetMaterialRed,
@@ -17,15 +25,87 @@ pub enum CommandType {
     CurrentMaterialGreen,
     CurrentMaterialBlue,
     CurrentMaterialAlpha,
-    CurrentMaterialNoise,
-    CurrentMaterialNoiseX,
-    CurrentMaterialNoiseY,
-    CurrentMaterialNoiseZ,
-    CurrentMaterialFluid,
-    UpdateCurrentMaterialRed,
-    UpdateCurrentMaterialGreen,
-    UpdateCurrentMaterialBlue,
-    UpdateCurrentMaterialAlpha,
+    /// Move one of the other material sliders. data1 is the position from 0 to 1 as f32
+    /// bits and data2 the slider index.
+    SetMaterialSetting,
+    /// Show a state on the button for a virtual key. data1 is the key and data2 the state.
+    SetButtonState,
+    SetLightAzimuth,
+    SetLightElevation,
+    SetLightIntensity,
+    SetAmbientLight,
+    SetShadowBias,
+    SetShadowSoftness,
+    SetFogDistance,
+    ModifiersChanged,
+    /// The selection moved. data1 is the axis from 0 to 2 and data2 the position on it as an i32.
+    SelectionPositionChanged,
+    /// data1 is the new selection radius.
+    SelectionSizeChanged,
+    /// data1 is the index of the new selection shape.
+    SelectionShapeChanged,
+    /// data1 is the material color packed as RGBA bytes and data2 the pattern index,
+    /// with MATERIAL_FLUID set when the material is fluid.
+    MaterialChanged,
+    /// data1 is the number of filled voxels.
+    VoxelCountChanged,
+    /// data1 is 1 when the scene has changed since it was last opened or saved.
+    UnsavedChangesChanged,
+    /// data1 is the frame rate as f32 bits.
+    FramesPerSecond,
+    /// A character typed while a widget or the scene has the keyboard. data1 is the character, with
+    /// backspace, enter and escape sent as their control characters.
+    TextInput,
+    /// Use the material in a palette swatch. data1 is the swatch index.
+    ApplySwatch,
+    /// Keep the current material in a palette swatch. data1 is the swatch index, and an
+    /// index past the last swatch adds a new one.
+    StoreSwatch,
+    /// Take a swatch out of the palette. data1 is the swatch index.
+    RemoveSwatch,
+    /// Select every voxel painted with the material in a swatch. data1 is the swatch index.
+    SelectSwatchVoxels,
+    /// data1 is the number of swatches in the palette.
+    PaletteSizeChanged,
+    /// data1 is the swatch index and data2 its color packed as RGBA bytes.
+    SwatchColorChanged,
+    /// data1 is the swatch index and data2 its pattern index, with MATERIAL_FLUID set
+    /// when the material is fluid.
+    SwatchPatternChanged,
+    /// data1 is the swatch index and data2 how many voxels are painted with it.
+    SwatchUsageChanged,
+}
+
+/// Bits set while each modifier key is held, in the data1 of a ModifiersChanged command
+/// and the data2 of a KeyDown command.
+pub const MODIFIER_SHIFT: u32 = 1;
+pub const MODIFIER_CONTROL: u32 = 2;
+pub const MODIFIER_ALT: u32 = 4;
+pub const MODIFIER_SUPER: u32 = 8;
+
+/// All the modifier bits.
+pub const MODIFIERS: u32 = MODIFIER_SHIFT | MODIFIER_CONTROL | MODIFIER_ALT | MODIFIER_SUPER;
+
+/// Set in the data2 of a KeyDown command when it comes from a key being held down.
+pub const KEY_REPEAT: u32 = 16;
+
+/// Set in the data2 of a MaterialChanged command when the material is fluid.
+pub const MATERIAL_FLUID: u32 = 256;
+
+/// Sent as the data1 of a PickMaterial command to sample the next voxel clicked, and as the
+/// data2 of the CurrentMaterial commands that answer it.
+pub const EYEDROPPER: u32 = u32::MAX;
+
+/// Pack a color into RGBA bytes to send it in one command field.
+pub fn pack_color(color: [f32; 4]) -> u32 {
+    let [red, green, blue, alpha] =
+        color.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u32);
+    red << 24 | green << 16 | blue << 8 | alpha
+}
+
+/// Unpack a color sent with pack_color.
+pub fn unpack_color(packed: u32) -> [f32; 4] {
+    [24, 16, 8, 0].map(|shift| ((packed >> shift) & 255) as f32 / 255.0)
 }
 
 /// A command that can be queued with the data that came with it.
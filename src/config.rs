@@ -0,0 +1,30 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// The folder the settings kept between sessions live in, like ~/.config/crafter on Linux.
+/// Falls back to the working directory when there is no home folder.
+pub fn config_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    match base {
+        Some(base) => base.join("crafter"),
+        None => PathBuf::from("."),
+    }
+}
+
+/// Where a settings file is kept, creating the settings folder if it is missing.
+pub fn config_path(file_name: &str) -> String {
+    let dir = config_dir();
+    if let Err(error) = fs::create_dir_all(&dir) {
+        log::error!("Could not create {}: {error}", dir.display());
+    }
+    dir.join(file_name).to_string_lossy().to_string()
+}
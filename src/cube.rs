@@ -1,3 +1,5 @@
+use crate::fluid::FluidSettings;
+use crate::pattern::PatternSettings;
 use crate::vertex::Vertex;
 use glium::index::PrimitiveType;
 use nalgebra::Isometry3;
@@ -14,10 +16,16 @@ pub struct Cube {
     pub center: f32,
     pub floor: f32,
     pub fluid: i32,
-    pub noise: i32,
-    pub noise_x: i32,
-    pub noise_y: i32,
-    pub noise_z: i32,
+    pub fluid_settings: FluidSettings,
+    pub pattern: PatternSettings,
+    /// How strongly the cube glows, from 0 to 1.
+    pub emissive: f32,
+    /// How rough the surface is, from 0 (mirror like) to 1 (matte).
+    pub roughness: f32,
+    /// How metallic the surface is, from 0 to 1.
+    pub metalness: f32,
+    /// The texture set drawn on the faces, 0 for none, otherwise the set number plus one.
+    pub texture: u32,
     pub bottom_occluded: bool,
     pub left_occluded: bool,
     pub right_occluded: bool,
@@ -25,9 +33,14 @@ pub struct Cube {
     pub back_occluded: bool,
     pub top_occluded: bool,
     pub smooth: bool,
+    /// Bit mask of the filled voxels in the 3x3x3 block around this one.
+    pub neighbours: u32,
     pub key: u64,
 }
 
+/// How much light reaches a face corner for each count of open neighbours.
+const AMBIENT_OCCLUSION_CURVE: [f32; 4] = [0.45, 0.65, 0.82, 1.0];
+
 use nalgebra_glm::Vec3;
 
 use crate::drawable::Drawable;
@@ -43,10 +56,12 @@ impl Cube {
             center: 0.5,
             floor: 0.0001,
             fluid: 0,
-            noise: 0,
-            noise_x: 0,
-            noise_y: 0,
-            noise_z: 0,
+            fluid_settings: FluidSettings::new(),
+            pattern: PatternSettings::new(),
+            emissive: 0.0,
+            roughness: 1.0,
+            metalness: 0.0,
+            texture: 0,
             bottom_occluded: false,
             left_occluded: false,
             right_occluded: false,
@@ -54,9 +69,111 @@ impl Cube {
             back_occluded: false,
             top_occluded: false,
             smooth: false,
+            neighbours: 0,
             key: 0,
         }
     }
+
+    /// Is the neighbouring voxel at this offset filled? Each offset is -1, 0 or 1.
+    pub fn neighbour(&self, offset: [i32; 3]) -> bool {
+        let bit = (offset[0] + 1) + (offset[1] + 1) * 3 + (offset[2] + 1) * 9;
+        self.neighbours & (1 << bit) != 0
+    }
+
+    /// Classic voxel ambient occlusion for one corner of a face.
+    /// Two filled sides block the corner completely, otherwise each filled neighbour darkens it.
+    fn corner_occlusion(&self, normal: [i32; 3], first: [i32; 3], second: [i32; 3]) -> f32 {
+        let side1 = self.neighbour([
+            normal[0] + first[0],
+            normal[1] + first[1],
+            normal[2] + first[2],
+        ]);
+        let side2 = self.neighbour([
+            normal[0] + second[0],
+            normal[1] + second[1],
+            normal[2] + second[2],
+        ]);
+        let corner = self.neighbour([
+            normal[0] + first[0] + second[0],
+            normal[1] + first[1] + second[1],
+            normal[2] + first[2] + second[2],
+        ]);
+        let open = if side1 && side2 {
+            0
+        } else {
+            3 - (side1 as usize + side2 as usize + corner as usize)
+        };
+        AMBIENT_OCCLUSION_CURVE[open]
+    }
+
+    /// Lay the texture across each face so it repeats once per voxel and is upright on the sides.
+    fn apply_texture_coordinates(vertices: &mut [Vertex]) {
+        // Same order the faces are built in: bottom, left, right, back, front, top.
+        // The texture axes for each face as (u, v) position indices.
+        let axes = [(0, 2), (2, 1), (2, 1), (0, 1), (0, 1), (0, 2)];
+        for (face, (u, v)) in axes.iter().enumerate() {
+            for vertex in vertices[face * 12..(face + 1) * 12].iter_mut() {
+                vertex.tex_coords = [vertex.position[*u], vertex.position[*v]];
+            }
+        }
+    }
+
+    /// Let the waves sink the open top of a fluid, pulling the sides down with it.
+    fn apply_fluid_surface(&self, vertices: &mut [Vertex]) {
+        if self.fluid == 0 || self.top_occluded {
+            return;
+        }
+        let height = (self.scale - self.floor).max(f32::EPSILON);
+        for vertex in vertices.iter_mut() {
+            vertex.sink = ((vertex.position[1] - self.floor) / height).clamp(0.0, 1.0);
+        }
+    }
+
+    /// Darken the corners of each face based on the filled neighbours.
+    fn apply_ambient_occlusion(&self, vertices: &mut [Vertex]) {
+        // Same order the faces are built in: bottom, left, right, back, front, top.
+        let normals = [
+            [0, -1, 0],
+            [-1, 0, 0],
+            [1, 0, 0],
+            [0, 0, 1],
+            [0, 0, -1],
+            [0, 1, 0],
+        ];
+        for (face, normal) in normals.iter().enumerate() {
+            let face_vertices = &mut vertices[face * 12..(face + 1) * 12];
+            // Every third vertex is the center of the face.
+            let center = face_vertices[2].position;
+            let axis = normal.iter().position(|n| *n != 0).unwrap_or(0);
+            let tangents = [(axis + 1) % 3, (axis + 2) % 3];
+
+            let mut total = 0.0;
+            for (index, vertex) in face_vertices.iter_mut().enumerate() {
+                if index % 3 != 2 {
+                    let mut first = [0; 3];
+                    let mut second = [0; 3];
+                    first[tangents[0]] = if vertex.position[tangents[0]] > center[tangents[0]] {
+                        1
+                    } else {
+                        -1
+                    };
+                    second[tangents[1]] = if vertex.position[tangents[1]] > center[tangents[1]] {
+                        1
+                    } else {
+                        -1
+                    };
+                    vertex.occlusion = self.corner_occlusion(*normal, first, second);
+                    total += vertex.occlusion;
+                }
+            }
+            // The center blends the 8 corner vertices (each corner is shared by 2 triangles).
+            for (index, vertex) in face_vertices.iter_mut().enumerate() {
+                if index % 3 == 2 {
+                    vertex.occlusion = total / 8.0;
+                }
+            }
+        }
+    }
 }
 
 impl Drawable for Cube {
@@ -83,20 +200,28 @@ impl Drawable for Cube {
         self.fluid
     }
 
-    fn noise(&self) -> i32 {
-        self.noise
+    fn fluid_settings(&self) -> FluidSettings {
+        self.fluid_settings
     }
 
-    fn noise_x(&self) -> i32 {
-        self.noise_x
+    fn pattern(&self) -> PatternSettings {
+        self.pattern
     }
 
-    fn noise_y(&self) -> i32 {
-        self.noise_y
+    fn emissive(&self) -> f32 {
+        self.emissive
     }
 
-    fn noise_z(&self) -> i32 {
-        self.noise_z
+    fn roughness(&self) -> f32 {
+        self.roughness
+    }
+
+    fn metalness(&self) -> f32 {
+        self.metalness
+    }
+
+    fn texture(&self) -> u32 {
+        self.texture
     }
 
     /// Move a cube.
@@ -367,6 +492,9 @@ impl Drawable for Cube {
         let mut vertices = [Vertex {
             position: [0.0, 0.0, 0.0],
             normal: [0.0, 0.0, 0.0],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         }; 72];
         // Bottom
         let b11 = Vec3::new(ldf[0] - dc[0], ldf[1] - dc[1], ldf[2] - dc[2]);
@@ -375,14 +503,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ldf[0], ldf[1], ldf[2]],
             normal: [bc1[0], bc1[1], bc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rdf[0], rdf[1], rdf[2]],
             normal: [bc1[0], bc1[1], bc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [dc[0], dc[1], dc[2]],
             normal: [bc1[0], bc1[1], bc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         let b21 = Vec3::new(rdf[0] - dc[0], rdf[1] - dc[1], rdf[2] - dc[2]);
@@ -391,14 +528,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rdf[0], rdf[1], rdf[2]],
             normal: [bc2[0], bc2[1], bc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rdb[0], rdb[1], rdb[2]],
             normal: [bc2[0], bc2[1], bc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [dc[0], dc[1], dc[2]],
             normal: [bc2[0], bc2[1], bc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         let b31 = Vec3::new(rdb[0] - dc[0], rdb[1] - dc[1], rdb[2] - dc[2]);
@@ -407,14 +553,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rdb[0], rdb[1], rdb[2]],
             normal: [bc3[0], bc3[1], bc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ldb[0], ldb[1], ldb[2]],
             normal: [bc3[0], bc3[1], bc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [dc[0], dc[1], dc[2]],
             normal: [bc3[0], bc3[1], bc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         let b41 = Vec3::new(ldb[0] - dc[0], ldb[1] - dc[1], ldb[2] - dc[2]);
@@ -423,14 +578,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ldb[0], ldb[1], ldb[2]],
             normal: [bc4[0], bc4[1], bc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ldf[0], ldf[1], ldf[2]],
             normal: [bc4[0], bc4[1], bc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [dc[0], dc[1], dc[2]],
             normal: [bc4[0], bc4[1], bc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         // Left
@@ -440,14 +604,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ldf[0], ldf[1], ldf[2]],
             normal: [lc1[0], lc1[1], lc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ldb[0], ldb[1], ldb[2]],
             normal: [lc1[0], lc1[1], lc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [lc[0], lc[1], lc[2]],
             normal: [lc1[0], lc1[1], lc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let l21 = Vec3::new(luf[0] - lc[0], luf[1] - lc[1], luf[2] - lc[2]);
         let l22 = Vec3::new(ldf[0] - lc[0], ldf[1] - lc[1], ldf[2] - lc[2]);
@@ -456,14 +629,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [luf[0], luf[1], luf[2]],
             normal: [lc2[0], lc2[1], lc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ldf[0], ldf[1], ldf[2]],
             normal: [lc2[0], lc2[1], lc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [lc[0], lc[1], lc[2]],
             normal: [lc2[0], lc2[1], lc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let l31 = Vec3::new(lub[0] - lc[0], lub[1] - lc[1], lub[2] - lc[2]);
         let l32 = Vec3::new(luf[0] - lc[0], luf[1] - lc[1], luf[2] - lc[2]);
@@ -471,14 +653,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [lub[0], lub[1], lub[2]],
             normal: [lc3[0], lc3[1], lc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [luf[0], luf[1], luf[2]],
             normal: [lc3[0], lc3[1], lc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [lc[0], lc[1], lc[2]],
             normal: [lc3[0], lc3[1], lc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let l41 = Vec3::new(ldb[0] - lc[0], ldb[1] - lc[1], ldb[2] - lc[2]);
         let l42 = Vec3::new(lub[0] - lc[0], lub[1] - lc[1], lub[2] - lc[2]);
@@ -486,14 +677,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ldb[0], ldb[1], ldb[2]],
             normal: [lc4[0], lc4[1], lc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [lub[0], lub[1], lub[2]],
             normal: [lc4[0], lc4[1], lc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [lc[0], lc[1], lc[2]],
             normal: [lc4[0], lc4[1], lc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         // Right
@@ -503,14 +703,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rdf[0], rdf[1], rdf[2]],
             normal: [rc1[0], rc1[1], rc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ruf[0], ruf[1], ruf[2]],
             normal: [rc1[0], rc1[1], rc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rc[0], rc[1], rc[2]],
             normal: [rc1[0], rc1[1], rc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let r21 = Vec3::new(ruf[0] - rc[0], ruf[1] - rc[1], ruf[2] - rc[2]);
         let r22 = Vec3::new(rub[0] - rc[0], rub[1] - rc[1], rub[2] - rc[2]);
@@ -518,14 +727,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ruf[0], ruf[1], ruf[2]],
             normal: [rc2[0], rc2[1], rc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rub[0], rub[1], rub[2]],
             normal: [rc2[0], rc2[1], rc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rc[0], rc[1], rc[2]],
             normal: [rc2[0], rc2[1], rc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let r31 = Vec3::new(rub[0] - rc[0], rub[1] - rc[1], rub[2] - rc[2]);
         let r32 = Vec3::new(rdb[0] - rc[0], rdb[1] - rc[1], rdb[2] - rc[2]);
@@ -533,14 +751,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rub[0], rub[1], rub[2]],
             normal: [rc3[0], rc3[1], rc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rdb[0], rdb[1], rdb[2]],
             normal: [rc3[0], rc3[1], rc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rc[0], rc[1], rc[2]],
             normal: [rc3[0], rc3[1], rc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let r41 = Vec3::new(rdb[0] - rc[0], rdb[1] - rc[1], rdb[2] - rc[2]);
         let r42 = Vec3::new(rdf[0] - rc[0], rdf[1] - rc[1], rdf[2] - rc[2]);
@@ -548,14 +775,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rdb[0], rdb[1], rdb[2]],
             normal: [rc4[0], rc4[1], rc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rdf[0], rdf[1], rdf[2]],
             normal: [rc4[0], rc4[1], rc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rc[0], rc[1], rc[2]],
             normal: [rc4[0], rc4[1], rc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         // Back
@@ -565,14 +801,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ldb[0], ldb[1], ldb[2]],
             normal: [bc1[0], bc1[1], bc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rdb[0], rdb[1], rdb[2]],
             normal: [bc1[0], bc1[1], bc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [bc[0], bc[1], bc[2]],
             normal: [bc1[0], bc1[1], bc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let b21 = Vec3::new(rdb[0] - bc[0], rdb[1] - bc[1], rdb[2] - bc[2]);
         let b22 = Vec3::new(rub[0] - bc[0], rub[1] - bc[1], rub[2] - bc[2]);
@@ -580,14 +825,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rdb[0], rdb[1], rdb[2]],
             normal: [bc2[0], bc2[1], bc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rub[0], rub[1], rub[2]],
             normal: [bc2[0], bc2[1], bc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [bc[0], bc[1], bc[2]],
             normal: [bc2[0], bc2[1], bc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let b31 = Vec3::new(rub[0] - bc[0], rub[1] - bc[1], rub[2] - bc[2]);
         let b32 = Vec3::new(lub[0] - bc[0], lub[1] - bc[1], lub[2] - bc[2]);
@@ -595,14 +849,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rub[0], rub[1], rub[2]],
             normal: [bc3[0], bc3[1], bc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [lub[0], lub[1], lub[2]],
             normal: [bc3[0], bc3[1], bc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [bc[0], bc[1], bc[2]],
             normal: [bc3[0], bc3[1], bc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let b41 = Vec3::new(lub[0] - bc[0], lub[1] - bc[1], lub[2] - bc[2]);
         let b42 = Vec3::new(ldb[0] - bc[0], ldb[1] - bc[1], ldb[2] - bc[2]);
@@ -610,14 +873,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [lub[0], lub[1], lub[2]],
             normal: [bc4[0], bc4[1], bc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ldb[0], ldb[1], ldb[2]],
             normal: [bc4[0], bc4[1], bc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [bc[0], bc[1], bc[2]],
             normal: [bc4[0], bc4[1], bc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         // Front
@@ -627,14 +899,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ldf[0], ldf[1], ldf[2]],
             normal: [fc1[0], fc1[1], fc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [luf[0], luf[1], luf[2]],
             normal: [fc1[0], fc1[1], fc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [fc[0], fc[1], fc[2]],
             normal: [fc1[0], fc1[1], fc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let f21 = Vec3::new(luf[0] - fc[0], luf[1] - fc[1], luf[2] - fc[2]);
         let f22 = Vec3::new(ruf[0] - fc[0], ruf[1] - fc[1], ruf[2] - fc[2]);
@@ -642,14 +923,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [luf[0], luf[1], luf[2]],
             normal: [fc2[0], fc2[1], fc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ruf[0], ruf[1], ruf[2]],
             normal: [fc2[0], fc2[1], fc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [fc[0], fc[1], fc[2]],
             normal: [fc2[0], fc2[1], fc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let f31 = Vec3::new(ruf[0] - fc[0], ruf[1] - fc[1], ruf[2] - fc[2]);
         let f32 = Vec3::new(rdf[0] - fc[0], rdf[1] - fc[1], rdf[2] - fc[2]);
@@ -657,14 +947,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ruf[0], ruf[1], ruf[2]],
             normal: [fc3[0], fc3[1], fc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rdf[0], rdf[1], rdf[2]],
             normal: [fc3[0], fc3[1], fc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [fc[0], fc[1], fc[2]],
             normal: [fc3[0], fc3[1], fc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         let f41 = Vec3::new(rdf[0] - fc[0], rdf[1] - fc[1], rdf[2] - fc[2]);
@@ -673,14 +972,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rdf[0], rdf[1], rdf[2]],
             normal: [fc4[0], fc4[1], fc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ldf[0], ldf[1], ldf[2]],
             normal: [fc4[0], fc4[1], fc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [fc[0], fc[1], fc[2]],
             normal: [fc4[0], fc4[1], fc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         // Top
@@ -690,14 +998,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [luf[0], luf[1], luf[2]],
             normal: [tc1[0], tc1[1], tc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [lub[0], lub[1], lub[2]],
             normal: [tc1[0], tc1[1], tc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [uc[0], uc[1], uc[2]],
             normal: [tc1[0], tc1[1], tc1[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         let t21 = Vec3::new(lub[0] - uc[0], lub[1] - uc[1], lub[2] - uc[2]);
@@ -706,14 +1023,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [lub[0], lub[1], lub[2]],
             normal: [tc2[0], tc2[1], tc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [rub[0], rub[1], rub[2]],
             normal: [tc2[0], tc2[1], tc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [uc[0], uc[1], uc[2]],
             normal: [tc2[0], tc2[1], tc2[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
         let t31 = Vec3::new(rub[0] - uc[0], rub[1] - uc[1], rub[2] - uc[2]);
@@ -722,14 +1048,23 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [rub[0], rub[1], rub[2]],
             normal: [tc3[0], tc3[1], tc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [ruf[0], ruf[1], ruf[2]],
             normal: [tc3[0], tc3[1], tc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [uc[0], uc[1], uc[2]],
             normal: [tc3[0], tc3[1], tc3[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         let t41 = Vec3::new(ruf[0] - uc[0], ruf[1] - uc[1], ruf[2] - uc[2]);
         let t42 = Vec3::new(luf[0] - uc[0], luf[1] - uc[1], luf[2] - uc[2]);
@@ -737,16 +1072,29 @@ impl Drawable for Cube {
         vertices[increment()] = Vertex {
             position: [ruf[0], ruf[1], ruf[2]],
             normal: [tc4[0], tc4[1], tc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [luf[0], luf[1], luf[2]],
             normal: [tc4[0], tc4[1], tc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
         vertices[increment()] = Vertex {
             position: [uc[0], uc[1], uc[2]],
             normal: [tc4[0], tc4[1], tc4[2]],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
         };
 
+        self.apply_ambient_occlusion(&mut vertices);
+        Self::apply_texture_coordinates(&mut vertices);
+        self.apply_fluid_surface(&mut vertices);
+
         let bottom = &vertices[0..12];
         let left = &vertices[12..24];
         let right = &vertices[24..36];
@@ -1,3 +1,5 @@
+use crate::fluid::FluidSettings;
+use crate::pattern::PatternSettings;
 use crate::vertex::Vertex;
 use glium::index::PrimitiveType;
 
@@ -15,8 +17,10 @@ pub trait Drawable {
     fn color(&self) -> &[f32; 4];
     fn depth(&self, camera: [f32; 3]) -> f32;
     fn fluid(&self) -> i32;
-    fn noise(&self) -> i32;
-    fn noise_x(&self) -> i32;
-    fn noise_y(&self) -> i32;
-    fn noise_z(&self) -> i32;
+    fn fluid_settings(&self) -> FluidSettings;
+    fn pattern(&self) -> PatternSettings;
+    fn emissive(&self) -> f32;
+    fn roughness(&self) -> f32;
+    fn metalness(&self) -> f32;
+    fn texture(&self) -> u32;
 }
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// The smallest wave scale, so the ripples never stop completely.
+pub const MIN_FLUID_WAVE_SCALE: f32 = 0.125;
+
+/// How a fluid material moves. Water, lava and slime all use the same ripples
+/// with different settings.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct FluidSettings {
+    /// How fast the ripples travel.
+    pub speed: f32,
+    /// How tightly packed the ripples are. Bigger numbers give shorter waves.
+    pub wave_scale: f32,
+    /// How far the hue swings between the troughs and crests of the waves, from 0 to 1.
+    pub color_shift: f32,
+    /// How far the top faces sink in the troughs of the waves, in voxels.
+    pub displacement: f32,
+}
+
+impl Default for FluidSettings {
+    fn default() -> Self {
+        FluidSettings::new()
+    }
+}
+
+impl FluidSettings {
+    /// The settings the fluid animation always used before they could be changed.
+    pub const fn new() -> FluidSettings {
+        FluidSettings {
+            speed: 10.0,
+            wave_scale: 4.0,
+            color_shift: 0.0,
+            displacement: 0.0,
+        }
+    }
+}
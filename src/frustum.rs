@@ -0,0 +1,30 @@
+use nalgebra::{Matrix4, Vector4};
+
+/// The six planes around everything the camera can see.
+pub struct Frustum {
+    /// Each plane is (a, b, c, d) where a point is inside when ax + by + cz + d >= 0.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Pull the planes out of a combined projection and view matrix.
+    pub fn from_matrix(matrix: &Matrix4<f32>) -> Frustum {
+        let row = |index: usize| matrix.row(index).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Could any part of the box be on screen? Boxes near the corners of the frustum
+    /// can pass without being visible, but a visible box is never rejected.
+    pub fn intersects_box(&self, min: [f32; 3], max: [f32; 3]) -> bool {
+        self.planes.iter().all(|plane| {
+            // Only the corner furthest along the plane normal needs testing.
+            let x = if plane.x >= 0.0 { max[0] } else { min[0] };
+            let y = if plane.y >= 0.0 { max[1] } else { min[1] };
+            let z = if plane.z >= 0.0 { max[2] } else { min[2] };
+            plane.x * x + plane.y * y + plane.z * z + plane.w >= 0.0
+        })
+    }
+}
@@ -1,28 +1,104 @@
-use crate::camera::Camera;
+use crate::atmosphere::{Atmosphere, Background};
+use crate::camera::{Camera, Projection};
 
 use crate::drawable::Drawable;
+use crate::lighting::{Lighting, MAX_POINT_LIGHTS, MAX_SHADOW_SOFTNESS, PointLight};
 use crate::material::Material;
+use crate::texture_atlas::{ATLAS_COLUMNS, TILE_SIZE, TextureSet, build_atlas};
 use crate::vertex::Vertex;
 use glium::Frame;
 use glium::Program;
 use glium::Surface;
 use glium::backend::glutin::Display;
+use glium::implement_uniform_block;
 use glium::index::PrimitiveType;
 use glium::texture::MipmapsOption;
+use glium::texture::RawImage2d;
+use glium::texture::SrgbTexture2d;
 use glium::texture::Texture2d;
 use glium::texture::UncompressedFloatFormat;
 use glium::uniform;
+use glium::uniforms::UniformBuffer;
 use glutin::surface::WindowSurface;
 use nalgebra::*;
 
+/// The waves on a fluid, shared by the camera vertex shader, which sinks the surface
+/// into the troughs, and the fragment shader, which shades by them. The sum of six
+/// ripples, so from -6 to 6.
+const ANIMATE_FLUID: &str = "
+                float animateFluid(vec3 position) {
+                    // We calculate the distance between the point and 6 ripple source locations
+                    // and combine 6 sinewaves from the 6 distances.
+                    vec3 ripple1 = vec3(100.0, 40.0, 10.0);
+                    vec3 ripple2 = vec3(50.0, -40.0, 30.0);
+                    vec3 ripple3 = vec3(-40.0, 40.0, -80.0);
+                    vec3 ripple4 = vec3(34.0, 23.0, 12.0);
+                    vec3 ripple5 = vec3(8.0, -13.0, 73.0);
+                    vec3 ripple6 = vec3(-25.0, 67.0, -34.0);
+                    float period = u_fluid_wave_scale;
+                    float distance1 = length(position - ripple1) * period;
+                    float distance2 = length(position - ripple2) * period;
+                    float distance3 = length(position - ripple3) * period;
+                    float distance4 = length(position - ripple4) * period;
+                    float distance5 = length(position - ripple5) * period;
+                    float distance6 = length(position - ripple6) * period;
+                    float scale = u_time * u_fluid_speed;
+                    return (
+                        sin(distance1 + scale) +
+                        sin(distance2 + scale) +
+                        sin(distance3 + scale) +
+                        sin(distance4 + scale) +
+                        sin(distance5 + scale) +
+                        sin(distance6 + scale)
+                        );
+                }
+";
+
+/// The point lights laid out to match the uniform block in the camera shader.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PointLightBlock {
+    /// Position of each light, w is unused.
+    pub positions: [[f32; 4]; MAX_POINT_LIGHTS],
+    /// Color of each light, w is the strength.
+    pub colors: [[f32; 4]; MAX_POINT_LIGHTS],
+    /// How many of the lights are in use.
+    pub count: i32,
+}
+
+implement_uniform_block!(PointLightBlock, positions, colors, count);
+
 /// All the things we need to know to render to the screen.
 pub struct Graphics {
     pub canvas_width: u32,
     pub canvas_height: u32,
     pub camera_program: Option<Program>,
     pub light_program: Option<Program>,
+    pub sky_program: Option<Program>,
     pub shadow_depth_texture: Option<Texture2d>,
     pub shadow_texture_size: u32,
+    /// The light settings used by the camera shaders.
+    pub lighting: Lighting,
+    /// The corners of the box holding every voxel. The shadow map is fitted to this box.
+    pub shadow_bounds: Option<([f32; 3], [f32; 3])>,
+    /// The glowing voxels that light their surroundings.
+    pub point_lights: Option<UniformBuffer<PointLightBlock>>,
+    /// The background and fog settings.
+    pub atmosphere: Atmosphere,
+    /// A triangle covering the whole screen, used to draw the background.
+    pub sky_vertices: Option<glium::VertexBuffer<Vertex>>,
+    /// The loaded cube map image and the path it came from.
+    pub sky_texture: Option<(String, SrgbTexture2d)>,
+    /// Every voxel texture packed into one image.
+    pub atlas_texture: Option<SrgbTexture2d>,
+    /// How many rows of tiles are in the atlas.
+    pub atlas_rows: u32,
+    /// The texture sets packed into the atlas.
+    pub atlas_sets: Vec<TextureSet>,
+    /// Whether the camera draws in perspective or orthographic.
+    pub projection: Projection,
+    /// The part of the window the camera draws into.
+    pub viewport: glium::Rect,
 }
 
 impl Graphics {
@@ -33,8 +109,25 @@ impl Graphics {
             canvas_height,
             camera_program: None,
             light_program: None,
+            sky_program: None,
             shadow_depth_texture: None,
             shadow_texture_size: 4096,
+            lighting: Lighting::default(),
+            shadow_bounds: None,
+            point_lights: None,
+            atmosphere: Atmosphere::new(),
+            sky_vertices: None,
+            sky_texture: None,
+            atlas_texture: None,
+            atlas_rows: 1,
+            atlas_sets: Vec::new(),
+            projection: Projection::Perspective,
+            viewport: glium::Rect {
+                left: 0,
+                bottom: 0,
+                width: canvas_width,
+                height: canvas_height,
+            },
         }
     }
 
@@ -53,26 +146,146 @@ impl Graphics {
     }
 
     /// Get the view from the light for calculating shadows.
-    pub fn build_light_projection(&self) -> Matrix4<f32> {
-        Orthographic3::new(-64.0, 64.0, -64.0, 64.0, 1.0, 240.0).into_inner()
+    /// The box is fitted tightly around the model so the whole world can cast shadows
+    /// without wasting shadow map resolution on empty space.
+    pub fn build_light_projection(&self, light_view: &Isometry3<f32>) -> Matrix4<f32> {
+        let Some((min, max)) = self.shadow_bounds else {
+            return Orthographic3::new(-64.0, 64.0, -64.0, 64.0, 1.0, 240.0).into_inner();
+        };
+
+        let mut low = Vector3::repeat(f32::MAX);
+        let mut high = Vector3::repeat(f32::MIN);
+        for corner in 0..8 {
+            let point = Point3::new(
+                if corner & 1 == 0 { min[0] } else { max[0] },
+                if corner & 2 == 0 { min[1] } else { max[1] },
+                if corner & 4 == 0 { min[2] } else { max[2] },
+            );
+            let light_point = light_view.transform_point(&point);
+            low = low.inf(&light_point.coords);
+            high = high.sup(&light_point.coords);
+        }
+
+        // Leave a little room so voxels on the edge of the box are not clipped.
+        let margin = 1.0;
+        // The light looks down the negative z axis, so the nearest point has the largest z.
+        Orthographic3::new(
+            low.x - margin,
+            high.x + margin,
+            low.y - margin,
+            high.y + margin,
+            -high.z - margin,
+            -low.z + margin,
+        )
+        .into_inner()
     }
 
     /// Get the view from the camera.
-    pub fn build_camera_projection(&self) -> Matrix4<f32> {
-        Perspective3::new(
-            self.canvas_width as f32 / self.canvas_height as f32,
-            std::f32::consts::PI / 4.0, // 45 degrees
-            1.0,
-            200.0,
+    pub fn build_camera_projection(&self, camera: &Camera) -> Matrix4<f32> {
+        camera.projection_matrix(
+            self.projection,
+            self.viewport.width as f32 / self.viewport.height.max(1) as f32,
         )
-        .into_inner()
+    }
+
+    /// Draw the camera into part of the window. The area is left, bottom, width and height
+    /// as fractions of the window.
+    pub fn set_viewport(&mut self, area: [f32; 4]) {
+        let width = self.canvas_width as f32;
+        let height = self.canvas_height as f32;
+        self.viewport = glium::Rect {
+            left: (area[0] * width) as u32,
+            bottom: (area[1] * height) as u32,
+            width: (area[2] * width) as u32,
+            height: (area[3] * height) as u32,
+        };
     }
 
     /// Compile the various shaders.
     pub fn setup_shaders(&mut self, display: &Display<WindowSurface>) {
         self.light_program = Some(self.setup_light_shaders(display));
         self.camera_program = Some(self.setup_camera_shaders(display));
+        self.sky_program = Some(self.setup_sky_shaders(display));
+        let corner = |x: f32, y: f32| Vertex {
+            position: [x, y, 0.0],
+            normal: [0.0, 0.0, 1.0],
+            occlusion: 1.0,
+            tex_coords: [0.0, 0.0],
+            sink: 0.0,
+        };
+        self.sky_vertices = Some(
+            glium::VertexBuffer::new(
+                display,
+                &[corner(-1.0, -1.0), corner(3.0, -1.0), corner(-1.0, 3.0)],
+            )
+            .unwrap(),
+        );
         self.create_shadow_depth_texture(display);
+        self.point_lights = Some(UniformBuffer::empty(display).unwrap());
+        self.set_texture_atlas(display, &[]);
+        self.set_point_lights(&[]);
+    }
+
+    /// Rebuild the atlas of voxel textures if the texture sets changed.
+    pub fn set_texture_atlas(&mut self, display: &Display<WindowSurface>, sets: &[TextureSet]) {
+        if self.atlas_texture.is_some() && self.atlas_sets == sets {
+            return;
+        }
+        self.atlas_sets = sets.to_vec();
+        let atlas = build_atlas(sets);
+        let image_dimensions = atlas.dimensions();
+        self.atlas_rows = image_dimensions.1 / TILE_SIZE;
+        let image = RawImage2d::from_raw_rgba_reversed(&atlas.into_raw(), image_dimensions);
+        self.atlas_texture = Some(SrgbTexture2d::new(display, image).unwrap());
+    }
+
+    /// Update the background and fog settings, loading the cube map image if it changed.
+    pub fn set_atmosphere(&mut self, display: &Display<WindowSurface>, atmosphere: &Atmosphere) {
+        if self.atmosphere == *atmosphere {
+            return;
+        }
+        self.atmosphere = atmosphere.clone();
+
+        let loaded_path = self.sky_texture.as_ref().map(|(path, _)| path);
+        if loaded_path == self.atmosphere.cube_map.as_ref() {
+            return;
+        }
+        self.sky_texture = None;
+        if let Some(path) = &self.atmosphere.cube_map {
+            match image::open(path) {
+                Ok(image) => {
+                    let image = image.to_rgba8();
+                    let image_dimensions = image.dimensions();
+                    let image =
+                        RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
+                    let texture = SrgbTexture2d::new(display, image).unwrap();
+                    self.sky_texture = Some((path.clone(), texture));
+                }
+                Err(error) => log::error!("Could not load the cube map {path}: {error}"),
+            }
+        }
+    }
+
+    /// Update the point lights used by the camera shaders. Only the first MAX_POINT_LIGHTS are used.
+    pub fn set_point_lights(&mut self, lights: &[PointLight]) {
+        let mut block = PointLightBlock {
+            positions: [[0.0; 4]; MAX_POINT_LIGHTS],
+            colors: [[0.0; 4]; MAX_POINT_LIGHTS],
+            count: lights.len().min(MAX_POINT_LIGHTS) as i32,
+        };
+        for (index, light) in lights.iter().take(MAX_POINT_LIGHTS).enumerate() {
+            block.positions[index] = [light.position[0], light.position[1], light.position[2], 1.0];
+            block.colors[index] = [
+                light.color[0],
+                light.color[1],
+                light.color[2],
+                light.strength,
+            ];
+        }
+        self.point_lights
+            .as_ref()
+            .expect("Point lights should be initialized")
+            .write(&block);
     }
 
     /// Compile the light shaders.
@@ -99,7 +312,7 @@ impl Graphics {
 
                 void main()
                 {
-                    fragColor = vec4(vec3(LinearizeDepth(gl_FragCoord.z + 0.0005)), 1.0);
+                    fragColor = vec4(vec3(LinearizeDepth(gl_FragCoord.z)), 1.0);
                 }
                 ";
 
@@ -116,49 +329,174 @@ impl Graphics {
         program.unwrap()
     }
 
+    /// Compile the background shaders.
+    pub fn setup_sky_shaders(&mut self, display: &Display<WindowSurface>) -> Program {
+        let vertex_shader_source = "#version 410
+                in vec3 position;
+                uniform mat4 u_inverse_view_projection;
+                out vec3 v_direction;
+
+                void main(void) {
+                    // Draw at the far plane and work out which way this pixel looks.
+                    gl_Position = vec4(position.xy, 1.0, 1.0);
+                    vec4 near = u_inverse_view_projection * vec4(position.xy, -1.0, 1.0);
+                    vec4 far = u_inverse_view_projection * vec4(position.xy, 1.0, 1.0);
+                    v_direction = far.xyz / far.w - near.xyz / near.w;
+                }
+            ";
+        let fragment_shader_source = "#version 410
+                precision mediump float;
+                uniform int u_background;
+                uniform vec3 u_sky_color;
+                uniform vec3 u_horizon_color;
+                uniform bool u_has_sky_map;
+                uniform sampler2D u_sky_map;
+                in vec3 v_direction;
+                out vec4 fragColor;
+
+                // Find the position in a strip of six cube faces ordered +x, -x, +y, -y, +z, -z.
+                vec2 cubeMapStrip(vec3 direction) {
+                    vec3 size = abs(direction);
+                    float face;
+                    vec2 uv;
+                    if (size.x >= size.y && size.x >= size.z) {
+                        face = direction.x > 0.0 ? 0.0 : 1.0;
+                        uv = vec2(direction.x > 0.0 ? -direction.z : direction.z, -direction.y) / size.x;
+                    } else if (size.y >= size.z) {
+                        face = direction.y > 0.0 ? 2.0 : 3.0;
+                        uv = vec2(direction.x, direction.y > 0.0 ? direction.z : -direction.z) / size.y;
+                    } else {
+                        face = direction.z > 0.0 ? 4.0 : 5.0;
+                        uv = vec2(direction.z > 0.0 ? direction.x : -direction.x, -direction.y) / size.z;
+                    }
+                    uv = uv * 0.5 + 0.5;
+                    return vec2((face + uv.x) / 6.0, 1.0 - uv.y);
+                }
+
+                void main(void) {
+                    vec3 direction = normalize(v_direction);
+                    vec3 color = u_horizon_color;
+                    if (u_background == 1) {
+                        color = mix(u_horizon_color, u_sky_color, clamp(direction.y, 0.0, 1.0));
+                    } else if (u_background == 2 && u_has_sky_map) {
+                        color = texture(u_sky_map, cubeMapStrip(direction)).rgb;
+                    }
+                    fragColor = vec4(color, 1.0);
+                }
+            ";
+
+        let program = glium::Program::from_source(
+            display,
+            vertex_shader_source,
+            fragment_shader_source,
+            None,
+        );
+        match program {
+            Ok(program) => program,
+            Err(error) => panic!("Failed to create program: {}", error),
+        }
+    }
+
     /// Compile the camera shaders.
     pub fn setup_camera_shaders(&mut self, display: &Display<WindowSurface>) -> Program {
-        let vertex_shader_source = "#version 410
+        let vertex_shader_source = [
+            "#version 410
                 in vec3 position;
                 in vec3 normal;
+                in float occlusion;
+                in vec2 tex_coords;
+                in float sink;
                 uniform mat4 uPMatrix;
                 uniform mat4 uMVMatrix;
                 uniform mat4 uMMatrix;
                 uniform mat4 u_light_PMatrix;
                 uniform mat4 u_light_MVMatrix;
+                uniform bool u_fluid;
+                uniform float u_time;
+                uniform float u_fluid_speed;
+                uniform float u_fluid_wave_scale;
+                uniform float u_fluid_displacement;
                 out vec4 positionFromLightPov;
                 out vec4 worldPosition;
+                out vec3 v_world_position;
                 out vec3 v_normal;
-
+                out float v_occlusion;
+                out vec2 v_tex_coords;
+                ",
+            ANIMATE_FLUID,
+            "
                 void main(void) {
                     // Multiply the position by the matrix.
                     vec4 a_position = vec4(position, 1.0);
+                    // The open tops of a fluid sink into the troughs of the waves, pulling
+                    // the tops of the sides down with them.
+                    if (u_fluid && sink > 0.0) {
+                        float wave = animateFluid((uMMatrix * a_position).xyz) / 6.0;
+                        a_position.y -= (wave * 0.5 + 0.5) * u_fluid_displacement * sink;
+                    }
                     gl_Position = uPMatrix * uMVMatrix * a_position;
 
                     //positionFromLightPov = uPMatrix * uMMatrix * a_position;
                     positionFromLightPov = u_light_PMatrix * u_light_MVMatrix * a_position;
                     // This is incorrect on purpose because a voxel grid aligns with the axis.
                     worldPosition = uPMatrix * uMMatrix * a_position;
+                    v_world_position = (uMMatrix * a_position).xyz;
                     v_normal = normal;
+                    v_occlusion = occlusion;
+                    v_tex_coords = tex_coords;
                 }
-                ";
+                ",
+        ]
+        .concat();
 
-        let fragment_shader_source = "#version 410
+        let fragment_shader_source = [
+            "#version 410
                 precision mediump float;
                 uniform vec4 u_color;
+                uniform int u_texture;
+                uniform sampler2D u_atlas;
+                uniform int u_atlas_columns;
+                uniform int u_atlas_rows;
                 uniform bool u_fluid;
-                uniform bool u_noise;
-                uniform bool u_noise_x;
-                uniform bool u_noise_y;
-                uniform bool u_noise_z;
+                uniform float u_fluid_speed;
+                uniform float u_fluid_wave_scale;
+                uniform float u_fluid_color_shift;
+                uniform int u_pattern;
+                uniform float u_pattern_scale;
+                uniform float u_pattern_contrast;
+                uniform vec3 u_secondary_color;
+                uniform bool u_pattern_shade;
                 uniform float u_time;
+                uniform bool u_ambient_occlusion;
+                uniform vec3 u_light_direction;
+                uniform vec3 u_light_color;
+                uniform float u_light_intensity;
+                uniform float u_ambient_light;
+                uniform bool u_shadows;
+                uniform float u_shadow_bias;
+                uniform int u_shadow_softness;
+                uniform float u_emissive;
+                uniform float u_roughness;
+                uniform float u_metalness;
+                uniform vec3 u_camera_position;
+                uniform bool u_fog;
+                uniform float u_fog_distance;
+                uniform vec3 u_fog_color;
+                layout(std140) uniform PointLights {
+                    vec4 positions[MAX_POINT_LIGHTS];
+                    vec4 colors[MAX_POINT_LIGHTS];
+                    int count;
+                } u_point_lights;
                 uniform int u_shadow_texture_size;
                 uniform sampler2D shadowMap;
                 out vec4 fragColor;
                 in vec4 positionFromLightPov;
                 in vec4 positionFromLightMV;
                 in vec4 worldPosition;
+                in vec3 v_world_position;
                 in vec3 v_normal;
+                in float v_occlusion;
+                in vec2 v_tex_coords;
 
                 float rand(vec2 co){
                     return fract(sin(dot(co, vec2(12.9898, 78.233))) * 43758.5453);
@@ -173,76 +511,182 @@ impl Graphics {
                     return (sin(scaled) * 0.5 + sin(scaled * 2.1) * 0.3 + sin(scaled * 0.45) * 0.2) * 0.5 + 0.5;
                 }
 
-                float animateFluid() {
-                    // We calculate the distance between the point and 3 ripple source locations
-                    // and combine 3 sinewaves from the 3 distances.
-                    vec3 ripple1 = vec3(100.0, 40.0, 10.0);
-                    vec3 ripple2 = vec3(50.0, -40.0, 30.0);
-                    vec3 ripple3 = vec3(-40.0, 40.0, -80.0);
-                    vec3 ripple4 = vec3(34.0, 23.0, 12.0);
-                    vec3 ripple5 = vec3(8.0, -13.0, 73.0);
-                    vec3 ripple6 = vec3(-25.0, 67.0, -34.0);
-                    float period = 4.0;
-                    float distance1 = length(worldPosition.xyz - ripple1) * period;
-                    float distance2 = length(worldPosition.xyz - ripple2) * period;
-                    float distance3 = length(worldPosition.xyz - ripple3) * period;
-                    float distance4 = length(worldPosition.xyz - ripple4) * period;
-                    float distance5 = length(worldPosition.xyz - ripple5) * period;
-                    float distance6 = length(worldPosition.xyz - ripple6) * period;
-                    float speed = 10.0;
-                    float scale = u_time * speed;
-                    return (
-                        sin(distance1 + scale) +
-                        sin(distance2 + scale) +
-                        sin(distance3 + scale) +
-                        sin(distance4 + scale) +
-                        sin(distance5 + scale) +
-                        sin(distance6 + scale)
-                        );
+                float hash(vec3 p) {
+                    return fract(sin(dot(p, vec3(127.1, 311.7, 74.7))) * 43758.5453);
                 }
 
-                void main(void) {
-                    float ambientLight = 0.5;
-                    vec3 positionFromLightPovInTexture = (positionFromLightPov.xyz/positionFromLightPov.w) * 0.5 + 0.5;
+                // A random unit vector for each lattice point.
+                vec3 latticeGradient(vec3 cell) {
+                    float angle = hash(cell) * 6.2831853;
+                    float z = hash(cell + 17.0) * 2.0 - 1.0;
+                    float r = sqrt(1.0 - z * z);
+                    return vec3(r * cos(angle), r * sin(angle), z);
+                }
+
+                // Classic gradient noise, roughly from -1 to 1.
+                float perlin(vec3 p) {
+                    vec3 cell = floor(p);
+                    vec3 f = fract(p);
+                    vec3 u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+                    float n000 = dot(latticeGradient(cell), f);
+                    float n100 = dot(latticeGradient(cell + vec3(1.0, 0.0, 0.0)), f - vec3(1.0, 0.0, 0.0));
+                    float n010 = dot(latticeGradient(cell + vec3(0.0, 1.0, 0.0)), f - vec3(0.0, 1.0, 0.0));
+                    float n110 = dot(latticeGradient(cell + vec3(1.0, 1.0, 0.0)), f - vec3(1.0, 1.0, 0.0));
+                    float n001 = dot(latticeGradient(cell + vec3(0.0, 0.0, 1.0)), f - vec3(0.0, 0.0, 1.0));
+                    float n101 = dot(latticeGradient(cell + vec3(1.0, 0.0, 1.0)), f - vec3(1.0, 0.0, 1.0));
+                    float n011 = dot(latticeGradient(cell + vec3(0.0, 1.0, 1.0)), f - vec3(0.0, 1.0, 1.0));
+                    float n111 = dot(latticeGradient(cell + vec3(1.0, 1.0, 1.0)), f - vec3(1.0, 1.0, 1.0));
+                    float nx00 = mix(n000, n100, u.x);
+                    float nx10 = mix(n010, n110, u.x);
+                    float nx01 = mix(n001, n101, u.x);
+                    float nx11 = mix(n011, n111, u.x);
+                    return mix(mix(nx00, nx10, u.y), mix(nx01, nx11, u.y), u.z);
+                }
+
+                float turbulence(vec3 p) {
+                    float total = 0.0;
+                    float amplitude = 1.0;
+                    for (int octave = 0; octave < 4; octave++) {
+                        total += abs(perlin(p)) * amplitude;
+                        p *= 2.0;
+                        amplitude *= 0.5;
+                    }
+                    return total;
+                }
 
-                    float closestDepth = texture(shadowMap, positionFromLightPovInTexture.xy).r;
-                    bool anyDepth = texture(shadowMap, positionFromLightPovInTexture.xy).a > 0.0;
+                // Rotate a color around the grey axis.
+                vec3 shiftHue(vec3 color, float angle) {
+                    vec3 axis = vec3(0.57735);
+                    float cosAngle = cos(angle);
+                    return color * cosAngle + cross(axis, color) * sin(angle) + axis * dot(axis, color) * (1.0 - cosAngle);
+                }
+
+                // How far towards the secondary color this fragment is, from 0 to 1.
+                float patternValue(vec3 p) {
+                    if (u_pattern == 1) {
+                        return hash(floor(p * 8.0));
+                    } else if (u_pattern == 2) {
+                        return rand_sinwave(p.x);
+                    } else if (u_pattern == 3) {
+                        return rand_sinwave(p.y);
+                    } else if (u_pattern == 4) {
+                        return rand_sinwave(p.z);
+                    } else if (u_pattern == 5) {
+                        return perlin(p * 2.0) * 0.5 + 0.5;
+                    } else if (u_pattern == 6) {
+                        return mod(floor(p.x) + floor(p.y) + floor(p.z), 2.0);
+                    } else if (u_pattern == 7) {
+                        return smoothstep(0.3, 0.7, fract(length(p.xz) * 2.0 + perlin(p) * 0.4));
+                    } else if (u_pattern == 8) {
+                        return sin(p.x * 2.0 + turbulence(p) * 5.0) * 0.5 + 0.5;
+                    }
+                    return 0.0;
+                }
 
-                    float inLight = (!anyDepth || closestDepth > (positionFromLightPovInTexture.z)) ? 1.0 : 0.0;
+                vec3 pointLighting(vec3 normal) {
+                    vec3 total = vec3(0.0);
+                    for (int i = 0; i < u_point_lights.count; i++) {
+                        vec3 toLight = u_point_lights.positions[i].xyz - v_world_position;
+                        float distance = length(toLight);
+                        float shade = max(dot(normal, toLight / max(distance, 0.001)), 0.0);
+                        float strength = u_point_lights.colors[i].w;
+                        float attenuation = strength * 2.0 / (1.0 + 0.3 * distance * distance);
+                        total += u_point_lights.colors[i].rgb * shade * attenuation;
+                    }
+                    return total;
+                }
+                ",
+            ANIMATE_FLUID,
+            "
+                // Percentage closer filtering. Average the shadow test over a square of texels
+                // so shadow edges are soft instead of jagged.
+                float shadowVisibility(float shade) {
+                    if (!u_shadows) {
+                        return 1.0;
+                    }
+                    vec3 positionFromLightPovInTexture = (positionFromLightPov.xyz/positionFromLightPov.w) * 0.5 + 0.5;
+                    // Surfaces at a grazing angle to the light need more bias to avoid acne.
+                    float bias = u_shadow_bias * (1.0 + 3.0 * (1.0 - shade));
+                    vec2 texelSize = vec2(1.0 / float(u_shadow_texture_size));
+                    float lit = 0.0;
+                    float samples = 0.0;
+                    for (int x = -u_shadow_softness; x <= u_shadow_softness; x++) {
+                        for (int y = -u_shadow_softness; y <= u_shadow_softness; y++) {
+                            vec4 closest = texture(shadowMap, positionFromLightPovInTexture.xy + vec2(x, y) * texelSize);
+                            bool anyDepth = closest.a > 0.0;
+                            lit += (!anyDepth || closest.r + bias > positionFromLightPovInTexture.z) ? 1.0 : 0.0;
+                            samples += 1.0;
+                        }
+                    }
+                    return lit / samples;
+                }
 
+                void main(void) {
                     // Diffuse
-                    vec3 lightDir = normalize(-(vec3(-3.0, -10.0, 5.0)));
+                    vec3 lightDir = normalize(u_light_direction);
                     vec3 normal = normalize(v_normal);
                     float shade = max(dot(normal, lightDir), 0.0);
 
+                    float inLight = shadowVisibility(shade);
 
-                    float combined = ambientLight + 0.6 * shade * inLight;
-                    float fluidCompensation = 1.0;
-                    float noiseCompensation = 1.0;
-
-                    if (u_fluid) {
-                        fluidCompensation = animateFluid() * 0.2 + 0.9;
+                    // Textured voxels look up their face tile in the atlas, tinted by the material color.
+                    vec3 baseColor = u_color.rgb;
+                    if (u_texture > 0) {
+                        int face = normal.y > 0.5 ? 0 : (normal.y < -0.5 ? 2 : 1);
+                        int tile = (u_texture - 1) * 3 + face;
+                        vec2 cell = vec2(tile % u_atlas_columns, u_atlas_rows - 1 - tile / u_atlas_columns);
+                        vec2 atlasPosition = (cell + fract(v_tex_coords)) / vec2(u_atlas_columns, u_atlas_rows);
+                        baseColor *= texture(u_atlas, atlasPosition).rgb;
                     }
-                    if (u_noise) {
-                        noiseCompensation = rand(worldPosition.xy) * 0.6 + 0.7;
+                    float patternAmount = patternValue(v_world_position * u_pattern_scale);
+                    if (u_pattern_shade) {
+                        baseColor *= 1.0 + (patternAmount * 2.0 - 1.0) * u_pattern_contrast;
+                    } else {
+                        baseColor = mix(baseColor, u_secondary_color, patternAmount * u_pattern_contrast);
                     }
-                    if (u_noise_x) {
-                        noiseCompensation = rand_sinwave(worldPosition.x) * 0.4 + 0.8;
+                    float fluidWave = 0.0;
+                    if (u_fluid) {
+                        fluidWave = animateFluid(v_world_position);
+                        baseColor = shiftHue(baseColor, fluidWave / 6.0 * u_fluid_color_shift * 3.14159);
                     }
-                    if (u_noise_y) {
-                        noiseCompensation = rand_sinwave(worldPosition.y) * 0.4 + 0.8;
+
+                    // Blinn-Phong specular. Smoother surfaces get a tighter, brighter highlight
+                    // and metals tint the highlight with their own color.
+                    vec3 viewDir = normalize(u_camera_position - v_world_position);
+                    vec3 halfway = normalize(lightDir + viewDir);
+                    float shininess = exp2(10.0 * (1.0 - u_roughness) + 1.0);
+                    float highlight = pow(max(dot(normal, halfway), 0.0), shininess) * (1.0 - u_roughness);
+                    vec3 specularColor = mix(vec3(0.5), baseColor, u_metalness);
+                    vec3 specular = specularColor * u_light_color * (u_light_intensity * highlight * inLight);
+
+                    float occlusion = u_ambient_occlusion ? v_occlusion : 1.0;
+                    vec3 combined = (vec3(u_ambient_light) + u_light_color * (u_light_intensity * shade * inLight) + pointLighting(normal)) * occlusion;
+                    // Metals have very little diffuse color of their own.
+                    vec3 diffuseColor = baseColor * (1.0 - 0.8 * u_metalness);
+                    float fluidCompensation = 1.0;
+
+                    if (u_fluid) {
+                        fluidCompensation = fluidWave * 0.2 + 0.9;
                     }
-                    if (u_noise_z) {
-                        noiseCompensation = rand_sinwave(worldPosition.z) * 0.4 + 0.8;
+                    // Glowing materials stay bright even when nothing lights them.
+                    vec3 glow = baseColor * u_emissive;
+                    vec3 color = diffuseColor * combined + specular * occlusion + glow;
+                    if (u_fog) {
+                        float distance = length(v_world_position - u_camera_position);
+                        color = mix(color, u_fog_color, smoothstep(u_fog_distance * 0.5, u_fog_distance, distance));
                     }
-                    fragColor = vec4(u_color.rgb * combined * noiseCompensation, u_color.a * fluidCompensation);
+                    fragColor = vec4(color, u_color.a * fluidCompensation);
                 }
-                ";
+                ",
+        ]
+        .concat()
+        // Size the point light block from the same constant as the uniform buffer.
+        .replace("MAX_POINT_LIGHTS", &MAX_POINT_LIGHTS.to_string());
 
         let program = glium::Program::from_source(
             display,
-            vertex_shader_source,
-            fragment_shader_source,
+            &vertex_shader_source,
+            &fragment_shader_source,
             None,
         );
         if program.is_err() {
@@ -272,7 +716,7 @@ impl Graphics {
         );
 
         // Compute the matrices
-        let projection_matrix = self.build_light_projection();
+        let projection_matrix = self.build_light_projection(&view);
         let model_view = (view * model).to_homogeneous();
         let model_view_array: [[f32; 4]; 4] = model_view.into();
         let projection_array: [[f32; 4]; 4] = projection_matrix.into();
@@ -324,16 +768,14 @@ impl Graphics {
         let color = drawable.color();
 
         // We need to calculate the model matrix for the drawable object
-        let eye = camera.eye;
-        let target = camera.target;
-        let view = Isometry3::look_at_rh(&eye, &target, &Vector3::y());
+        let view = camera.view();
 
         let model = Isometry3::new(
             Vector3::from_row_slice(drawable.translation()),
             Vector3::from_row_slice(drawable.rotation()),
         );
 
-        let projection_matrix = self.build_camera_projection();
+        let projection_matrix = self.build_camera_projection(&camera);
         let model_view = (view * model).to_homogeneous();
         let model_matrix = model.to_homogeneous();
         let model_view_array: [[f32; 4]; 4] = model_view.into();
@@ -344,7 +786,7 @@ impl Graphics {
         let light_eye = light.eye;
         let light_target = light.target;
         let light_view = Isometry3::look_at_rh(&light_eye, &light_target, &Vector3::y());
-        let light_projection_matrix = self.build_light_projection();
+        let light_projection_matrix = self.build_light_projection(&light_view);
         let light_model_view = (light_view * model).to_homogeneous();
         let light_model_view_array: [[f32; 4]; 4] = light_model_view.into();
         let light_projection_array: [[f32; 4]; 4] = light_projection_matrix.into();
@@ -354,15 +796,49 @@ impl Graphics {
             .unwrap()
             .sampled()
             .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest);
+        let atlas = self
+            .atlas_texture
+            .as_ref()
+            .expect("Texture atlas should be initialized")
+            .sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest);
+        let pattern = drawable.pattern();
+        let fluid = drawable.fluid_settings();
 
         let uniforms = uniform! {
           u_color: *color,
           u_fluid: drawable.fluid() != 0,
-          u_noise: drawable.noise() != 0,
-          u_noise_x: drawable.noise_x() != 0,
-          u_noise_y: drawable.noise_y() != 0,
-          u_noise_z: drawable.noise_z() != 0,
+          u_fluid_speed: fluid.speed,
+          u_fluid_wave_scale: fluid.wave_scale,
+          u_fluid_color_shift: fluid.color_shift,
+          u_fluid_displacement: fluid.displacement,
+          u_pattern: pattern.pattern.index(),
+          u_pattern_scale: pattern.scale,
+          u_pattern_contrast: pattern.contrast,
+          u_secondary_color: pattern.secondary_color,
+          u_pattern_shade: pattern.shade,
           u_time: elapsed,
+          u_ambient_occlusion: self.lighting.ambient_occlusion,
+          u_light_direction: self.lighting.direction(),
+          u_light_color: self.lighting.color,
+          u_light_intensity: self.lighting.intensity,
+          u_ambient_light: self.lighting.ambient,
+          u_shadows: self.lighting.shadows,
+          u_shadow_bias: self.lighting.shadow_bias,
+          u_shadow_softness: self.lighting.shadow_softness.min(MAX_SHADOW_SOFTNESS) as i32,
+          u_emissive: drawable.emissive(),
+          u_roughness: drawable.roughness(),
+          u_metalness: drawable.metalness(),
+          u_camera_position: [camera.eye.x, camera.eye.y, camera.eye.z],
+          u_fog: self.atmosphere.fog,
+          u_fog_distance: self.atmosphere.fog_distance,
+          u_fog_color: self.atmosphere.horizon_color,
+          u_texture: drawable.texture() as i32,
+          u_atlas: atlas,
+          u_atlas_columns: ATLAS_COLUMNS as i32,
+          u_atlas_rows: self.atlas_rows as i32,
+          PointLights: self.point_lights.as_ref().expect("Point lights should be initialized"),
           u_shadow_texture_size:       self.shadow_texture_size,
           uMVMatrix: model_view_array,
           uMMatrix: model_array,
@@ -381,12 +857,7 @@ impl Graphics {
                 write: true,
                 ..Default::default()
             },
-            viewport: Some(glium::Rect {
-                left: 0,
-                bottom: 0,
-                width: self.canvas_width,
-                height: self.canvas_height,
-            }),
+            viewport: Some(self.viewport),
             ..Default::default()
         };
         frame
@@ -415,16 +886,14 @@ impl Graphics {
         let indices = glium::index::NoIndices(PrimitiveType::TrianglesList);
 
         // We need to calculate the model matrix for the drawable object
-        let eye = camera.eye;
-        let target = camera.target;
-        let view = Isometry3::look_at_rh(&eye, &target, &Vector3::y());
+        let view = camera.view();
 
         let model = Isometry3::new(
             Vector3::from_row_slice(&[0.0, 0.0, 0.0]),
             Vector3::from_row_slice(&[0.0, 0.0, 0.0]),
         );
 
-        let projection_matrix = self.build_camera_projection();
+        let projection_matrix = self.build_camera_projection(&camera);
         let projection_array: [[f32; 4]; 4] = projection_matrix.into();
         let model_view = (view * model).to_homogeneous();
         let model_view_array: [[f32; 4]; 4] = model_view.into();
@@ -435,7 +904,7 @@ impl Graphics {
         let light_eye = light.eye;
         let light_target = light.target;
         let light_view = Isometry3::look_at_rh(&light_eye, &light_target, &Vector3::y());
-        let light_projection_matrix = self.build_light_projection();
+        let light_projection_matrix = self.build_light_projection(&light_view);
         let light_projection_array: [[f32; 4]; 4] = light_projection_matrix.into();
         let light_model_view = (light_view * model).to_homogeneous();
         let light_model_view_array: [[f32; 4]; 4] = light_model_view.into();
@@ -446,21 +915,55 @@ impl Graphics {
             .unwrap()
             .sampled()
             .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest);
+        let atlas = self
+            .atlas_texture
+            .as_ref()
+            .expect("Texture atlas should be initialized")
+            .sampled()
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest);
         /*  glium::uniforms::Sampler::new(self.shadow_depth_texture.as_ref().unwrap())
         .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
         .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
         .depth_texture_comparison(Some(
             glium::uniforms::DepthTextureComparison::LessOrEqual,
         ));*/
+        let pattern = material.pattern_settings();
+        let fluid = material.fluid_settings();
 
         let uniforms = uniform! {
           u_color: material.upscale_color(),
           u_fluid: material.fluid != 0,
-          u_noise: material.noise != 0,
-          u_noise_x: material.noise_x != 0,
-          u_noise_y: material.noise_y != 0,
-          u_noise_z: material.noise_z != 0,
+          u_fluid_speed: fluid.speed,
+          u_fluid_wave_scale: fluid.wave_scale,
+          u_fluid_color_shift: fluid.color_shift,
+          u_fluid_displacement: fluid.displacement,
+          u_pattern: pattern.pattern.index(),
+          u_pattern_scale: pattern.scale,
+          u_pattern_contrast: pattern.contrast,
+          u_secondary_color: pattern.secondary_color,
+          u_pattern_shade: pattern.shade,
           u_time: elapsed,
+          u_ambient_occlusion: self.lighting.ambient_occlusion,
+          u_light_direction: self.lighting.direction(),
+          u_light_color: self.lighting.color,
+          u_light_intensity: self.lighting.intensity,
+          u_ambient_light: self.lighting.ambient,
+          u_shadows: self.lighting.shadows,
+          u_shadow_bias: self.lighting.shadow_bias,
+          u_shadow_softness: self.lighting.shadow_softness.min(MAX_SHADOW_SOFTNESS) as i32,
+          u_emissive: material.upscale_emissive(),
+          u_roughness: material.upscale_roughness(),
+          u_metalness: material.upscale_metalness(),
+          u_camera_position: [camera.eye.x, camera.eye.y, camera.eye.z],
+          u_fog: self.atmosphere.fog,
+          u_fog_distance: self.atmosphere.fog_distance,
+          u_fog_color: self.atmosphere.horizon_color,
+          u_texture: material.texture as i32,
+          u_atlas: atlas,
+          u_atlas_columns: ATLAS_COLUMNS as i32,
+          u_atlas_rows: self.atlas_rows as i32,
+          PointLights: self.point_lights.as_ref().expect("Point lights should be initialized"),
           u_shadow_texture_size:       self.shadow_texture_size,
           uMVMatrix: model_view_array,
           uMMatrix: model_array,
@@ -483,12 +986,7 @@ impl Graphics {
                 write: write_depth,
                 ..Default::default()
             },
-            viewport: Some(glium::Rect {
-                left: 0,
-                bottom: 0,
-                width: self.canvas_width,
-                height: self.canvas_height,
-            }),
+            viewport: Some(self.viewport),
             ..Default::default()
         };
         frame
@@ -513,7 +1011,70 @@ impl Graphics {
 
     /// Prepare the camera frame.
     pub fn prepare_camera_frame(&mut self, frame: &mut Frame) {
-        frame.clear_color_and_depth((0.5, 0.5, 0.7, 1.0), 1.0);
+        let horizon = self.atmosphere.horizon_color;
+        frame.clear_color_and_depth((horizon[0], horizon[1], horizon[2], 1.0), 1.0);
+    }
+
+    /// Draw the sky behind everything else.
+    pub fn draw_background(&mut self, frame: &mut Frame, camera: Camera) {
+        if self.atmosphere.background == Background::Solid {
+            // The clear color already did the job.
+            return;
+        }
+        let view_projection =
+            self.build_camera_projection(&camera) * camera.view().to_homogeneous();
+        let inverse_array: [[f32; 4]; 4] = view_projection
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .into();
+        let background = self.atmosphere.background_index();
+        let sky_color = self.atmosphere.sky_color;
+        let horizon_color = self.atmosphere.horizon_color;
+
+        match &self.sky_texture {
+            Some((_, texture)) => {
+                let sky_map = texture
+                    .sampled()
+                    .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp);
+                let uniforms = uniform! {
+                  u_inverse_view_projection: inverse_array,
+                  u_background: background,
+                  u_sky_color: sky_color,
+                  u_horizon_color: horizon_color,
+                  u_has_sky_map: true,
+                  u_sky_map: sky_map,
+                };
+                self.draw_sky(frame, &uniforms);
+            }
+            None => {
+                let uniforms = uniform! {
+                  u_inverse_view_projection: inverse_array,
+                  u_background: background,
+                  u_sky_color: sky_color,
+                  u_horizon_color: horizon_color,
+                  u_has_sky_map: false,
+                };
+                self.draw_sky(frame, &uniforms);
+            }
+        }
+    }
+
+    /// Draw the full screen sky triangle.
+    fn draw_sky(&self, frame: &mut Frame, uniforms: &impl glium::uniforms::Uniforms) {
+        let indices = glium::index::NoIndices(PrimitiveType::TrianglesList);
+        let params = glium::DrawParameters {
+            viewport: Some(self.viewport),
+            ..Default::default()
+        };
+        frame
+            .draw(
+                self.sky_vertices.as_ref().expect("Sky vertices"),
+                indices,
+                self.sky_program.as_ref().expect("Shader"),
+                uniforms,
+                &params,
+            )
+            .unwrap();
     }
 
     /// We are done with the camera frame.
@@ -1,3 +1,5 @@
+use crate::fluid::FluidSettings;
+use crate::pattern::PatternSettings;
 use crate::vertex::Vertex;
 use glium::index::PrimitiveType;
 use nalgebra::Isometry3;
@@ -16,10 +18,8 @@ pub struct Grid {
     pub rotation: [f32; 3],
     pub color: [f32; 4],
     pub fluid: i32,
-    pub noise: i32,
-    pub noise_x: i32,
-    pub noise_y: i32,
-    pub noise_z: i32,
+    pub fluid_settings: FluidSettings,
+    pub pattern: PatternSettings,
     pub key: u64,
 }
 
@@ -35,16 +35,17 @@ impl Grid {
             vertices: [Vertex {
                 position: [0.0, 0.0, 0.0],
                 normal: [0.0, 0.0, 0.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             }; 3084],
             max_scale: 300,
             translation: [0.0; 3],
             rotation: [0.0; 3],
             color: [0.2, 0.2, 0.5, 0.1],
             fluid: 0,
-            noise: 0,
-            noise_x: 0,
-            noise_y: 0,
-            noise_z: 0,
+            fluid_settings: FluidSettings::new(),
+            pattern: PatternSettings::new(),
             key: 0,
         }
     }
@@ -83,6 +84,9 @@ impl Drawable for Grid {
                     (row_vertices[2]) * scale_f / 2.0,
                 ],
                 normal: [0.0, 1.0, 0.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             };
             self.vertices[increment()] = Vertex {
                 position: [
@@ -91,6 +95,9 @@ impl Drawable for Grid {
                     (row_vertices[5]) * scale_f / 2.0,
                 ],
                 normal: [0.0, 1.0, 0.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             };
         }
 
@@ -102,6 +109,9 @@ impl Drawable for Grid {
                     (col_vertices[2]) * scale_f / 2.0,
                 ],
                 normal: [0.0, 1.0, 0.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             };
             self.vertices[increment()] = Vertex {
                 position: [
@@ -110,6 +120,9 @@ impl Drawable for Grid {
                     (col_vertices[5]) * scale_f / 2.0,
                 ],
                 normal: [0.0, 1.0, 0.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
             };
         }
 
@@ -186,20 +199,32 @@ impl Drawable for Grid {
         self.fluid
     }
 
-    fn noise(&self) -> i32 {
-        self.noise
+    fn fluid_settings(&self) -> FluidSettings {
+        self.fluid_settings
     }
 
-    fn noise_x(&self) -> i32 {
-        self.noise_x
+    fn pattern(&self) -> PatternSettings {
+        self.pattern
     }
 
-    fn noise_y(&self) -> i32 {
-        self.noise_y
+    /// The grid never glows.
+    fn emissive(&self) -> f32 {
+        0.0
     }
 
-    fn noise_z(&self) -> i32 {
-        self.noise_z
+    /// The grid is matte.
+    fn roughness(&self) -> f32 {
+        1.0
+    }
+
+    /// The grid is not metallic.
+    fn metalness(&self) -> f32 {
+        0.0
+    }
+
+    /// The grid is never textured.
+    fn texture(&self) -> u32 {
+        0
     }
 
     /// Calculate the distance from the camera to the grid.
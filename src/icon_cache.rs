@@ -0,0 +1,172 @@
+use glium::Program;
+use glium::backend::glutin::Display;
+use glium::texture::{RawImage2d, SrgbTexture2d};
+use glutin::surface::WindowSurface;
+use std::collections::HashMap;
+
+/// The GPU resources the UI draws with, so icons are only decoded and uploaded once
+/// and the shaders are only compiled once.
+pub struct IconCache {
+    /// Fills shapes with a single color.
+    pub shape_program: Option<Program>,
+    /// Draws an icon as it is.
+    pub image_program: Option<Program>,
+    /// Draws text from the font image in a single color.
+    pub text_program: Option<Program>,
+    /// Blends the colors of the corners across each triangle.
+    pub gradient_program: Option<Program>,
+    /// Every icon drawn so far, keyed by its path.
+    textures: HashMap<String, SrgbTexture2d>,
+}
+
+impl IconCache {
+    pub fn new() -> IconCache {
+        IconCache {
+            shape_program: None,
+            image_program: None,
+            text_program: None,
+            gradient_program: None,
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Compile the shaders. Call again if the display is recreated.
+    pub fn setup_shaders(&mut self, display: &Display<WindowSurface>) {
+        let vertex_shader_src = r#"
+            #version 140
+
+            in vec3 position;
+            void main() {
+                gl_Position = vec4(position, 1.0);
+            }
+        "#;
+        let fragment_shader_src = r#"
+            #version 140
+            uniform vec4 u_color;
+            out vec4 color;
+            void main() {
+                color = u_color;
+            }
+        "#;
+        self.shape_program = Some(
+            Program::from_source(display, vertex_shader_src, fragment_shader_src, None).unwrap(),
+        );
+
+        let vertex_shader_src = r#"
+                #version 140
+
+                in vec2 position;
+                in vec2 tex_coords;
+                out vec2 v_tex_coords;
+
+                void main() {
+                    v_tex_coords = tex_coords;
+
+                    gl_Position = vec4(position, 0.0, 1.0);
+                }
+            "#;
+        let fragment_shader_src = r#"
+                #version 140
+
+                in vec2 v_tex_coords;
+                out vec4 color;
+
+                uniform sampler2D tex;
+
+                void main() {
+                    color = texture(tex, v_tex_coords);
+                }
+            "#;
+        self.image_program = Some(
+            Program::from_source(display, vertex_shader_src, fragment_shader_src, None).unwrap(),
+        );
+
+        let fragment_shader_src = r#"
+                #version 140
+
+                in vec2 v_tex_coords;
+                out vec4 color;
+
+                uniform sampler2D tex;
+                uniform vec4 u_color;
+
+                void main() {
+                    // The font is white, so only its coverage is used.
+                    color = vec4(u_color.rgb, u_color.a * texture(tex, v_tex_coords).a);
+                }
+            "#;
+        self.text_program = Some(
+            Program::from_source(display, vertex_shader_src, fragment_shader_src, None).unwrap(),
+        );
+
+        let vertex_shader_src = r#"
+                #version 140
+
+                in vec2 position;
+                in vec4 color;
+                out vec4 v_color;
+
+                void main() {
+                    v_color = color;
+
+                    gl_Position = vec4(position, 0.0, 1.0);
+                }
+            "#;
+        let fragment_shader_src = r#"
+                #version 140
+
+                in vec4 v_color;
+                out vec4 color;
+
+                void main() {
+                    color = v_color;
+                }
+            "#;
+        self.gradient_program = Some(
+            Program::from_source(display, vertex_shader_src, fragment_shader_src, None).unwrap(),
+        );
+    }
+
+    /// Decode and upload an icon unless it is already cached. An icon that cannot be
+    /// loaded is replaced by a placeholder, so a missing file does not stop the program.
+    pub fn load(&mut self, display: &Display<WindowSurface>, path: &str) {
+        if self.textures.contains_key(path) {
+            return;
+        }
+        let image = match image::open(path) {
+            Ok(image) => {
+                let image = image.to_rgba8();
+                let image_dimensions = image.dimensions();
+                RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions)
+            }
+            Err(error) => {
+                log::error!("Could not load the icon {path}: {error}");
+                placeholder()
+            }
+        };
+        // Use SrgbTexture2d for correct color handling
+        let texture = SrgbTexture2d::new(display, image).unwrap();
+        self.textures.insert(path.to_string(), texture);
+    }
+
+    /// An icon loaded earlier with load.
+    pub fn texture(&self, path: &str) -> Option<&SrgbTexture2d> {
+        self.textures.get(path)
+    }
+}
+
+/// A magenta and black checkerboard that stands out where an icon is missing.
+fn placeholder() -> RawImage2d<'static, u8> {
+    let size = 4;
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            if (x + y) % 2 == 0 {
+                pixels.extend([255, 0, 255, 255]);
+            } else {
+                pixels.extend([0, 0, 0, 255]);
+            }
+        }
+    }
+    RawImage2d::from_raw_rgba(pixels, (size, size))
+}
@@ -1,9 +1,17 @@
+use crate::command::{MODIFIER_ALT, MODIFIER_CONTROL, MODIFIER_SHIFT, MODIFIER_SUPER};
 use crate::operating_system::OperatingSystem;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// The file in the settings folder the user can edit to change the key bindings.
+pub const KEY_BINDINGS_FILE: &str = "key_bindings.json";
+
+/// The bindings used when there is no key bindings file.
+const DEFAULT_KEY_BINDINGS: &str = include_str!("../resources/key_bindings.json");
 
 /// A list of actions that we want to handle
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Action {
-    Forward,
     OpenScene,
     SaveScene,
     MoveUp,
@@ -23,6 +31,100 @@ pub enum Action {
     ToggleFluid,
     ToggleShowGrid,
     ToggleNoise,
+    ToggleAmbientOcclusion,
+    ToggleShadows,
+    CycleLightingPreset,
+    ApplyLightColor,
+    CycleBackground,
+    ToggleFog,
+    OpenSkybox,
+    ApplySkyColor,
+    ApplyHorizonColor,
+    OpenTexture,
+    CycleTexture,
+    ApplySecondaryColor,
+    ToggleOrbit,
+    FrameSelection,
+    FrameAll,
+    ToggleProjection,
+    ViewFront,
+    ViewBack,
+    ViewLeft,
+    ViewRight,
+    ViewTop,
+    ViewBottom,
+    /// Glide the camera to a bookmark.
+    RecallBookmark(u32),
+    /// Remember where the camera is in a bookmark.
+    StoreBookmark(u32),
+    CycleLayout,
+    ReloadKeyBindings,
+    AddSwatch,
+    ImportPalette,
+    ExportPalette,
+    PaletteFromScene,
+    PaintSelection,
+}
+
+impl Action {
+    /// What the action does, for the list of key bindings.
+    pub fn description(&self) -> String {
+        let text = match self {
+            Action::OpenScene => "Open a scene",
+            Action::SaveScene => "Save the scene",
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::MoveBackward => "Move backward",
+            Action::MoveForward => "Move forward",
+            Action::ToggleVoxel => "Create/Destroy voxels in the current selection",
+            Action::MoveSelectionLeft => "Move selection left",
+            Action::MoveSelectionRight => "Move selection right",
+            Action::MoveSelectionUp => "Move selection up",
+            Action::MoveSelectionDown => "Move selection down",
+            Action::MoveSelectionForward => "Move selection forward",
+            Action::MoveSelectionBackward => "Move selection backward",
+            Action::ToggleSelectionShape => "Cycle the selection shape",
+            Action::ToggleFluid => "Toggle fluid mode",
+            Action::ToggleShowGrid => "Toggle grid visibility",
+            Action::ToggleNoise => "Cycle the material pattern",
+            Action::ToggleAmbientOcclusion => "Toggle ambient occlusion",
+            Action::ToggleShadows => "Toggle shadows",
+            Action::CycleLightingPreset => "Cycle the lighting preset",
+            Action::ApplyLightColor => "Use the current material color for the light",
+            Action::CycleBackground => "Cycle the background",
+            Action::ToggleFog => "Toggle fog",
+            Action::OpenSkybox => "Choose a cube map image for the background",
+            Action::ApplySkyColor => "Use the current material color for the sky",
+            Action::ApplyHorizonColor => "Use the current material color for the horizon and fog",
+            Action::OpenTexture => "Add a texture from PNG files",
+            Action::CycleTexture => "Cycle the material texture",
+            Action::ApplySecondaryColor => {
+                "Use the current material color as the pattern secondary color"
+            }
+            Action::ToggleOrbit => "Toggle orbiting around the selection",
+            Action::FrameSelection => "Frame the selection",
+            Action::FrameAll => "Frame the whole model",
+            Action::ToggleProjection => "Toggle between perspective and orthographic",
+            Action::ViewFront => "Look from the front",
+            Action::ViewBack => "Look from the back",
+            Action::ViewLeft => "Look from the left",
+            Action::ViewRight => "Look from the right",
+            Action::ViewTop => "Look from the top",
+            Action::ViewBottom => "Look from the bottom",
+            Action::RecallBookmark(slot) => return format!("Glide to camera bookmark {slot}"),
+            Action::StoreBookmark(slot) => return format!("Bookmark the camera as {slot}"),
+            Action::CycleLayout => "Cycle between one, two and four viewports",
+            Action::ReloadKeyBindings => "Reload the key bindings",
+            Action::AddSwatch => "Add the current material to the palette",
+            Action::ImportPalette => "Add the colors from a palette file to the palette",
+            Action::ExportPalette => "Save the palette to a file",
+            Action::PaletteFromScene => "Switch the palette between the materials in the scene, most used first, and your own",
+            Action::PaintSelection => "Paint the filled voxels in the selection with the current material",
+        };
+        text.to_string()
+    }
 }
 
 /// A list of virtual key codes that we want to handle
@@ -34,10 +136,29 @@ pub enum VirtualKeyCode {
     ToggleNoise = 4004,
     ToggleFluid = 4005,
     ToggleSelectionShape = 4006,
+    ToggleAmbientOcclusion = 4007,
+    ToggleShadows = 4008,
+    CycleLightingPreset = 4009,
+    ApplyLightColor = 4010,
+    CycleBackground = 4011,
+    ToggleFog = 4012,
+    OpenSkybox = 4013,
+    ApplySkyColor = 4014,
+    ApplyHorizonColor = 4015,
+    OpenTexture = 4016,
+    CycleTexture = 4017,
+    ApplySecondaryColor = 4018,
+    ToggleOrbit = 4019,
+    FrameSelection = 4020,
+    FrameAll = 4021,
+    CycleLayout = 4022,
+    ImportPalette = 4023,
+    ExportPalette = 4024,
+    PaletteFromScene = 4025,
 }
 
 impl VirtualKeyCode {
-    fn from_u32(value: u32) -> Option<VirtualKeyCode> {
+    pub fn from_u32(value: u32) -> Option<VirtualKeyCode> {
         match value {
             4001 => Some(VirtualKeyCode::OpenScene),
             4002 => Some(VirtualKeyCode::SaveScene),
@@ -45,13 +166,152 @@ impl VirtualKeyCode {
             4004 => Some(VirtualKeyCode::ToggleNoise),
             4005 => Some(VirtualKeyCode::ToggleFluid),
             4006 => Some(VirtualKeyCode::ToggleSelectionShape),
+            4007 => Some(VirtualKeyCode::ToggleAmbientOcclusion),
+            4008 => Some(VirtualKeyCode::ToggleShadows),
+            4009 => Some(VirtualKeyCode::CycleLightingPreset),
+            4010 => Some(VirtualKeyCode::ApplyLightColor),
+            4011 => Some(VirtualKeyCode::CycleBackground),
+            4012 => Some(VirtualKeyCode::ToggleFog),
+            4013 => Some(VirtualKeyCode::OpenSkybox),
+            4014 => Some(VirtualKeyCode::ApplySkyColor),
+            4015 => Some(VirtualKeyCode::ApplyHorizonColor),
+            4016 => Some(VirtualKeyCode::OpenTexture),
+            4017 => Some(VirtualKeyCode::CycleTexture),
+            4018 => Some(VirtualKeyCode::ApplySecondaryColor),
+            4019 => Some(VirtualKeyCode::ToggleOrbit),
+            4020 => Some(VirtualKeyCode::FrameSelection),
+            4021 => Some(VirtualKeyCode::FrameAll),
+            4022 => Some(VirtualKeyCode::CycleLayout),
+            4023 => Some(VirtualKeyCode::ImportPalette),
+            4024 => Some(VirtualKeyCode::ExportPalette),
+            4025 => Some(VirtualKeyCode::PaletteFromScene),
             _ => None, // Returns None for unknown values
         }
     }
 }
 
+/// One entry in the key bindings file. The key is a key name, optionally after
+/// modifiers joined with plus signs, like "Ctrl+Shift+S".
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Binding {
+    pub key: String,
+    pub action: Action,
+    /// Does holding the key down keep triggering the action?
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+/// The layout of the key bindings file.
+#[derive(Serialize, Deserialize)]
+struct BindingsFile {
+    bindings: Vec<Binding>,
+}
+
+/// Key names and their scancodes on Linux and Mac.
+const KEY_NAMES: &[(&str, u32, u32)] = &[
+    ("A", 30, 0),
+    ("B", 48, 11),
+    ("C", 46, 8),
+    ("D", 32, 2),
+    ("E", 18, 14),
+    ("F", 33, 3),
+    ("G", 34, 5),
+    ("H", 35, 4),
+    ("I", 23, 34),
+    ("J", 36, 38),
+    ("K", 37, 40),
+    ("L", 38, 37),
+    ("M", 50, 46),
+    ("N", 49, 45),
+    ("O", 24, 31),
+    ("P", 25, 35),
+    ("Q", 16, 12),
+    ("R", 19, 15),
+    ("S", 31, 1),
+    ("T", 20, 17),
+    ("U", 22, 32),
+    ("V", 47, 9),
+    ("W", 17, 13),
+    ("X", 45, 7),
+    ("Y", 21, 16),
+    ("Z", 44, 6),
+    ("1", 2, 18),
+    ("2", 3, 19),
+    ("3", 4, 20),
+    ("4", 5, 21),
+    ("5", 6, 23),
+    ("6", 7, 22),
+    ("7", 8, 26),
+    ("8", 9, 28),
+    ("9", 10, 25),
+    ("0", 11, 29),
+    ("-", 12, 27),
+    ("=", 13, 24),
+    ("[", 26, 33),
+    ("]", 27, 30),
+    (";", 39, 41),
+    ("'", 40, 39),
+    ("`", 41, 50),
+    ("\\", 43, 42),
+    (",", 51, 43),
+    (".", 52, 47),
+    ("/", 53, 44),
+    ("Space", 57, 49),
+    ("Tab", 15, 48),
+    ("Enter", 28, 36),
+    ("Backspace", 14, 51),
+    ("Escape", 1, 53),
+    ("Delete", 111, 117),
+    ("Home", 102, 115),
+    ("End", 107, 119),
+    ("PageUp", 104, 116),
+    ("PageDown", 109, 121),
+    ("Up", 103, 126),
+    ("Down", 108, 125),
+    ("Left", 105, 123),
+    ("Right", 106, 124),
+    ("F1", 59, 122),
+    ("F2", 60, 120),
+    ("F3", 61, 99),
+    ("F4", 62, 118),
+    ("F5", 63, 96),
+    ("F6", 64, 97),
+    ("F7", 65, 98),
+    ("F8", 66, 100),
+    ("F9", 67, 101),
+    ("F10", 68, 109),
+    ("F11", 87, 103),
+    ("F12", 88, 111),
+    ("Numpad0", 82, 82),
+    ("Numpad1", 79, 83),
+    ("Numpad2", 80, 84),
+    ("Numpad3", 81, 85),
+    ("Numpad4", 75, 86),
+    ("Numpad5", 76, 87),
+    ("Numpad6", 77, 88),
+    ("Numpad7", 71, 89),
+    ("Numpad8", 72, 91),
+    ("Numpad9", 73, 92),
+];
+
+/// Modifier names and the bits they set.
+const MODIFIER_NAMES: &[(&str, u32)] = &[
+    ("Shift", MODIFIER_SHIFT),
+    ("Ctrl", MODIFIER_CONTROL),
+    ("Control", MODIFIER_CONTROL),
+    ("Alt", MODIFIER_ALT),
+    ("Option", MODIFIER_ALT),
+    ("Super", MODIFIER_SUPER),
+    ("Cmd", MODIFIER_SUPER),
+    ("Command", MODIFIER_SUPER),
+];
+
 pub struct KeyBindings {
-    pub os: OperatingSystem
+    pub os: OperatingSystem,
+    /// The bindings in the order they were listed.
+    bindings: Vec<Binding>,
+    /// Scancode, modifier bits and the index of the binding they trigger.
+    table: Vec<(u32, u32, usize)>,
 }
 
 impl KeyBindings {
@@ -60,28 +320,140 @@ impl KeyBindings {
         #[cfg(target_os = "linux")]
         {
             KeyBindings {
-                os: OperatingSystem::Linux
+                os: OperatingSystem::Linux,
+                bindings: Vec::new(),
+                table: Vec::new(),
             }
         }
         #[cfg(target_os = "macos")]
         {
             KeyBindings {
-                os: OperatingSystem::Mac
+                os: OperatingSystem::Mac,
+                bindings: Vec::new(),
+                table: Vec::new(),
             }
         }
     }
 
+    /// Load the bindings from a file, or use the defaults if there is no file.
+    /// Calling this again picks up changes to the file.
+    pub fn load(&mut self, path: &str) {
+        let text = fs::read_to_string(path).ok();
+        let file = text.as_deref().and_then(|text| {
+            serde_json::from_str::<BindingsFile>(text)
+                .map_err(|error| log::error!("Could not read the key bindings in {path}: {error}"))
+                .ok()
+        });
+        match file {
+            Some(file) => {
+                println!("Loaded key bindings from {path}");
+                self.set_bindings(file.bindings);
+            }
+            None => {
+                let defaults: BindingsFile = serde_json::from_str(DEFAULT_KEY_BINDINGS)
+                    .expect("The default key bindings should be valid");
+                self.set_bindings(defaults.bindings);
+            }
+        }
+    }
+
+    /// Replace the bindings. Keys that cannot be understood are skipped, and when
+    /// one key is bound to two actions the first one wins.
+    pub fn set_bindings(&mut self, bindings: Vec<Binding>) {
+        self.table.clear();
+        for (index, binding) in bindings.iter().enumerate() {
+            let Some((code, modifiers)) = self.parse_key(&binding.key) else {
+                log::warn!("Unknown key {} for {:?}", binding.key, binding.action);
+                continue;
+            };
+            match self.bound(code, modifiers) {
+                Some(bound) if bindings[bound].action != binding.action => log::warn!(
+                    "{} is bound to both {:?} and {:?}, using {:?}",
+                    binding.key,
+                    bindings[bound].action,
+                    binding.action,
+                    bindings[bound].action
+                ),
+                Some(_) => (),
+                None => self.table.push((code, modifiers, index)),
+            }
+        }
+        self.bindings = bindings;
+    }
+
+    /// Turn a key like "Ctrl+S" into its scancode and modifier bits.
+    fn parse_key(&self, key: &str) -> Option<(u32, u32)> {
+        let mut parts: Vec<&str> = key.split('+').map(|part| part.trim()).collect();
+        let name = parts.pop()?;
+        let mut modifiers = 0;
+        for part in parts {
+            let (_, bit) = MODIFIER_NAMES
+                .iter()
+                .find(|(modifier, _)| modifier.eq_ignore_ascii_case(part))?;
+            modifiers |= bit;
+        }
+        let (_, linux, mac) = KEY_NAMES
+            .iter()
+            .find(|(known, _, _)| known.eq_ignore_ascii_case(name))?;
+        let code = if self.os == OperatingSystem::Mac {
+            *mac
+        } else {
+            *linux
+        };
+        Some((code, modifiers))
+    }
+
+    /// One line per action listing the keys bound to it, in the order they were bound.
+    pub fn descriptions(&self) -> Vec<String> {
+        let mut actions: Vec<(Action, Vec<&str>)> = Vec::new();
+        for binding in self.bindings.iter() {
+            match actions
+                .iter_mut()
+                .find(|(action, _)| *action == binding.action)
+            {
+                Some((_, keys)) => keys.push(&binding.key),
+                None => actions.push((binding.action, vec![&binding.key])),
+            }
+        }
+        actions
+            .iter()
+            .map(|(action, keys)| format!("{}: {}", keys.join(" or "), action.description()))
+            .collect()
+    }
+
     pub fn virtual_key(&self, action: Option<Action>) -> Option<VirtualKeyCode> {
         match action {
             Some(Action::ToggleShowGrid) => Some(VirtualKeyCode::ToggleShowGrid),
             Some(Action::ToggleNoise) => Some(VirtualKeyCode::ToggleNoise),
             Some(Action::ToggleFluid) => Some(VirtualKeyCode::ToggleFluid),
             Some(Action::ToggleSelectionShape) => Some(VirtualKeyCode::ToggleSelectionShape),
+            Some(Action::ToggleAmbientOcclusion) => Some(VirtualKeyCode::ToggleAmbientOcclusion),
+            Some(Action::ToggleShadows) => Some(VirtualKeyCode::ToggleShadows),
+            Some(Action::CycleLightingPreset) => Some(VirtualKeyCode::CycleLightingPreset),
+            Some(Action::ApplyLightColor) => Some(VirtualKeyCode::ApplyLightColor),
+            Some(Action::CycleBackground) => Some(VirtualKeyCode::CycleBackground),
+            Some(Action::ToggleFog) => Some(VirtualKeyCode::ToggleFog),
+            Some(Action::OpenSkybox) => Some(VirtualKeyCode::OpenSkybox),
+            Some(Action::ApplySkyColor) => Some(VirtualKeyCode::ApplySkyColor),
+            Some(Action::ApplyHorizonColor) => Some(VirtualKeyCode::ApplyHorizonColor),
+            Some(Action::OpenTexture) => Some(VirtualKeyCode::OpenTexture),
+            Some(Action::CycleTexture) => Some(VirtualKeyCode::CycleTexture),
+            Some(Action::ApplySecondaryColor) => Some(VirtualKeyCode::ApplySecondaryColor),
+            Some(Action::ToggleOrbit) => Some(VirtualKeyCode::ToggleOrbit),
+            Some(Action::FrameSelection) => Some(VirtualKeyCode::FrameSelection),
+            Some(Action::FrameAll) => Some(VirtualKeyCode::FrameAll),
+            Some(Action::CycleLayout) => Some(VirtualKeyCode::CycleLayout),
+            Some(Action::ImportPalette) => Some(VirtualKeyCode::ImportPalette),
+            Some(Action::ExportPalette) => Some(VirtualKeyCode::ExportPalette),
+            Some(Action::PaletteFromScene) => Some(VirtualKeyCode::PaletteFromScene),
             _ => None
         }
     }
 
-    pub fn action(&self, code: u32) -> Option<Action> {
+    /// Find the action for a key pressed with the given modifiers held. Repeats from a
+    /// held key only trigger bindings that ask for them. Virtual key codes from the
+    /// buttons ignore the modifiers.
+    pub fn action(&self, code: u32, modifiers: u32, repeat: bool) -> Option<Action> {
         let virtual_code_opt = VirtualKeyCode::from_u32(code);
 
         match virtual_code_opt {
@@ -91,55 +463,40 @@ impl KeyBindings {
             Some(VirtualKeyCode::ToggleNoise) => Some(Action::ToggleNoise),
             Some(VirtualKeyCode::ToggleFluid) => Some(Action::ToggleFluid),
             Some(VirtualKeyCode::ToggleSelectionShape) => Some(Action::ToggleSelectionShape),
-            None => {
-                if self.os == OperatingSystem::Linux {
-                    return match code {
-                        17 => Some(Action::Forward),
-                        16 => Some(Action::MoveUp),
-                        18 => Some(Action::MoveDown),
-                        30 => Some(Action::MoveLeft),
-                        32 => Some(Action::MoveRight),
-                        31 => Some(Action::MoveBackward),
-                        17 => Some(Action::MoveForward),
-                        57 => Some(Action::ToggleVoxel),
-                        75 => Some(Action::MoveSelectionLeft),
-                        77 => Some(Action::MoveSelectionRight),
-                        71 => Some(Action::MoveSelectionUp),
-                        73 => Some(Action::MoveSelectionDown),
-                        72 => Some(Action::MoveSelectionForward),
-                        76 => Some(Action::MoveSelectionBackward),
-                        20 => Some(Action::ToggleSelectionShape),
-                        33 => Some(Action::ToggleFluid),
-                        34 => Some(Action::ToggleShowGrid),
-                        49 => Some(Action::ToggleNoise),
-                        _ => None
-                    }
-                }
-                if self.os == OperatingSystem::Mac {
-                    return match code {
-                        126 => Some(Action::Forward),
-                        12 => Some(Action::MoveUp),
-                        14 => Some(Action::MoveDown),
-                        0 => Some(Action::MoveLeft),
-                        2 => Some(Action::MoveRight),
-                        1 => Some(Action::MoveBackward),
-                        13 => Some(Action::MoveForward),
-                        49 => Some(Action::ToggleVoxel),
-                        38 => Some(Action::MoveSelectionLeft),
-                        37 => Some(Action::MoveSelectionRight),
-                        32 => Some(Action::MoveSelectionUp),
-                        31 => Some(Action::MoveSelectionDown),
-                        34 => Some(Action::MoveSelectionForward),
-                        40 => Some(Action::MoveSelectionBackward),
-                        17 => Some(Action::ToggleSelectionShape),
-                        3 => Some(Action::ToggleFluid),
-                        5 => Some(Action::ToggleShowGrid),
-                        45 => Some(Action::ToggleNoise),
-                        _ => None
-                    }
-                }
-                return None
-            }
+            Some(VirtualKeyCode::ToggleAmbientOcclusion) => Some(Action::ToggleAmbientOcclusion),
+            Some(VirtualKeyCode::ToggleShadows) => Some(Action::ToggleShadows),
+            Some(VirtualKeyCode::CycleLightingPreset) => Some(Action::CycleLightingPreset),
+            Some(VirtualKeyCode::ApplyLightColor) => Some(Action::ApplyLightColor),
+            Some(VirtualKeyCode::CycleBackground) => Some(Action::CycleBackground),
+            Some(VirtualKeyCode::ToggleFog) => Some(Action::ToggleFog),
+            Some(VirtualKeyCode::OpenSkybox) => Some(Action::OpenSkybox),
+            Some(VirtualKeyCode::ApplySkyColor) => Some(Action::ApplySkyColor),
+            Some(VirtualKeyCode::ApplyHorizonColor) => Some(Action::ApplyHorizonColor),
+            Some(VirtualKeyCode::OpenTexture) => Some(Action::OpenTexture),
+            Some(VirtualKeyCode::CycleTexture) => Some(Action::CycleTexture),
+            Some(VirtualKeyCode::ApplySecondaryColor) => Some(Action::ApplySecondaryColor),
+            Some(VirtualKeyCode::ToggleOrbit) => Some(Action::ToggleOrbit),
+            Some(VirtualKeyCode::FrameSelection) => Some(Action::FrameSelection),
+            Some(VirtualKeyCode::FrameAll) => Some(Action::FrameAll),
+            Some(VirtualKeyCode::CycleLayout) => Some(Action::CycleLayout),
+            Some(VirtualKeyCode::ImportPalette) => Some(Action::ImportPalette),
+            Some(VirtualKeyCode::ExportPalette) => Some(Action::ExportPalette),
+            Some(VirtualKeyCode::PaletteFromScene) => Some(Action::PaletteFromScene),
+            None => self
+                .bound(code, modifiers)
+                .map(|index| &self.bindings[index])
+                .filter(|binding| binding.repeat || !repeat)
+                .map(|binding| binding.action),
         }
     }
+
+    /// The index of the binding for a scancode with exactly these modifiers held.
+    fn bound(&self, code: u32, modifiers: u32) -> Option<usize> {
+        self.table
+            .iter()
+            .find(|(bound_code, bound_modifiers, _)| {
+                *bound_code == code && *bound_modifiers == modifiers
+            })
+            .map(|(_, _, index)| *index)
+    }
 }
@@ -0,0 +1,255 @@
+use crate::cube::Cube;
+use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The largest shadow softness the shader supports, as the sample count grows with the square.
+pub const MAX_SHADOW_SOFTNESS: u32 = 4;
+
+/// The named lighting setups we can cycle through.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightingPreset {
+    Day,
+    Sunset,
+    Night,
+    Studio,
+}
+
+impl LightingPreset {
+    /// Get the preset that follows this one.
+    pub fn next(&self) -> LightingPreset {
+        match self {
+            LightingPreset::Day => LightingPreset::Sunset,
+            LightingPreset::Sunset => LightingPreset::Night,
+            LightingPreset::Night => LightingPreset::Studio,
+            LightingPreset::Studio => LightingPreset::Day,
+        }
+    }
+
+    /// The preset with the light color and strength nearest to some lighting, for
+    /// lighting loaded from a scene.
+    pub fn closest(lighting: &Lighting) -> LightingPreset {
+        let difference = |preset: &LightingPreset| {
+            let other = Lighting::preset(*preset);
+            let color: f32 = lighting
+                .color
+                .iter()
+                .zip(other.color)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum();
+            color + (lighting.intensity - other.intensity).powi(2)
+        };
+        [
+            LightingPreset::Day,
+            LightingPreset::Sunset,
+            LightingPreset::Night,
+            LightingPreset::Studio,
+        ]
+        .into_iter()
+        .min_by(|a, b| difference(a).total_cmp(&difference(b)))
+        .expect("There is always a preset")
+    }
+}
+
+/// Scene wide lighting settings. These are saved with the scene.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Lighting {
+    /// Angle of the light around the vertical axis in radians.
+    pub azimuth: f32,
+    /// Angle of the light above the horizon in radians.
+    pub elevation: f32,
+    /// How far the light sits from the center of the scene. Used for the shadow map.
+    pub distance: f32,
+    /// The color of the directional light.
+    pub color: [f32; 3],
+    /// How strong the directional light is.
+    pub intensity: f32,
+    /// How much light reaches surfaces that face away from the light.
+    pub ambient: f32,
+    /// Do voxels cast shadows?
+    pub shadows: bool,
+    /// Darken corners and creases with ambient occlusion.
+    pub ambient_occlusion: bool,
+    /// How much closer a surface must be than the shadow map before it is in shadow.
+    /// Raise this to remove speckled shadow acne, lower it if shadows detach from their voxels.
+    pub shadow_bias: f32,
+    /// How many shadow map texels to blur over in each direction. 0 gives hard edges.
+    pub shadow_softness: u32,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Lighting::preset(LightingPreset::Day)
+    }
+}
+
+impl Lighting {
+    /// Create the lighting settings for one of the presets.
+    pub const fn preset(preset: LightingPreset) -> Lighting {
+        match preset {
+            LightingPreset::Day => Lighting {
+                azimuth: 45.0_f32.to_radians(),
+                elevation: 35.0_f32.to_radians(),
+                distance: 104.0,
+                color: [1.0, 1.0, 1.0],
+                intensity: 0.6,
+                ambient: 0.5,
+                shadows: true,
+                ambient_occlusion: true,
+                shadow_bias: 0.0005,
+                shadow_softness: 1,
+            },
+            LightingPreset::Sunset => Lighting {
+                azimuth: 120.0_f32.to_radians(),
+                elevation: 12.0_f32.to_radians(),
+                distance: 104.0,
+                color: [1.0, 0.6, 0.3],
+                intensity: 0.8,
+                ambient: 0.35,
+                shadows: true,
+                ambient_occlusion: true,
+                shadow_bias: 0.0005,
+                shadow_softness: 2,
+            },
+            LightingPreset::Night => Lighting {
+                azimuth: 200.0_f32.to_radians(),
+                elevation: 50.0_f32.to_radians(),
+                distance: 104.0,
+                color: [0.5, 0.6, 1.0],
+                intensity: 0.25,
+                ambient: 0.15,
+                shadows: true,
+                ambient_occlusion: true,
+                shadow_bias: 0.0005,
+                shadow_softness: 1,
+            },
+            LightingPreset::Studio => Lighting {
+                azimuth: -30.0_f32.to_radians(),
+                elevation: 60.0_f32.to_radians(),
+                distance: 104.0,
+                color: [1.0, 1.0, 1.0],
+                intensity: 0.5,
+                ambient: 0.6,
+                shadows: false,
+                ambient_occlusion: true,
+                shadow_bias: 0.0005,
+                shadow_softness: 2,
+            },
+        }
+    }
+
+    /// The unit vector pointing from the scene towards the light.
+    pub fn direction(&self) -> [f32; 3] {
+        [
+            self.elevation.cos() * self.azimuth.sin(),
+            self.elevation.sin(),
+            self.elevation.cos() * self.azimuth.cos(),
+        ]
+    }
+
+    /// Where the light sits when looking at the center of the scene.
+    pub fn position(&self) -> Point3<f32> {
+        let direction = self.direction();
+        Point3::new(
+            direction[0] * self.distance,
+            direction[1] * self.distance,
+            direction[2] * self.distance,
+        )
+    }
+}
+
+/// The most point lights the camera shader can handle at once.
+pub const MAX_POINT_LIGHTS: usize = 16;
+
+/// When there are too many glowing voxels, those within a block of this size share one light.
+const POINT_LIGHT_CLUSTER_SIZE: f32 = 8.0;
+
+/// A merged light is never stronger than this, so a big lava lake doesn't flood the scene.
+const MAX_CLUSTER_STRENGTH: f32 = 4.0;
+
+/// Light given off by glowing voxels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointLight {
+    /// The center of the light in world space.
+    pub position: [f32; 3],
+    /// The color of the light.
+    pub color: [f32; 3],
+    /// How far the light reaches.
+    pub strength: f32,
+}
+
+impl PointLight {
+    /// Collect a light for every glowing cube. When there are more than the shader can handle
+    /// the lights are merged by area so large glowing regions still light their surroundings.
+    pub fn from_cubes(cubes: &[Cube]) -> Vec<PointLight> {
+        let lights: Vec<PointLight> = cubes
+            .iter()
+            .filter(|cube| cube.emissive > 0.0)
+            .map(|cube| PointLight {
+                position: [
+                    cube.translation[0] + cube.center,
+                    cube.translation[1] + cube.center,
+                    cube.translation[2] + cube.center,
+                ],
+                color: [cube.color[0], cube.color[1], cube.color[2]],
+                strength: cube.emissive,
+            })
+            .collect();
+
+        if lights.len() <= MAX_POINT_LIGHTS {
+            return lights;
+        }
+
+        let mut clusters: HashMap<[i32; 3], PointLight> = HashMap::new();
+        for light in lights.iter() {
+            let key = [
+                (light.position[0] / POINT_LIGHT_CLUSTER_SIZE).floor() as i32,
+                (light.position[1] / POINT_LIGHT_CLUSTER_SIZE).floor() as i32,
+                (light.position[2] / POINT_LIGHT_CLUSTER_SIZE).floor() as i32,
+            ];
+            let cluster = clusters.entry(key).or_insert(PointLight {
+                position: [0.0; 3],
+                color: [0.0; 3],
+                strength: 0.0,
+            });
+            // Weight by strength so the merged light sits near the brightest voxels.
+            for axis in 0..3 {
+                cluster.position[axis] += light.position[axis] * light.strength;
+                cluster.color[axis] += light.color[axis] * light.strength;
+            }
+            cluster.strength += light.strength;
+        }
+        log::info!(
+            "Merged {} emissive voxels into {} lights",
+            lights.len(),
+            clusters.len()
+        );
+
+        clusters
+            .into_values()
+            .map(|cluster| PointLight {
+                position: cluster.position.map(|value| value / cluster.strength),
+                color: cluster.color.map(|value| value / cluster.strength),
+                strength: cluster.strength.min(MAX_CLUSTER_STRENGTH),
+            })
+            .collect()
+    }
+
+    /// Choose the lights that matter most from the camera position, up to the shader limit.
+    /// Lights that miss out still glow, they just don't light their neighbours.
+    pub fn nearest(lights: &[PointLight], camera_eye: [f32; 3]) -> Vec<PointLight> {
+        let mut ranked = lights.to_vec();
+        if ranked.len() > MAX_POINT_LIGHTS {
+            let importance = |light: &PointLight| {
+                let distance_squared = (light.position[0] - camera_eye[0]).powi(2)
+                    + (light.position[1] - camera_eye[1]).powi(2)
+                    + (light.position[2] - camera_eye[2]).powi(2);
+                light.strength / (1.0 + distance_squared)
+            };
+            ranked.sort_by(|a, b| importance(b).total_cmp(&importance(a)));
+            ranked.truncate(MAX_POINT_LIGHTS);
+        }
+        ranked
+    }
+}
@@ -1,5 +1,6 @@
 use crate::command::Command;
 use crate::command::CommandType;
+use crate::command::{KEY_REPEAT, MODIFIER_ALT, MODIFIER_CONTROL, MODIFIER_SHIFT, MODIFIER_SUPER};
 use crate::graphics::Graphics;
 use crate::scene::Scene;
 use crate::ui_context::UiContext;
@@ -7,40 +8,59 @@ use env_logger;
 use glium::backend::glutin::SimpleWindowBuilder;
 use glium::winit::event::Event::{AboutToWait, WindowEvent};
 use glium::winit::event::WindowEvent::{
-    CloseRequested, CursorMoved, KeyboardInput, MouseInput, MouseWheel, RedrawRequested, Resized,
+    CloseRequested, CursorMoved, KeyboardInput, ModifiersChanged, MouseInput, MouseWheel,
+    RedrawRequested, Resized, ScaleFactorChanged,
 };
 use glium::winit::event::{ElementState, MouseButton, MouseScrollDelta};
 use glium::winit::event_loop::EventLoop;
+use glium::winit::keyboard::{Key, NamedKey};
 use glium::winit::platform::scancode::PhysicalKeyExtScancode;
 use std::time::Duration;
 use std::time::Instant;
 
+mod atmosphere;
 mod graphics;
 
+mod bookmark;
 mod button;
 mod camera;
 mod canvas;
+mod color_picker;
+mod color_vertex;
 mod command;
 mod command_queue;
+mod config;
 mod cube;
 mod drawable;
+mod fluid;
+mod frustum;
 mod grid;
+mod icon_cache;
 mod image_vertex;
 mod key_bindings;
+mod lighting;
 mod material;
+mod material_palette;
 mod model;
 mod mouse;
 mod ocnode;
 mod octree;
 mod operating_system;
 mod palette;
+mod pattern;
+mod render_chunk;
 mod scene;
 mod slider;
+mod status_bar;
+mod status_line;
 mod storage;
 mod stored_octree;
 mod swatch;
+mod texture_atlas;
 mod ui_context;
+mod ui_layout;
 mod vertex;
+mod viewport;
 mod widget;
 
 fn main() {
@@ -82,13 +102,18 @@ fn main() {
     let mut mouse_down_x = 0;
     let mut mouse_down_y = 0;
     let mut mouse_pressed = false;
+    // The modifier keys currently held, as MODIFIER_ bits.
+    let mut modifiers_held = 0;
+    // Was the pointer over the UI when it last moved?
+    let mut pointer_over_ui = false;
 
     let mut window_width = width;
     let mut window_height = height;
     let mut graphics: Graphics = Graphics::new(width, height);
     graphics.setup_shaders(&display);
 
-    let mut ui = UiContext::new();
+    let mut ui = UiContext::new((width, height), window.scale_factor());
+    ui.setup_shaders(&display);
     let mut last_fps = Instant::now();
     let mut fps_count = 0;
     ui.create_default_ui();
@@ -106,6 +131,10 @@ fn main() {
                         window_height = window_size.height;
                         graphics = Graphics::new(window_size.width, window_size.height);
                         graphics.setup_shaders(&display);
+                        ui.resize((window_width, window_height), window.scale_factor());
+                    }
+                    ScaleFactorChanged { scale_factor, .. } => {
+                        ui.resize((window_width, window_height), scale_factor);
                     }
 
                     RedrawRequested => {
@@ -119,6 +148,8 @@ fn main() {
                             scene.queue_command(*command);
                         });
 
+                        ui.set_status(scene.status());
+
                         if scene.throttle() {
                             let mut frame = display.draw();
                             // By finishing the frame swap buffers and thereby make it visible on the window
@@ -127,8 +158,15 @@ fn main() {
                             frame.finish().unwrap();
                             let end = Instant::now();
                             fps_count += 1;
-                            if end - last_fps > Duration::from_millis(1000 * 10) {
-                                println!("FPS: {:?}", fps_count as f32 / 10.0);
+                            let since_last_fps = end - last_fps;
+                            if since_last_fps > Duration::from_millis(1000) {
+                                let frames_per_second =
+                                    fps_count as f32 / since_last_fps.as_secs_f32();
+                                ui.queue_command(Command {
+                                    command_type: CommandType::FramesPerSecond,
+                                    data1: frames_per_second.to_bits(),
+                                    data2: 0,
+                                });
                                 fps_count = 0;
                                 last_fps = end;
                             }
@@ -158,9 +196,23 @@ fn main() {
                                         data1: screen_x.to_bits(),
                                         data2: screen_y.to_bits(),
                                     };
-                                    scene.queue_command(mouse_down);
+                                    // Clicks on widgets drawn over the scene stay in the UI.
+                                    if !ui.contains(screen_x, screen_y) {
+                                        scene.queue_command(mouse_down);
+                                    }
                                     ui.queue_command(mouse_down);
                                 }
+                                MouseButton::Middle => {
+                                    let screen_x =
+                                        (cursor_x as f32 / window_width as f32) * 2.0 - 1.0;
+                                    let screen_y =
+                                        -((cursor_y as f32 / window_height as f32) * 2.0 - 1.0);
+                                    scene.queue_command(Command {
+                                        command_type: CommandType::MiddleMouseDown,
+                                        data1: screen_x.to_bits(),
+                                        data2: screen_y.to_bits(),
+                                    });
+                                }
                                 _ => {}
                             },
                             ElementState::Released => match button {
@@ -185,10 +237,19 @@ fn main() {
                                             data1: screen_x.to_bits(),
                                             data2: screen_y.to_bits(),
                                         };
-                                        scene.queue_command(mouse_click);
+                                        if !ui.contains(screen_x, screen_y) {
+                                            scene.queue_command(mouse_click);
+                                        }
                                         ui.queue_command(mouse_click);
                                     }
                                 }
+                                MouseButton::Middle => {
+                                    scene.queue_command(Command {
+                                        command_type: CommandType::MiddleMouseUp,
+                                        data1: 0,
+                                        data2: 0,
+                                    });
+                                }
                                 _ => {}
                             },
                         }
@@ -206,49 +267,123 @@ fn main() {
                             data1: screen_x.to_bits(),
                             data2: screen_y.to_bits(),
                         };
+                        // Tell the scene when the pointer crosses onto or off the UI, so
+                        // drags over the UI leave the camera alone.
+                        let over_ui = ui.contains(screen_x, screen_y);
+                        if over_ui != pointer_over_ui {
+                            pointer_over_ui = over_ui;
+                            scene.queue_command(Command {
+                                command_type: CommandType::PointerOverUi,
+                                data1: over_ui as u32,
+                                data2: 0,
+                            });
+                        }
                         scene.queue_command(mouse_moved);
                         if mouse_pressed {
                             ui.queue_command(mouse_moved);
                             //scene.process_commands();
                         }
+                        ui.queue_command(Command {
+                            command_type: CommandType::MouseHover,
+                            ..mouse_moved
+                        });
                         cursor_x = position.x as u32;
                         cursor_y = position.y as u32;
                     }
                     KeyboardInput { event, .. } => {
-                        if event.state == ElementState::Pressed {
+                        if event.state == ElementState::Pressed
+                            && (ui.has_focus() || scene.has_focus())
+                        {
+                            // Typing goes to the widget with the keyboard, or to the scene
+                            // while it asks for a name, instead of triggering actions.
+                            let text = match &event.logical_key {
+                                Key::Named(NamedKey::Backspace) => String::from("\u{8}"),
+                                Key::Named(NamedKey::Enter) => String::from("\r"),
+                                Key::Named(NamedKey::Escape) => String::from("\u{1b}"),
+                                Key::Character(text) => text.to_string(),
+                                _ => String::new(),
+                            };
+                            for character in text.chars() {
+                                let typed = Command {
+                                    command_type: CommandType::TextInput,
+                                    data1: character as u32,
+                                    data2: 0,
+                                };
+                                if ui.has_focus() {
+                                    ui.queue_command(typed);
+                                } else {
+                                    scene.queue_command(typed);
+                                }
+                            }
+                        } else if event.state == ElementState::Pressed {
+                            let repeat = if event.repeat { KEY_REPEAT } else { 0 };
                             let key_pressed = Command {
                                 command_type: CommandType::KeyDown,
                                 data1: event.physical_key.to_scancode().unwrap(),
-                                data2: 0,
+                                data2: modifiers_held | repeat,
                             };
+                            // The scene tells the UI which action the key triggered.
                             scene.queue_command(key_pressed);
-                            ui.queue_command(key_pressed);
                             //scene.process_commands();
                         }
                     }
-                    MouseWheel { delta, .. } => match delta {
-                        MouseScrollDelta::LineDelta(x, y) => {
-                            let mouse_wheel = Command {
-                                command_type: CommandType::MouseScroll,
-                                data1: x as u32,
-                                data2: y as u32,
-                            };
-                            println!("Mouse wheel scrolled: x={}, y={}", x, y);
-                            scene.queue_command(mouse_wheel);
-                            ui.queue_command(mouse_wheel);
-                            //scene.process_commands();
+                    ModifiersChanged(modifiers) => {
+                        let state = modifiers.state();
+                        let mut held = 0;
+                        if state.shift_key() {
+                            held |= MODIFIER_SHIFT;
                         }
-                        MouseScrollDelta::PixelDelta(position) => {
-                            let mouse_wheel = Command {
-                                command_type: CommandType::MouseScroll,
-                                data1: position.x as u32,
-                                data2: position.y as u32,
-                            };
-                            println!("Mouse pad scrolled: x={}, y={}", position.x, position.y);
-                            scene.queue_command(mouse_wheel);
-                            ui.queue_command(mouse_wheel);
+                        if state.control_key() {
+                            held |= MODIFIER_CONTROL;
+                        }
+                        if state.alt_key() {
+                            held |= MODIFIER_ALT;
+                        }
+                        if state.super_key() {
+                            held |= MODIFIER_SUPER;
+                        }
+                        modifiers_held = held;
+                        let modifiers_changed = Command {
+                            command_type: CommandType::ModifiersChanged,
+                            data1: held,
+                            data2: 0,
+                        };
+                        scene.queue_command(modifiers_changed);
+                        ui.queue_command(modifiers_changed);
+                    }
+                    MouseWheel { delta, .. } => {
+                        let screen_x = (cursor_x as f32 / window_width as f32) * 2.0 - 1.0;
+                        let screen_y = -((cursor_y as f32 / window_height as f32) * 2.0 - 1.0);
+                        // Scrolling over a widget, like the palette, stays in the UI.
+                        let over_ui = ui.contains(screen_x, screen_y);
+                        match delta {
+                            MouseScrollDelta::LineDelta(x, y) => {
+                                let mouse_wheel = Command {
+                                    command_type: CommandType::MouseScroll,
+                                    data1: x.to_bits(),
+                                    data2: y.to_bits(),
+                                };
+                                println!("Mouse wheel scrolled: x={}, y={}", x, y);
+                                if !over_ui {
+                                    scene.queue_command(mouse_wheel);
+                                }
+                                ui.queue_command(mouse_wheel);
+                                //scene.process_commands();
+                            }
+                            MouseScrollDelta::PixelDelta(position) => {
+                                let mouse_wheel = Command {
+                                    command_type: CommandType::MouseScroll,
+                                    data1: (position.x as f32).to_bits(),
+                                    data2: (position.y as f32).to_bits(),
+                                };
+                                println!("Mouse pad scrolled: x={}, y={}", position.x, position.y);
+                                if !over_ui {
+                                    scene.queue_command(mouse_wheel);
+                                }
+                                ui.queue_command(mouse_wheel);
+                            }
                         }
-                    },
+                    }
                     _ => (),
                 },
                 AboutToWait => {
@@ -1,24 +1,42 @@
+use crate::fluid::FluidSettings;
+use crate::pattern::{Pattern, PatternSettings};
+use serde::{Deserialize, Serialize};
 use std::hash::Hash;
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone)]
 pub struct Material {
     pub color: [i32; 4],
-    pub noise: i32,
-    pub noise_x: i32,
-    pub noise_y: i32,
-    pub noise_z: i32,
+    pub pattern: Pattern,
+    /// How many times the pattern repeats per voxel, in hundredths.
+    pub pattern_scale: i32,
+    /// How far the pattern blends towards the secondary color, from 0 to 255.
+    pub pattern_contrast: i32,
+    /// The color the pattern blends towards.
+    pub secondary_color: [i32; 3],
+    /// Does the pattern brighten and darken the color instead, like old noise?
+    #[serde(default)]
+    pub pattern_shade: bool,
     pub fluid: i32,
+    /// How fast the fluid ripples travel, in tenths.
+    pub fluid_speed: i32,
+    /// How tightly packed the fluid ripples are, in hundredths.
+    pub fluid_wave_scale: i32,
+    /// How far the fluid hue swings, from 0 to 255.
+    pub fluid_color_shift: i32,
+    /// How far the top faces of the fluid sink, from 0 to 255.
+    pub fluid_displacement: i32,
+    /// How strongly the material glows, from 0 to 255.
+    pub emissive: i32,
+    /// How rough the surface is, from 0 to 255.
+    pub roughness: i32,
+    /// How metallic the surface is, from 0 to 255.
+    pub metalness: i32,
+    /// The texture set, 0 for none, otherwise the set number plus one.
+    pub texture: u32,
 }
 
 impl Material {
-    pub fn new(
-        color: [f32; 4],
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
-        fluid: i32,
-    ) -> Self {
+    pub fn new(color: [f32; 4], pattern: &PatternSettings, fluid: i32, emissive: f32) -> Self {
         Material {
             color: [
                 Material::downscale_color(color[0]),
@@ -26,14 +44,49 @@ impl Material {
                 Material::downscale_color(color[2]),
                 Material::downscale_color(color[3]),
             ],
-            noise,
-            noise_x,
-            noise_y,
-            noise_z,
+            pattern: pattern.pattern,
+            pattern_scale: (pattern.scale * 100.0).round() as i32,
+            pattern_contrast: Material::downscale_color(pattern.contrast),
+            secondary_color: [
+                Material::downscale_color(pattern.secondary_color[0]),
+                Material::downscale_color(pattern.secondary_color[1]),
+                Material::downscale_color(pattern.secondary_color[2]),
+            ],
+            pattern_shade: pattern.shade,
             fluid,
+            fluid_speed: 100,
+            fluid_wave_scale: 400,
+            fluid_color_shift: 0,
+            fluid_displacement: 0,
+            emissive: Material::downscale_color(emissive),
+            roughness: 255,
+            metalness: 0,
+            texture: 0,
         }
     }
 
+    /// Set how rough and metallic the surface is, both from 0 to 1.
+    pub fn with_surface(mut self, roughness: f32, metalness: f32) -> Self {
+        self.roughness = Material::downscale_color(roughness);
+        self.metalness = Material::downscale_color(metalness);
+        self
+    }
+
+    /// Set how the material moves when it is fluid.
+    pub fn with_fluid(mut self, fluid: &FluidSettings) -> Self {
+        self.fluid_speed = (fluid.speed * 10.0).round() as i32;
+        self.fluid_wave_scale = (fluid.wave_scale * 100.0).round() as i32;
+        self.fluid_color_shift = Material::downscale_color(fluid.color_shift);
+        self.fluid_displacement = Material::downscale_color(fluid.displacement);
+        self
+    }
+
+    /// Set which texture set is drawn on the faces, 0 for none.
+    pub fn with_texture(mut self, texture: u32) -> Self {
+        self.texture = texture;
+        self
+    }
+
     pub fn downscale_color(color: f32) -> i32 {
         (color * 255.0) as i32
     }
@@ -46,4 +99,44 @@ impl Material {
             self.color[3] as f32 / 255.0,
         ]
     }
+
+    /// The pattern settings at full precision.
+    pub fn pattern_settings(&self) -> PatternSettings {
+        PatternSettings {
+            pattern: self.pattern,
+            scale: self.pattern_scale as f32 / 100.0,
+            contrast: self.pattern_contrast as f32 / 255.0,
+            secondary_color: [
+                self.secondary_color[0] as f32 / 255.0,
+                self.secondary_color[1] as f32 / 255.0,
+                self.secondary_color[2] as f32 / 255.0,
+            ],
+            shade: self.pattern_shade,
+        }
+    }
+
+    /// The fluid settings at full precision.
+    pub fn fluid_settings(&self) -> FluidSettings {
+        FluidSettings {
+            speed: self.fluid_speed as f32 / 10.0,
+            wave_scale: self.fluid_wave_scale as f32 / 100.0,
+            color_shift: self.fluid_color_shift as f32 / 255.0,
+            displacement: self.fluid_displacement as f32 / 255.0,
+        }
+    }
+
+    /// The glow strength from 0 to 1.
+    pub fn upscale_emissive(&self) -> f32 {
+        self.emissive as f32 / 255.0
+    }
+
+    /// The roughness from 0 to 1.
+    pub fn upscale_roughness(&self) -> f32 {
+        self.roughness as f32 / 255.0
+    }
+
+    /// The metalness from 0 to 1.
+    pub fn upscale_metalness(&self) -> f32 {
+        self.metalness as f32 / 255.0
+    }
 }
@@ -0,0 +1,246 @@
+use crate::color_picker::parse_hex;
+use crate::material::Material;
+use crate::pattern::PatternSettings;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The file in the settings folder the palette is kept in between sessions.
+pub const PALETTE_FILE: &str = "palette.json";
+
+/// How wide each swatch is in an exported PNG strip, in pixels.
+const STRIP_SWATCH_SIZE: u32 = 16;
+
+/// The file types a palette can be imported from or exported to.
+pub const PALETTE_EXTENSIONS: [&str; 4] = ["json", "gpl", "hex", "png"];
+
+/// Materials kept to paint with again. Only JSON palettes keep the whole material;
+/// the other formats only hold colors.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MaterialPalette {
+    pub materials: Vec<Material>,
+}
+
+impl MaterialPalette {
+    pub const fn new() -> MaterialPalette {
+        MaterialPalette {
+            materials: Vec::new(),
+        }
+    }
+
+    /// Read a palette saved with save, if there is one.
+    pub fn load(path: &str) -> Option<MaterialPalette> {
+        let text = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&text)
+            .map_err(|error| log::error!("Could not read the palette in {path}: {error}"))
+            .ok()
+    }
+
+    /// Keep the palette for the next session.
+    pub fn save(&self, path: &str) {
+        let json_string =
+            serde_json::to_string_pretty(self).expect("Failed to serialize the palette");
+        if let Err(error) = fs::write(path, json_string) {
+            log::error!("Could not write the palette to {path}: {error}");
+        }
+    }
+
+    /// Read a palette from a JSON, GIMP (.gpl), hex or PNG strip file, chosen by the
+    /// file extension.
+    pub fn import(path: &str) -> Result<MaterialPalette, String> {
+        let extension = extension(path);
+        if extension == "png" {
+            let image = image::open(path).map_err(|error| error.to_string())?;
+            return Ok(MaterialPalette::with_colors(strip_colors(
+                &image.to_rgba8(),
+            )));
+        }
+        let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        match extension.as_str() {
+            "json" => serde_json::from_str(&text).map_err(|error| error.to_string()),
+            "gpl" => Ok(MaterialPalette::with_colors(gpl_colors(&text))),
+            "hex" => Ok(MaterialPalette::with_colors(hex_colors(&text))),
+            _ => Err(format!("Unknown palette type {extension}")),
+        }
+    }
+
+    /// Write the palette as JSON, GIMP (.gpl), hex or a PNG strip, chosen by the file
+    /// extension. Files without a known extension get JSON.
+    pub fn export(&self, path: &str) -> Result<(), String> {
+        let text = match extension(path).as_str() {
+            // An image cannot be empty, and a blank one would come back as a swatch.
+            "png" if self.materials.is_empty() => {
+                return Err(String::from(
+                    "An empty palette cannot be saved as a PNG strip",
+                ));
+            }
+            "png" => return self.strip().save(path).map_err(|error| error.to_string()),
+            "gpl" => self.gpl(),
+            "hex" => self.hex(),
+            _ => serde_json::to_string_pretty(self).map_err(|error| error.to_string())?,
+        };
+        fs::write(path, text).map_err(|error| error.to_string())
+    }
+
+    /// A plain material for each color.
+    fn with_colors(colors: Vec<[f32; 4]>) -> MaterialPalette {
+        MaterialPalette {
+            materials: colors
+                .iter()
+                .map(|color| Material::new(*color, &PatternSettings::new(), 0, 0.0))
+                .collect(),
+        }
+    }
+
+    /// The palette in the GIMP palette format, named after the patterns.
+    fn gpl(&self) -> String {
+        let mut text = String::from("GIMP Palette\nName: Crafter\nColumns: 8\n#\n");
+        for material in &self.materials {
+            let [red, green, blue, _] = material.color;
+            text.push_str(&format!(
+                "{red:3} {green:3} {blue:3}\t{:?}\n",
+                material.pattern
+            ));
+        }
+        text
+    }
+
+    /// One hex code per line, with alpha only where it is not opaque.
+    fn hex(&self) -> String {
+        let mut text = String::new();
+        for material in &self.materials {
+            let [red, green, blue, alpha] = material.color;
+            text.push_str(&format!("{red:02x}{green:02x}{blue:02x}"));
+            if alpha != 255 {
+                text.push_str(&format!("{alpha:02x}"));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// A row of squares, one per swatch.
+    fn strip(&self) -> RgbaImage {
+        let width = self.materials.len() as u32 * STRIP_SWATCH_SIZE;
+        let mut image = RgbaImage::new(width, STRIP_SWATCH_SIZE);
+        for (index, material) in self.materials.iter().enumerate() {
+            let pixel = Rgba(material.color.map(|value| value.clamp(0, 255) as u8));
+            for x in 0..STRIP_SWATCH_SIZE {
+                for y in 0..STRIP_SWATCH_SIZE {
+                    image.put_pixel(index as u32 * STRIP_SWATCH_SIZE + x, y, pixel);
+                }
+            }
+        }
+        image
+    }
+}
+
+/// The lower case extension of a file name.
+fn extension(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// The colors in a GIMP palette. The header, comments and names are skipped.
+fn gpl_colors(text: &str) -> Vec<[f32; 4]> {
+    text.lines()
+        .filter_map(|line| {
+            let channels: Vec<u8> = line
+                .split_whitespace()
+                .take(3)
+                .map_while(|part| part.parse().ok())
+                .collect();
+            match channels[..] {
+                [red, green, blue] => {
+                    Some([red, green, blue, 255].map(|value| value as f32 / 255.0))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The colors in a file with a hex code on each line.
+fn hex_colors(text: &str) -> Vec<[f32; 4]> {
+    text.lines()
+        .filter_map(|line| parse_hex(line.trim(), 1.0))
+        .collect()
+}
+
+/// The colors across the middle row of an image, one per run of matching pixels, so
+/// strips with a pixel or a block per color both work.
+fn strip_colors(image: &RgbaImage) -> Vec<[f32; 4]> {
+    let mut colors: Vec<[f32; 4]> = Vec::new();
+    let mut last = None;
+    for x in 0..image.width() {
+        let pixel = image.get_pixel(x, image.height() / 2).0;
+        if last != Some(pixel) {
+            colors.push(pixel.map(|value| value as f32 / 255.0));
+            last = Some(pixel);
+        }
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A palette of plain colors, each one different from the one before.
+    fn palette() -> MaterialPalette {
+        MaterialPalette::with_colors(vec![
+            [1.0, 0.0, 0.0, 1.0],
+            [0.2, 0.4, 0.6, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0],
+        ])
+    }
+
+    #[test]
+    fn hex_keeps_the_colors() {
+        let palette = palette();
+
+        assert_eq!(
+            MaterialPalette::with_colors(hex_colors(&palette.hex())),
+            palette
+        );
+    }
+
+    #[test]
+    fn hex_keeps_alpha_where_it_is_not_opaque() {
+        let palette = MaterialPalette::with_colors(vec![[0.2, 0.4, 0.6, 0.2]]);
+
+        assert_eq!(
+            MaterialPalette::with_colors(hex_colors(&palette.hex())),
+            palette
+        );
+    }
+
+    #[test]
+    fn gpl_keeps_the_colors() {
+        let palette = palette();
+
+        assert_eq!(
+            MaterialPalette::with_colors(gpl_colors(&palette.gpl())),
+            palette
+        );
+    }
+
+    #[test]
+    fn png_strip_keeps_the_colors() {
+        let palette = palette();
+
+        assert_eq!(
+            MaterialPalette::with_colors(strip_colors(&palette.strip())),
+            palette
+        );
+    }
+
+    #[test]
+    fn empty_palettes_are_not_saved_as_png() {
+        assert!(MaterialPalette::new().export("empty.png").is_err());
+    }
+}
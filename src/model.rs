@@ -1,6 +1,12 @@
+use crate::atmosphere::Atmosphere;
+use crate::bookmark::Bookmark;
 use crate::cube::Cube;
+use crate::lighting::Lighting;
+use crate::material::Material;
+use crate::material_palette::MaterialPalette;
 use crate::octree::Octree;
 use crate::storage::Storage;
+use crate::texture_atlas::TextureSet;
 use nalgebra::Point3;
 
 /// A model contains an Octree of voxels.
@@ -22,32 +28,34 @@ impl Model {
         self.voxels.drawables()
     }
 
+    /// Paint the voxels connected to the first one along a line, returning the
+    /// materials they had.
     pub fn paint_first_collision(
         &mut self,
         near: Point3<f32>,
         far: Point3<f32>,
-        material_color: [f32; 4],
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
-        fluid: i32,
-    ) {
-        self.voxels.paint_first_collision(
-            near,
-            far,
-            material_color,
-            noise,
-            noise_x,
-            noise_y,
-            noise_z,
-            fluid,
-        );
+        material: &Material,
+    ) -> Vec<Material> {
+        self.voxels.paint_first_collision(near, far, material)
+    }
+
+    /// The color of the first voxel along a line, if it hits one.
+    pub fn color_at_first_collision(
+        &self,
+        near: Point3<f32>,
+        far: Point3<f32>,
+    ) -> Option<[f32; 4]> {
+        self.voxels.color_at_first_collision(near, far)
+    }
+
+    /// Call optimize on the nested OcNodes, returning true if the level of detail changed.
+    pub fn optimize(&mut self, camera_eyes: &[[f32; 3]]) -> bool {
+        self.voxels.optimize(camera_eyes)
     }
 
-    /// Call optimize on the nested OcNodes
-    pub fn optimize(&mut self, camera_eye: [f32; 3]) {
-        self.voxels.optimize(camera_eye);
+    /// Change how far away voxels start being merged for level of detail.
+    pub fn set_lod_distance(&mut self, lod_distance: f32) {
+        self.voxels.set_lod_distance(lod_distance);
     }
 
     pub fn recalculate_occlusion(&mut self) {
@@ -58,26 +66,51 @@ impl Model {
         self.voxels.recalculate_occlusion_for_selections(selections);
     }
 
+    /// How many voxels are filled in.
+    pub fn voxel_count(&self) -> usize {
+        self.voxels.active_count()
+    }
+
+    /// How many voxels use each material, most used first.
+    pub fn material_usage(&self) -> Vec<(Material, usize)> {
+        self.voxels.material_usage()
+    }
+
+    /// The materials of the filled voxels in the list.
+    pub fn filled_materials(&self, positions: &[[i32; 3]]) -> Vec<Material> {
+        self.voxels.filled_materials(positions)
+    }
+
+    /// The positions of the voxels painted with a material.
+    pub fn voxels_with_material(&self, material: &Material) -> Vec<[i32; 3]> {
+        self.voxels.voxels_with_material(material)
+    }
+
+    /// Paint the filled voxels in the list, leaving empty ones empty.
+    pub fn paint_voxels(
+        &mut self,
+        positions: &[[i32; 3]],
+        material: &Material,
+        camera_eyes: &[[f32; 3]],
+    ) {
+        self.voxels.paint_voxels(positions, material, camera_eyes);
+    }
+
     /// Initialise
     pub fn init(&mut self) {
         self.voxels.init();
     }
 
+    /// Fill or empty the voxels in the list, returning how many of them changed.
     pub fn toggle_voxels(
         &mut self,
         positions: Vec<[i32; 3]>,
         value: bool,
-        color: [f32; 4],
-        camera_eye: [f32; 3],
-        fluid: i32,
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
-    ) {
-        self.voxels.toggle_voxels(
-            positions, value, color, camera_eye, fluid, noise, noise_x, noise_y, noise_z,
-        );
+        material: &Material,
+        camera_eyes: &[[f32; 3]],
+    ) -> usize {
+        self.voxels
+            .toggle_voxels(positions, value, material, camera_eyes)
     }
 
     /// Determine if all voxels in the list are active.
@@ -85,19 +118,49 @@ impl Model {
         self.voxels.all_voxels_active(positions)
     }
 
-    /// Save a scene to browser indexeddb
-    pub fn save(&self, path: &str) {
+    /// Save a scene to disk along with the scene lighting.
+    pub fn save(
+        &self,
+        path: &str,
+        lighting: &Lighting,
+        atmosphere: &Atmosphere,
+        textures: &[TextureSet],
+        bookmarks: &[Bookmark],
+        palette: &MaterialPalette,
+    ) {
         let storage = Storage::new(path);
 
-        let serial = self.voxels.prepare();
+        let mut serial = self.voxels.prepare();
+        serial.lighting = *lighting;
+        serial.atmosphere = atmosphere.clone();
+        serial.textures = textures.to_vec();
+        serial.bookmarks = bookmarks.to_vec();
+        serial.palette = palette.clone();
         _ = storage.save(serial);
     }
 
-    /// Save a scene to browser indexeddb
-    pub fn load(&mut self, path: &str, camera_eye: [f32; 3]) {
+    /// Load a scene from disk and return the lighting, atmosphere, textures, bookmarks
+    /// and palette saved with it.
+    pub fn load(
+        &mut self,
+        path: &str,
+        camera_eyes: &[[f32; 3]],
+    ) -> (
+        Lighting,
+        Atmosphere,
+        Vec<TextureSet>,
+        Vec<Bookmark>,
+        MaterialPalette,
+    ) {
         let storage = Storage::new(path);
 
         let loaded = storage.load_first_scene().unwrap();
-        self.voxels.load_from_serial(loaded, camera_eye);
+        let lighting = loaded.lighting;
+        let atmosphere = loaded.atmosphere.clone();
+        let textures = loaded.textures.clone();
+        let bookmarks = loaded.bookmarks.clone();
+        let palette = loaded.palette.clone();
+        self.voxels.load_from_serial(loaded, camera_eyes);
+        (lighting, atmosphere, textures, bookmarks, palette)
     }
 }
@@ -6,6 +6,10 @@ pub struct Mouse {
     pub last_position: Point2<f32>,
     /// The button state of the mouse.
     pub is_pressed: bool,
+    /// Is the middle button held down for panning?
+    pub middle_pressed: bool,
+    /// Is the pointer over the UI, so presses and drags there leave the scene alone?
+    pub over_ui: bool,
 }
 
 impl Mouse {
@@ -14,6 +18,8 @@ impl Mouse {
         Mouse {
             last_position: Point2::new(0.0, 0.0),
             is_pressed: false,
+            middle_pressed: false,
+            over_ui: false,
         }
     }
 }
@@ -1,6 +1,10 @@
+use crate::fluid::FluidSettings;
+use crate::material::Material;
+use crate::pattern::{Pattern, PatternSettings};
 use crate::{cube::Cube, drawable::Drawable};
 use nalgebra::Point3;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Helper function to create an empty list.
 /// The scope is odd.
@@ -8,6 +12,11 @@ fn empty_list() -> [Option<Box<Ocnode>>; 8] {
     [None, None, None, None, None, None, None, None]
 }
 
+/// Nodes saved before roughness existed should stay matte.
+fn default_roughness() -> f32 {
+    1.0
+}
+
 pub const LEVELS: u32 = 9;
 
 /// A struct representing a single cube for the octree.
@@ -34,24 +43,49 @@ pub struct Ocnode {
     children: [Option<Box<Self>>; 8],
     /// Does this cube contain smaller ones?
     has_children: bool,
+    /// Draw this cube as one merged cube because it is far from the camera.
+    #[serde(skip)]
+    lod: bool,
     /// The color of the cube including alpha channel.
     color: [f32; 4],
     /// Render this node with fluid animation.
     fluid: i32,
-    /// Render this node with a noisy texture.
+    /// How the fluid animation moves.
+    #[serde(default)]
+    fluid_settings: FluidSettings,
+    /// The procedural pattern painted on this node.
+    #[serde(default)]
+    pattern: PatternSettings,
+    /// Noise flags from scenes saved before patterns existed. Only read when loading.
+    #[serde(default, skip_serializing)]
     noise: i32,
-    /// Render this node with a noisy texture in the x direction.
+    #[serde(default, skip_serializing)]
     noise_x: i32,
-    /// Render this node with a noisy texture in the y direction.
+    #[serde(default, skip_serializing)]
     noise_y: i32,
-    /// Render this node with a noisy texture in the z direction.
+    #[serde(default, skip_serializing)]
     noise_z: i32,
+    /// How strongly this node glows, from 0 to 1.
+    #[serde(default)]
+    emissive: f32,
+    /// How rough the surface is, from 0 (mirror like) to 1 (matte).
+    #[serde(default = "default_roughness")]
+    roughness: f32,
+    /// How metallic the surface is, from 0 to 1.
+    #[serde(default)]
+    metalness: f32,
+    /// The texture set, 0 for none, otherwise the set number plus one.
+    #[serde(default)]
+    texture: u32,
     pub front_occluded_calculated: bool,
     pub back_occluded_calculated: bool,
     pub top_occluded_calculated: bool,
     pub bottom_occluded_calculated: bool,
     pub left_occluded_calculated: bool,
     pub right_occluded_calculated: bool,
+    /// Bit mask of the active nodes in the 3x3x3 block around this one, used for ambient occlusion.
+    #[serde(default)]
+    pub neighbours_calculated: u32,
 }
 
 impl Ocnode {
@@ -65,18 +99,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: [0.8, 0.8, 0.8, 0.8],
             fluid: 0,
+            fluid_settings: FluidSettings::new(),
+            pattern: PatternSettings::new(),
             noise: 0,
             noise_x: 0,
             noise_y: 0,
             noise_z: 0,
+            emissive: 0.0,
+            roughness: 1.0,
+            metalness: 0.0,
+            texture: 0,
             front_occluded_calculated: false,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
+            neighbours_calculated: 0,
         }
     }
 
@@ -172,39 +214,29 @@ impl Ocnode {
         dx * dx + dy * dy + dz * dz
     }
 
+    /// Paint the cube and the cubes connected to it, returning the materials they had.
     pub fn paint_connected_nodes(
         &mut self,
         collision: (i32, i32, i32, u32),
-        material_color: [f32; 4],
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
-        fluid: i32,
-    ) {
+        material: &Material,
+    ) -> Vec<Material> {
         let mut completed = Vec::new();
+        let mut replaced = Vec::new();
         self.paint_connected_nodes_with_completion(
             collision,
-            material_color,
-            noise,
-            noise_x,
-            noise_y,
-            noise_z,
-            fluid,
+            material,
             completed.as_mut(),
+            &mut replaced,
         );
+        replaced
     }
 
     pub fn paint_connected_nodes_with_completion(
         &mut self,
         collision: (i32, i32, i32, u32),
-        material_color: [f32; 4],
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
-        fluid: i32,
+        material: &Material,
         completed: &mut Vec<(i32, i32, i32, u32)>,
+        replaced: &mut Vec<Material>,
     ) {
         let (x, y, z, level) = collision;
         let candidate_opt = self.find_mut_by_index(x, y, z, level);
@@ -219,12 +251,10 @@ impl Ocnode {
         if let Some(candidate) = candidate_opt {
             println!("Push completion vector");
             completed.push((x, y, z, level));
-            candidate.color = material_color;
-            candidate.noise = noise;
-            candidate.noise_x = noise_x;
-            candidate.noise_y = noise_y;
-            candidate.noise_z = noise_z;
-            candidate.fluid = fluid;
+            if candidate.active {
+                replaced.push(candidate.material());
+            }
+            candidate.apply_material(material);
             left_occluded = candidate.left_occluded_calculated;
             right_occluded = candidate.right_occluded_calculated;
             top_occluded = candidate.top_occluded_calculated;
@@ -240,13 +270,9 @@ impl Ocnode {
             if !completed.contains(&(x - 1, y, z, level)) {
                 self.paint_connected_nodes_with_completion(
                     (x - 1, y, z, level),
-                    material_color,
-                    noise,
-                    noise_x,
-                    noise_y,
-                    noise_z,
-                    fluid,
+                    material,
                     completed,
+                    replaced,
                 );
             }
         }
@@ -254,13 +280,9 @@ impl Ocnode {
             if !completed.contains(&(x + 1, y, z, level)) {
                 self.paint_connected_nodes_with_completion(
                     (x + 1, y, z, level),
-                    material_color,
-                    noise,
-                    noise_x,
-                    noise_y,
-                    noise_z,
-                    fluid,
+                    material,
                     completed,
+                    replaced,
                 );
             }
         }
@@ -268,13 +290,9 @@ impl Ocnode {
             if !completed.contains(&(x, y + 1, z, level)) {
                 self.paint_connected_nodes_with_completion(
                     (x, y + 1, z, level),
-                    material_color,
-                    noise,
-                    noise_x,
-                    noise_y,
-                    noise_z,
-                    fluid,
+                    material,
                     completed,
+                    replaced,
                 );
             }
         }
@@ -282,13 +300,9 @@ impl Ocnode {
             if !completed.contains(&(x, y - 1, z, level)) {
                 self.paint_connected_nodes_with_completion(
                     (x, y - 1, z, level),
-                    material_color,
-                    noise,
-                    noise_x,
-                    noise_y,
-                    noise_z,
-                    fluid,
+                    material,
                     completed,
+                    replaced,
                 );
             }
         }
@@ -297,13 +311,9 @@ impl Ocnode {
                 println!("Move forward");
                 self.paint_connected_nodes_with_completion(
                     (x, y, z - 1, level),
-                    material_color,
-                    noise,
-                    noise_x,
-                    noise_y,
-                    noise_z,
-                    fluid,
+                    material,
                     completed,
+                    replaced,
                 );
             }
         }
@@ -312,13 +322,9 @@ impl Ocnode {
                 println!("Move backwards");
                 self.paint_connected_nodes_with_completion(
                     (x, y, z + 1, level),
-                    material_color,
-                    noise,
-                    noise_x,
-                    noise_y,
-                    noise_z,
-                    fluid,
+                    material,
                     completed,
+                    replaced,
                 );
             }
         }
@@ -393,20 +399,24 @@ impl Ocnode {
     pub fn uniform(&self, compare: &Ocnode) -> bool {
         let compare_color = compare.color;
         let compare_fluid = compare.fluid;
-        let compare_noise = compare.noise;
-        let compare_noise_x = compare.noise_x;
-        let compare_noise_y = compare.noise_y;
-        let compare_noise_z = compare.noise_z;
+        let compare_fluid_settings = compare.fluid_settings;
+        let compare_pattern = compare.pattern;
+        let compare_emissive = compare.emissive;
+        let compare_roughness = compare.roughness;
+        let compare_metalness = compare.metalness;
+        let compare_texture = compare.texture;
 
         !(compare_color[0] != self.color[0]
             || compare_color[1] != self.color[1]
             || compare_color[2] != self.color[2]
             || compare_color[3] != self.color[3]
             || compare_fluid != self.fluid
-            || compare_noise != self.noise
-            || compare_noise_x != self.noise_x
-            || compare_noise_y != self.noise_y
-            || compare_noise_z != self.noise_z)
+            || compare_fluid_settings != self.fluid_settings
+            || compare_pattern != self.pattern
+            || compare_emissive != self.emissive
+            || compare_roughness != self.roughness
+            || compare_metalness != self.metalness
+            || compare_texture != self.texture)
     }
 
     pub fn bottom_occluded(&self, root: &Ocnode) -> bool {
@@ -517,6 +527,37 @@ impl Ocnode {
         false
     }
 
+    /// Find which of the 26 surrounding nodes at the same level are active.
+    /// Each offset from -1 to 1 on every axis maps to one bit, the center bit is this node.
+    pub fn neighbours(&self, root: &Ocnode) -> u32 {
+        let step = self.resolution(self.sub_division_level) as i32;
+        let mut mask = 0;
+
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let bit = (dx + 1) + (dy + 1) * 3 + (dz + 1) * 9;
+                    let active = if dx == 0 && dy == 0 && dz == 0 {
+                        self.active
+                    } else {
+                        root.find_by_index(
+                            self.x_index + dx * step,
+                            self.y_index + dy * step,
+                            self.z_index + dz * step,
+                            self.sub_division_level,
+                        )
+                        .is_some_and(|neighbour| neighbour.active)
+                    };
+                    if active {
+                        mask |= 1 << bit;
+                    }
+                }
+            }
+        }
+
+        mask
+    }
+
     pub fn find_mut_by_index(&mut self, x: i32, y: i32, z: i32, level: u32) -> Option<&mut Ocnode> {
         if level == self.sub_division_level {
             if self.x_index == x && self.y_index == y && self.z_index == z {
@@ -591,6 +632,51 @@ impl Ocnode {
         found
     }
 
+    /// The color of the cube including alpha channel.
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    /// The material the cube is painted with.
+    pub fn material(&self) -> Material {
+        Material::new(self.color, &self.pattern, self.fluid, self.emissive)
+            .with_surface(self.roughness, self.metalness)
+            .with_texture(self.texture)
+            .with_fluid(&self.fluid_settings)
+    }
+
+    /// Count how many active cubes use each material, including all it's children.
+    pub fn count_materials(&self, counts: &mut HashMap<Material, usize>) {
+        if self.active {
+            *counts.entry(self.material()).or_default() += 1;
+        }
+        for child in self.children.iter().flatten() {
+            child.count_materials(counts);
+        }
+    }
+
+    /// Collect the positions of the active cubes painted with a material, including
+    /// all it's children.
+    pub fn find_material(&self, material: &Material, found: &mut Vec<[i32; 3]>) {
+        if self.active && self.material() == *material {
+            found.push([self.x_index, self.y_index, self.z_index]);
+        }
+        for child in self.children.iter().flatten() {
+            child.find_material(material, found);
+        }
+    }
+
+    /// Count the active cubes including this one and all it's children.
+    pub fn active_count(&self) -> usize {
+        let own = if self.active { 1 } else { 0 };
+        own + self
+            .children
+            .iter()
+            .flatten()
+            .map(|child| child.active_count())
+            .sum::<usize>()
+    }
+
     /// Set this cube and all it's children to hidden.
     pub fn clear(&mut self) {
         self.active = false;
@@ -622,19 +708,44 @@ impl Ocnode {
             found.active = node.active;
             found.color = node.color;
             found.fluid = node.fluid;
-            found.noise = node.noise;
-            found.noise_x = node.noise_x;
-            found.noise_y = node.noise_y;
-            found.noise_z = node.noise_z;
+            found.fluid_settings = node.fluid_settings;
+            found.pattern = node.pattern;
+            // Only scenes saved before patterns existed set the noise flags.
+            let noise = node.noise | node.noise_x | node.noise_y | node.noise_z;
+            if node.pattern.pattern == Pattern::Solid && noise != 0 {
+                found.pattern = PatternSettings::from_noise_flags(
+                    node.noise,
+                    node.noise_x,
+                    node.noise_y,
+                    node.noise_z,
+                );
+            }
+            found.emissive = node.emissive;
+            found.roughness = node.roughness;
+            found.metalness = node.metalness;
+            found.texture = node.texture;
             found.back_occluded_calculated = node.back_occluded_calculated;
             found.front_occluded_calculated = node.front_occluded_calculated;
             found.top_occluded_calculated = node.top_occluded_calculated;
             found.bottom_occluded_calculated = node.bottom_occluded_calculated;
             found.left_occluded_calculated = node.left_occluded_calculated;
             found.right_occluded_calculated = node.right_occluded_calculated;
+            found.neighbours_calculated = node.neighbours_calculated;
         }
     }
 
+    /// Copy the render settings from a material onto this node.
+    pub fn apply_material(&mut self, material: &Material) {
+        self.color = material.upscale_color();
+        self.fluid = material.fluid;
+        self.fluid_settings = material.fluid_settings();
+        self.pattern = material.pattern_settings();
+        self.emissive = material.upscale_emissive();
+        self.roughness = material.upscale_roughness();
+        self.metalness = material.upscale_metalness();
+        self.texture = material.texture;
+    }
+
     /// Determine the distance between this cube and the camera.
     fn _depth(&self, camera: [f32; 3]) -> f32 {
         let half = self.resolution(self.sub_division_level) as f32 / 2.0;
@@ -644,69 +755,116 @@ impl Ocnode {
         .sqrt()
     }
 
-    /// Set the active state to match the combined active state of all children.
-    pub fn optimize(&mut self, _camera_eye: [f32; 3]) {
+    /// Find the distance from a point to the closest part of this cube.
+    fn nearest_distance(&self, point: [f32; 3]) -> f32 {
+        let size = self.resolution(self.sub_division_level) as f32;
+        let indexes = [self.x_index, self.y_index, self.z_index];
+        indexes
+            .iter()
+            .zip(point)
+            .map(|(index, value)| {
+                let low = *index as f32;
+                let gap = (low - value).max(value - (low + size)).max(0.0);
+                gap * gap
+            })
+            .sum::<f32>()
+            .sqrt()
+    }
 
-        /*if self.has_children {
-            // Optimize leaf first then move up the tree.
-            let squirts = self.children.each_mut();
-            for child in squirts {
-                match child {
-                    None => {}
-                    Some(down) => {
-                        down.optimize(camera_eye);
-                    }
-                }
+    /// Mark distant cubes to be drawn as one merged cube instead of all their children.
+    /// A cube is merged once it is further away from every camera than lod_distance times
+    /// half its width, so nothing closer than lod_distance is ever merged. Zero turns this off.
+    /// Cubes that are mostly empty are left to their children so they do not turn solid.
+    /// Returns true if any cube changed between merged and not merged.
+    pub fn optimize(&mut self, camera_eyes: &[[f32; 3]], lod_distance: f32) -> bool {
+        let was_merged = self.lod;
+        self.lod = false;
+        if !self.has_children {
+            return was_merged;
+        }
+        if lod_distance > 0.0 {
+            let size = self.resolution(self.sub_division_level) as f32;
+            let distance = camera_eyes
+                .iter()
+                .map(|eye| self.nearest_distance(*eye))
+                .fold(f32::INFINITY, f32::min);
+            if distance > lod_distance * size / 2.0 && self.mostly_filled() {
+                self.lod = true;
+                return !was_merged;
             }
-            let squirts = self.children.each_mut();
-            let has_peg = squirts
-                .into_iter()
-                .any(|child| child.as_ref().expect("child").active);
+        }
+        let mut changed = was_merged;
+        for child in self.children.iter_mut().flatten() {
+            changed |= child.optimize(camera_eyes, lod_distance);
+        }
+        changed
+    }
 
-            let squirts = self.children.each_mut();
-            let has_hole = squirts
-                .into_iter()
-                .any(|child| !child.as_ref().expect("child").active);
-            //let squirts = self.children.each_mut();
-            let mut color = [0.0, 0.0, 0.0, 0.0];
-            let mut fluid = 0;
-            let mut noise = 0;
-            for i in self.children.each_mut() {
-                let node = i.as_ref().expect("child");
-                if node.active {
-                    color = node.color;
-                    fluid = node.fluid;
-                    noise = node.noise;
-                }
-            }
+    /// Is at least half of the space inside this cube filled?
+    fn mostly_filled(&self) -> bool {
+        let size = self.resolution(self.sub_division_level) as u64;
+        self.filled_volume() * 2 >= size * size * size
+    }
 
-            let squirts = self.children.each_mut();
-            let not_uniform_color = squirts.into_iter().any(|child| {
-                let compare = child.as_ref().expect("child").color;
-                let compare_fluid = child.as_ref().expect("child").fluid;
-                let compare_noise = child.as_ref().expect("child").noise;
-                compare[0] != color[0]
-                    || compare[1] != color[1]
-                    || compare[2] != color[2]
-                    || compare[3] != color[3]
-                    || compare_fluid != fluid
-                    || compare_noise != noise
-            });
-
-            let res = LEVELS.checked_sub(self.sub_division_level).expect("");
-            let depth = self.depth(camera_eye) / res as f32;
-            let lod = 60.0;
-
-            self.active = (has_peg && (depth > lod)) || (!has_hole && !not_uniform_color);
-            for i in self.children.each_mut() {
-                let node = i.as_ref().expect("child");
-                if node.active {
-                    self.color = node.color;
-                    self.fluid = node.fluid;
-                    self.noise = node.noise;
-                }
+    /// Count the unit voxels drawn inside this cube.
+    fn filled_volume(&self) -> u64 {
+        if self.active {
+            let size = self.resolution(self.sub_division_level) as u64;
+            return size * size * size;
+        }
+        self.children
+            .iter()
+            .flatten()
+            .map(|child| child.filled_volume())
+            .sum()
+    }
+
+    /// Collect this cube and all the active cubes inside it.
+    fn collect_active<'a>(&'a self, found: &mut Vec<&'a Ocnode>) {
+        if self.active {
+            found.push(self);
+        }
+        for child in self.children.iter().flatten() {
+            child.collect_active(found);
+        }
+    }
+
+    /// Draw this cube as a single cube with the average color of the active cubes inside it.
+    /// The rest of the material comes from the first active cube.
+    fn merged_drawables(&self) -> Vec<Cube> {
+        let mut found = vec![];
+        self.collect_active(&mut found);
+        let Some(first) = found.first() else {
+            return vec![];
+        };
+
+        let mut color = [0.0; 4];
+        for node in found.iter() {
+            for (total, value) in color.iter_mut().zip(node.color) {
+                *total += value;
             }
-        }*/
+        }
+        let count = found.len() as f32;
+
+        let mut cube = Cube::new();
+        cube.color = color.map(|total| total / count);
+        cube.fluid = first.fluid;
+        cube.fluid_settings = first.fluid_settings;
+        cube.pattern = first.pattern;
+        cube.emissive = first.emissive;
+        cube.roughness = first.roughness;
+        cube.metalness = first.metalness;
+        cube.texture = first.texture;
+        cube.scale = self.resolution(self.sub_division_level) as f32;
+        cube.smooth = true;
+        cube.init();
+        cube.translate([
+            self.x_index as f32,
+            self.y_index as f32,
+            self.z_index as f32,
+        ]);
+
+        vec![cube]
     }
 
     /// Are all the nodes in the list of nodes active?
@@ -725,47 +883,59 @@ impl Ocnode {
         true
     }
 
+    /// Fill or empty the voxels in the list, returning how many of them changed.
     pub fn toggle_voxels(
         &mut self,
         positions: &Vec<[i32; 3]>,
         value: bool,
-        color: [f32; 4],
-        fluid: i32,
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
-    ) {
+        material: &Material,
+    ) -> usize {
         println!("Toggle voxels in {:?}", positions.len());
+        let mut changed = 0;
         for position in positions {
             let maybe = self.find_mut_by_index(position[0], position[1], position[2], LEVELS);
             if maybe.is_some() {
                 let actual = maybe.unwrap();
+                if actual.active != value {
+                    changed += 1;
+                }
                 actual.active = value;
-                actual.color = color;
-                actual.fluid = fluid;
-                actual.noise = noise;
-                actual.noise_x = noise_x;
-                actual.noise_y = noise_y;
-                actual.noise_z = noise_z;
+                actual.apply_material(material);
             }
         }
         println!("Toggle voxels Done");
+        changed
+    }
+
+    /// Paint the active voxels in the list, leaving empty ones empty.
+    pub fn paint_voxels(&mut self, positions: &[[i32; 3]], material: &Material) {
+        for position in positions {
+            if let Some(actual) =
+                self.find_mut_by_index(position[0], position[1], position[2], LEVELS)
+                && actual.active
+            {
+                actual.apply_material(material);
+            }
+        }
     }
 
     /// Generate a list of drawables from the active cubes in this one.
     pub fn drawables(&mut self) -> Vec<Cube> {
         if self.has_children {
-            if self.active {
+            if self.lod {
+                self.merged_drawables()
+            } else if self.active {
                 let scale = self.resolution(self.sub_division_level) as f32;
                 let mut cube = Cube::new();
 
                 cube.color = self.color;
                 cube.fluid = self.fluid;
-                cube.noise = self.noise;
-                cube.noise_x = self.noise_x;
-                cube.noise_y = self.noise_y;
-                cube.noise_z = self.noise_z;
+                cube.fluid_settings = self.fluid_settings;
+                cube.pattern = self.pattern;
+                cube.emissive = self.emissive;
+                cube.roughness = self.roughness;
+                cube.metalness = self.metalness;
+                cube.texture = self.texture;
                 cube.scale = scale;
                 cube.smooth = true;
 
@@ -775,6 +945,7 @@ impl Ocnode {
                 cube.front_occluded = self.front_occluded_calculated;
                 cube.back_occluded = self.back_occluded_calculated;
                 cube.top_occluded = self.top_occluded_calculated;
+                cube.neighbours = self.neighbours_calculated;
                 cube.init();
 
                 let x = self.x_index as f32 * (1.0);
@@ -805,10 +976,12 @@ impl Ocnode {
 
             cube.color = self.color;
             cube.fluid = self.fluid;
-            cube.noise = self.noise;
-            cube.noise_x = self.noise_x;
-            cube.noise_y = self.noise_y;
-            cube.noise_z = self.noise_z;
+            cube.fluid_settings = self.fluid_settings;
+            cube.pattern = self.pattern;
+            cube.emissive = self.emissive;
+            cube.roughness = self.roughness;
+            cube.metalness = self.metalness;
+            cube.texture = self.texture;
             cube.scale = scale;
             cube.smooth = true;
 
@@ -818,6 +991,7 @@ impl Ocnode {
             cube.front_occluded = self.front_occluded_calculated;
             cube.back_occluded = self.back_occluded_calculated;
             cube.top_occluded = self.top_occluded_calculated;
+            cube.neighbours = self.neighbours_calculated;
             cube.init();
 
             let x = self.x_index as f32 * (scale);
@@ -840,6 +1014,7 @@ impl Ocnode {
             self.bottom_occluded_calculated = self.bottom_occluded(root);
             self.left_occluded_calculated = self.left_occluded(root);
             self.right_occluded_calculated = self.right_occluded(root);
+            self.neighbours_calculated = self.neighbours(root);
         }
         if self.has_children {
             let squirts = self.children.each_mut();
@@ -886,18 +1061,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
 
         self.children[1] = Some(Box::new(Ocnode {
@@ -908,18 +1091,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
         self.children[2] = Some(Box::new(Ocnode {
             x_index: self.x_index,
@@ -929,18 +1120,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
         self.children[3] = Some(Box::new(Ocnode {
             x_index: self.x_index,
@@ -950,18 +1149,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
         self.children[4] = Some(Box::new(Ocnode {
             x_index: self.x_index + self.resolution(self.sub_division_level + 1) as i32,
@@ -971,18 +1178,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
         self.children[5] = Some(Box::new(Ocnode {
             x_index: self.x_index,
@@ -992,18 +1207,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
         self.children[6] = Some(Box::new(Ocnode {
             x_index: self.x_index + self.resolution(self.sub_division_level + 1) as i32,
@@ -1013,18 +1236,26 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
         self.children[7] = Some(Box::new(Ocnode {
             x_index: self.x_index + self.resolution(self.sub_division_level + 1) as i32,
@@ -1034,18 +1265,136 @@ impl Ocnode {
             active: false,
             children: [None, None, None, None, None, None, None, None],
             has_children: false,
+            lod: false,
             color: self.color,
             fluid: self.fluid,
-            noise: self.noise,
-            noise_x: self.noise_x,
-            noise_y: self.noise_y,
-            noise_z: self.noise_z,
+            fluid_settings: self.fluid_settings,
+            pattern: self.pattern,
+            noise: 0,
+            noise_x: 0,
+            noise_y: 0,
+            noise_z: 0,
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metalness: self.metalness,
+            texture: self.texture,
             back_occluded_calculated: false,
             top_occluded_calculated: false,
             bottom_occluded_calculated: false,
             left_occluded_calculated: false,
             right_occluded_calculated: false,
             front_occluded_calculated: false,
+            neighbours_calculated: 0,
         }));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cube four voxels wide in the corner at the origin, with the listed voxels filled.
+    fn block(filled: &[[i32; 3]]) -> Ocnode {
+        let mut node = Ocnode::new();
+        node.x_index = 0;
+        node.y_index = 0;
+        node.z_index = 0;
+        node.sub_division_level = LEVELS - 2;
+        node.decimate(3);
+        for [x, y, z] in filled {
+            node.find_mut_by_index(*x, *y, *z, LEVELS)
+                .expect("voxel inside the block")
+                .active = true;
+        }
+        node
+    }
+
+    /// Every voxel position in the block.
+    fn all_voxels() -> Vec<[i32; 3]> {
+        let mut voxels = vec![];
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    voxels.push([x, y, z]);
+                }
+            }
+        }
+        voxels
+    }
+
+    /// The position, width and color of each drawable.
+    fn summary(node: &mut Ocnode) -> Vec<([f32; 3], f32, [f32; 4])> {
+        node.drawables()
+            .iter()
+            .map(|cube| (cube.translation, cube.scale, cube.color))
+            .collect()
+    }
+
+    #[test]
+    fn nothing_is_merged_inside_lod_distance() {
+        let filled: Vec<[i32; 3]> = all_voxels()
+            .into_iter()
+            .filter(|[x, y, z]| (x + y + z) % 3 != 0)
+            .collect();
+        let eye = [6.0, 2.0, 2.0];
+
+        let mut with_lod = block(&filled);
+        with_lod.optimize(&[eye], 10.0);
+        let mut without_lod = block(&filled);
+        without_lod.optimize(&[eye], 0.0);
+
+        assert_eq!(summary(&mut with_lod), summary(&mut without_lod));
+    }
+
+    #[test]
+    fn sparse_cubes_are_not_merged() {
+        let filled = [[0, 0, 0], [3, 3, 3]];
+        let eye = [500.0, 2.0, 2.0];
+
+        let mut with_lod = block(&filled);
+        with_lod.optimize(&[eye], 1.0);
+        let mut without_lod = block(&filled);
+        without_lod.optimize(&[eye], 0.0);
+
+        assert_eq!(summary(&mut with_lod), summary(&mut without_lod));
+    }
+
+    #[test]
+    fn distant_filled_cubes_are_merged_once() {
+        let mut node = block(&all_voxels());
+        let eye = [500.0, 2.0, 2.0];
+
+        assert!(node.optimize(&[eye], 1.0));
+        assert!(!node.optimize(&[eye], 1.0));
+        let drawables = summary(&mut node);
+        assert_eq!(drawables.len(), 1);
+        assert_eq!(drawables[0].1, 4.0);
+
+        assert!(node.optimize(&[eye], 0.0));
+        assert_eq!(summary(&mut node).len(), 64);
+    }
+
+    #[test]
+    fn cubes_near_any_camera_are_not_merged() {
+        let mut node = block(&all_voxels());
+
+        node.optimize(&[[500.0, 2.0, 2.0], [2.0, 2.0, 6.0]], 10.0);
+
+        assert_eq!(summary(&mut node).len(), 64);
+    }
+
+    #[test]
+    fn loaded_solid_cubes_keep_their_pattern() {
+        let saved = block(&[[1, 2, 3]]);
+        let mut loaded = block(&[]);
+        for node in saved.active_nodes() {
+            loaded.apply(&node);
+        }
+
+        let found = loaded
+            .find_by_index(1, 2, 3, LEVELS)
+            .expect("voxel inside the block");
+        assert!(found.active);
+        assert_eq!(found.pattern, PatternSettings::new());
+    }
+}
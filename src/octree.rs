@@ -1,15 +1,25 @@
+use crate::atmosphere::Atmosphere;
 use crate::cube::Cube;
+use crate::lighting::Lighting;
+use crate::material::Material;
+use crate::material_palette::MaterialPalette;
 use crate::ocnode::Ocnode;
 use crate::stored_octree::StoredOctree;
 use nalgebra::Point3;
+use std::collections::HashMap;
 
 pub const LEVELS: u32 = 9;
 
+/// Pairs of voxels start merging this far from the camera.
+pub const DEFAULT_LOD_DISTANCE: f32 = 80.0;
+
 /// An octree has a name and a tree of nodes.
 #[derive(Clone)]
 pub struct Octree {
     pub root: Ocnode,
     depth: u32,
+    /// How far away cubes start being merged for level of detail. Zero turns it off.
+    lod_distance: f32,
 }
 
 impl Octree {
@@ -18,6 +28,7 @@ impl Octree {
         Octree {
             root: Ocnode::new(),
             depth: 1,
+            lod_distance: DEFAULT_LOD_DISTANCE,
         }
     }
 
@@ -26,6 +37,38 @@ impl Octree {
         self.root.active_nodes()
     }
 
+    /// Count the active nodes without copying them.
+    pub fn active_count(&self) -> usize {
+        self.root.active_count()
+    }
+
+    /// How many active nodes use each material, most used first.
+    pub fn material_usage(&self) -> Vec<(Material, usize)> {
+        let mut counts = HashMap::new();
+        self.root.count_materials(&mut counts);
+        let mut usage: Vec<(Material, usize)> = counts.into_iter().collect();
+        // Break ties by color so the order does not change between scans.
+        usage.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.color.cmp(&b.0.color)));
+        usage
+    }
+
+    /// The materials of the active nodes in the list.
+    pub fn filled_materials(&self, positions: &[[i32; 3]]) -> Vec<Material> {
+        positions
+            .iter()
+            .filter_map(|[x, y, z]| self.root.find_by_index(*x, *y, *z, LEVELS))
+            .filter(|node| node.active)
+            .map(|node| node.material())
+            .collect()
+    }
+
+    /// The positions of the active nodes painted with a material.
+    pub fn voxels_with_material(&self, material: &Material) -> Vec<[i32; 3]> {
+        let mut found = Vec::new();
+        self.root.find_material(material, &mut found);
+        found
+    }
+
     /// Hide all nodes in the tree.
 
     pub fn recalculate_occlusion(&mut self) {
@@ -40,16 +83,15 @@ impl Octree {
             selections.len()
         );
 
-        let variations = vec![
-            [0, 0, 0],
-            [1, 0, 0],
-            [-1, 0, 0],
-            [0, 1, 0],
-            [0, -1, 0],
-            [0, 0, 1],
-            [0, 0, 1],
-            [0, 0, -1],
-        ];
+        // Ambient occlusion depends on the diagonal neighbours too, so visit the whole 3x3x3 block.
+        let mut variations = Vec::with_capacity(27);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    variations.push([x, y, z]);
+                }
+            }
+        }
 
         for position in selections {
             for variant in variations.iter() {
@@ -69,6 +111,7 @@ impl Octree {
                         let bottom_occluded_calculated = actual.bottom_occluded(&self.root);
                         let left_occluded_calculated = actual.left_occluded(&self.root);
                         let right_occluded_calculated = actual.right_occluded(&self.root);
+                        let neighbours_calculated = actual.neighbours(&self.root);
 
                         let maybe_mut = self
                             .root
@@ -85,6 +128,7 @@ impl Octree {
                         maybe_mut.bottom_occluded_calculated = bottom_occluded_calculated;
                         maybe_mut.left_occluded_calculated = left_occluded_calculated;
                         maybe_mut.right_occluded_calculated = right_occluded_calculated;
+                        maybe_mut.neighbours_calculated = neighbours_calculated;
                     }
                 }
             }
@@ -95,32 +139,37 @@ impl Octree {
         &mut self,
         near: Point3<f32>,
         far: Point3<f32>,
-        material_color: [f32; 4],
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
-        fluid: i32,
-    ) {
+        material: &Material,
+    ) -> Vec<Material> {
         let collision_opt = self.root.find_first_collision(near, far);
 
-        if let Some(collision) = collision_opt {
-            self.root.paint_connected_nodes(
-                collision,
-                material_color,
-                noise,
-                noise_x,
-                noise_y,
-                noise_z,
-                fluid,
-            );
+        match collision_opt {
+            Some(collision) => self.root.paint_connected_nodes(collision, material),
+            None => Vec::new(),
         }
     }
 
+    /// The color of the first voxel along a line, if it hits one.
+    pub fn color_at_first_collision(
+        &self,
+        near: Point3<f32>,
+        far: Point3<f32>,
+    ) -> Option<[f32; 4]> {
+        let (x, y, z, level) = self.root.find_first_collision(near, far)?;
+        let node = self.root.find_by_index(x, y, z, level)?;
+        Some(node.color())
+    }
+
     /// Optimize walks the tree and based on the camera position
     /// hides nested smaller cubes in bigger ones if the detail is not required.
-    pub fn optimize(&mut self, camera_eye: [f32; 3]) {
-        self.root.optimize(camera_eye);
+    /// Returns true if the cubes to draw changed.
+    pub fn optimize(&mut self, camera_eyes: &[[f32; 3]]) -> bool {
+        self.root.optimize(camera_eyes, self.lod_distance)
+    }
+
+    /// Change how far away cubes start being merged for level of detail.
+    pub fn set_lod_distance(&mut self, lod_distance: f32) {
+        self.lod_distance = lod_distance;
     }
 
     /// Subdivide the tree into small cubes.
@@ -131,7 +180,7 @@ impl Octree {
     }
 
     /// Load the scene from disk.
-    pub fn load_from_serial(&mut self, source: StoredOctree, camera_eye: [f32; 3]) {
+    pub fn load_from_serial(&mut self, source: StoredOctree, camera_eyes: &[[f32; 3]]) {
         self.root.clear();
 
         println!("Clear the nodes");
@@ -142,7 +191,7 @@ impl Octree {
             println!("Applying node {}", index);
             self.root.apply(&node);
         }
-        self.root.optimize(camera_eye);
+        self.root.optimize(camera_eyes, self.lod_distance);
         println!("Load from serial done");
     }
 
@@ -157,28 +206,39 @@ impl Octree {
         self.root.decimate(sub_division_level);
     }
 
+    /// Fill or empty the voxels in the list, returning how many of them changed.
     pub fn toggle_voxels(
         &mut self,
         positions: Vec<[i32; 3]>,
         value: bool,
-        color: [f32; 4],
-        camera_eye: [f32; 3],
-        fluid: i32,
-        noise: i32,
-        noise_x: i32,
-        noise_y: i32,
-        noise_z: i32,
+        material: &Material,
+        camera_eyes: &[[f32; 3]],
+    ) -> usize {
+        let changed = self.root.toggle_voxels(&positions, value, material);
+        self.root.optimize(camera_eyes, self.lod_distance);
+        changed
+    }
+
+    /// Paint the active voxels in the list, leaving empty ones empty.
+    pub fn paint_voxels(
+        &mut self,
+        positions: &[[i32; 3]],
+        material: &Material,
+        camera_eyes: &[[f32; 3]],
     ) {
-        self.root.toggle_voxels(
-            &positions, value, color, fluid, noise, noise_x, noise_y, noise_z,
-        );
-        self.root.optimize(camera_eye);
+        self.root.paint_voxels(positions, material);
+        self.root.optimize(camera_eyes, self.lod_distance);
     }
 
     /// Serialize the tree.
     pub fn prepare(&self) -> StoredOctree {
         StoredOctree {
             active_nodes: self.active_nodes(),
+            lighting: Lighting::default(),
+            atmosphere: Atmosphere::default(),
+            textures: Vec::new(),
+            bookmarks: Vec::new(),
+            palette: MaterialPalette::new(),
         }
     }
 
@@ -1,38 +1,139 @@
 use crate::canvas::Canvas;
-use crate::command::{Command, CommandType};
+use crate::color_vertex::ColorVertex;
+use crate::command::{
+    Command, CommandType, MATERIAL_FLUID, MODIFIER_ALT, MODIFIER_CONTROL, MODIFIER_SHIFT,
+    MODIFIER_SUPER, unpack_color,
+};
+use crate::icon_cache::IconCache;
+use crate::key_bindings::Action;
+use crate::pattern::Pattern;
 use glium::Frame;
 use glium::backend::glutin::Display;
 use glutin::surface::WindowSurface;
 
+/// How many rows of swatches the panel shows.
+const PALETTE_ROWS: usize = 2;
+
+/// What the panel knows about a material in the palette, which is enough to draw it.
+#[derive(Copy, Clone)]
+struct PaletteSwatch {
+    color: [f32; 4],
+    pattern: Pattern,
+    fluid: bool,
+    /// How many voxels in the scene are painted with it.
+    usage: u32,
+}
+
+/// A grid of the materials in the palette, filled column by column and scrolled
+/// sideways with the mouse wheel. Clicking a swatch paints with its material, shift
+/// clicking keeps the current material in it, alt clicking selects every voxel painted
+/// with it and control clicking removes it. The cell after the last swatch adds the
+/// current material.
 pub struct Palette {
     pub position: (f32, f32),
     pub size: (f32, f32),
-    pub color: [f32; 4],
-    pub noise: bool,
-    pub fluid: bool,
-    pub picker_icon_path: String,
-    pub apply_icon_path: String,
-    pub index: usize,
+    /// Kept in step with the scene, which holds the whole materials.
+    swatches: Vec<PaletteSwatch>,
+    /// How many columns are scrolled out of view on the left.
+    scroll: usize,
+    /// The modifier keys currently held, as MODIFIER_ bits.
+    modifiers: u32,
+    /// Where the mouse was last seen.
+    mouse: (f32, f32),
 }
 
 impl Palette {
-    pub fn new(
-        position: (f32, f32),
-        size: (f32, f32),
-        color: [f32; 4],
-        noise: bool,
-        fluid: bool,
-        index: usize,
-    ) -> Self {
+    pub fn new(position: (f32, f32), size: (f32, f32)) -> Self {
         Palette {
             position,
             size,
-            color,
-            noise,
-            fluid,
-            picker_icon_path: String::from("resources/color-picker.png"),
-            apply_icon_path: String::from("resources/color-apply.png"),
-            index,
+            swatches: Vec::new(),
+            scroll: 0,
+            modifiers: 0,
+            mouse: (-2.0, -2.0),
+        }
+    }
+
+    /// The width and height of a cell.
+    fn cell_size(&self) -> (f32, f32) {
+        let height = self.size.1 / PALETTE_ROWS as f32;
+        (height, height)
+    }
+
+    /// How many columns fit across the panel.
+    fn visible_columns(&self) -> usize {
+        ((self.size.0 / self.cell_size().0) as usize).max(1)
+    }
+
+    /// The furthest the panel can scroll, keeping the add cell in view at the end.
+    fn max_scroll(&self) -> usize {
+        let columns = (self.swatches.len() + 1).div_ceil(PALETTE_ROWS);
+        columns.saturating_sub(self.visible_columns())
+    }
+
+    /// The bottom left corner of a cell, if it is scrolled into view.
+    fn cell_position(&self, cell: usize) -> Option<(f32, f32)> {
+        let column = (cell / PALETTE_ROWS).checked_sub(self.scroll)?;
+        if column >= self.visible_columns() {
+            return None;
+        }
+        let row = cell % PALETTE_ROWS;
+        let (width, height) = self.cell_size();
+        Some((
+            self.position.0 + column as f32 * width,
+            self.position.1 + self.size.1 - (row + 1) as f32 * height,
+        ))
+    }
+
+    /// The swatch or add cell at a point.
+    fn cell_at(&self, x: f32, y: f32) -> Option<usize> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let (width, height) = self.cell_size();
+        let column = ((x - self.position.0) / width) as usize;
+        let row = ((self.position.1 + self.size.1 - y) / height) as usize;
+        if column >= self.visible_columns() || row >= PALETTE_ROWS {
+            return None;
+        }
+        let cell = (column + self.scroll) * PALETTE_ROWS + row;
+        (cell <= self.swatches.len()).then_some(cell)
+    }
+
+    /// Draw a corner on swatches with a pattern and a strip along the bottom of
+    /// fluid ones, in black or white to stand out from the color.
+    fn draw_marks(
+        canvas: &mut Canvas,
+        swatch: &PaletteSwatch,
+        position: (f32, f32),
+        size: (f32, f32),
+    ) {
+        let [red, green, blue, _] = swatch.color;
+        let brightness = red * 0.3 + green * 0.59 + blue * 0.11;
+        let color = if brightness > 0.5 {
+            [0.0, 0.0, 0.0, 0.8]
+        } else {
+            [1.0, 1.0, 1.0, 0.8]
+        };
+        if swatch.pattern != Pattern::Solid {
+            let (right, top) = (position.0 + size.0, position.1 + size.1);
+            canvas.draw_triangles(&[
+                ColorVertex {
+                    position: [right - size.0 * 0.4, top],
+                    color,
+                },
+                ColorVertex {
+                    position: [right, top],
+                    color,
+                },
+                ColorVertex {
+                    position: [right, top - size.1 * 0.4],
+                    color,
+                },
+            ]);
+        }
+        if swatch.fluid {
+            canvas.draw_rectangle(position, (size.0, size.1 * 0.15), color);
         }
     }
 }
@@ -40,127 +141,185 @@ impl Palette {
 use crate::widget::Widget;
 
 impl Widget for Palette {
-    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame) {
-        let mut canvas = Canvas::new(display, frame);
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache) {
+        let mut canvas = Canvas::new(display, frame, icons);
 
         let border_color = [0.1, 0.1, 0.1, 0.8];
         let border = 0.01;
         canvas.draw_rectangle_with_border(
             self.position,
-            (self.size.0, self.size.1),
-            self.color,
+            self.size,
+            [0.3, 0.3, 0.3, 0.8],
             border,
             border_color,
         );
-        canvas.draw_rectangle_with_border(
-            self.position,
-            (self.size.0 / 2.0, self.size.1 / 2.0),
-            self.color,
-            border / 2.0,
-            border_color,
-        );
-        canvas.draw_image(
-            (
-                self.position.0 + border / 2.0,
-                self.position.1 + border / 2.0,
-            ),
-            ((self.size.0 / 2.0) - border, (self.size.1 / 2.0) - border),
-            self.picker_icon_path.as_str(),
-        );
-        canvas.draw_rectangle_with_border(
-            (self.position.0 + self.size.0 / 2.0, self.position.1),
-            (self.size.0 / 2.0, self.size.1 / 2.0),
-            self.color,
-            border / 2.0,
-            border_color,
-        );
-        canvas.draw_image(
-            (
-                self.position.0 + border / 2.0 + self.size.0 / 2.0,
-                self.position.1 + border / 2.0,
-            ),
-            (self.size.0 / 2.0 - border, self.size.1 / 2.0 - border),
-            &self.apply_icon_path.as_str(),
-        );
+
+        let (width, height) = self.cell_size();
+        let inset = border / 2.0;
+        let hovered = self.cell_at(self.mouse.0, self.mouse.1);
+        for cell in 0..=self.swatches.len() {
+            let Some((x, y)) = self.cell_position(cell) else {
+                continue;
+            };
+            let position = (x + inset, y + inset);
+            let size = (width - inset * 2.0, height - inset * 2.0);
+            let outline = if hovered == Some(cell) {
+                [1.0, 1.0, 1.0, 1.0]
+            } else {
+                border_color
+            };
+            match self.swatches.get(cell) {
+                Some(swatch) => {
+                    canvas.draw_rectangle_with_border(position, size, swatch.color, inset, outline);
+                    Palette::draw_marks(&mut canvas, swatch, position, size);
+                }
+                None => {
+                    canvas.draw_rectangle_with_border(
+                        position,
+                        size,
+                        [0.6, 0.6, 0.6, 1.0],
+                        inset,
+                        outline,
+                    );
+                    let text_height = size.1 * 0.8;
+                    let text_width = canvas.text_width(text_height, "+");
+                    canvas.draw_text(
+                        (
+                            position.0 + (size.0 - text_width) / 2.0,
+                            position.1 + (size.1 - text_height) / 2.0,
+                        ),
+                        text_height,
+                        "+",
+                        [1.0, 1.0, 1.0, 1.0],
+                    );
+                }
+            }
+        }
+
+        // Show how much of the palette is in view when it does not all fit.
+        let max_scroll = self.max_scroll();
+        if max_scroll > 0 {
+            let columns = (max_scroll + self.visible_columns()) as f32;
+            let thumb_width = self.size.0 * self.visible_columns() as f32 / columns;
+            let thumb_x = self.position.0 + self.size.0 * self.scroll as f32 / columns;
+            canvas.draw_rectangle(
+                (thumb_x, self.position.1),
+                (thumb_width, inset),
+                [0.9, 0.9, 0.9, 0.9],
+            );
+        }
+    }
+
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32)) {
+        self.position = position;
+        self.size = size;
+        self.scroll = self.scroll.min(self.max_scroll());
     }
 
     fn process_command(&mut self, command: &Command) -> Vec<Command> {
         let mut translated_commands = Vec::new();
-        // Process window event.
         match command.command_type {
             CommandType::MouseDown => {
                 let x = f32::from_bits(command.data1);
                 let y = f32::from_bits(command.data2);
-                if x >= self.position.0
-                    && x <= self.position.0 + self.size.0 / 2.0
-                    && y >= self.position.1
-                    && y <= self.position.1 + self.size.1 / 2.0
-                {
-                    translated_commands.push(Command {
-                        command_type: CommandType::PickMaterial,
-                        data1: self.index as u32,
-                        data2: self.index as u32,
-                    })
-                } else if x >= self.position.0 + self.size.0 / 2.0
-                    && x <= self.position.0 + self.size.0
-                    && y >= self.position.1
-                    && y <= self.position.1 + self.size.1 / 2.0
-                {
-                    println!("Update current material color");
+                if let Some(cell) = self.cell_at(x, y) {
+                    let command_type = if cell == self.swatches.len() {
+                        CommandType::StoreSwatch
+                    } else if self.modifiers & (MODIFIER_CONTROL | MODIFIER_SUPER) != 0 {
+                        CommandType::RemoveSwatch
+                    } else if self.modifiers & MODIFIER_SHIFT != 0 {
+                        CommandType::StoreSwatch
+                    } else if self.modifiers & MODIFIER_ALT != 0 {
+                        CommandType::SelectSwatchVoxels
+                    } else {
+                        CommandType::ApplySwatch
+                    };
                     translated_commands.push(Command {
-                        command_type: CommandType::UpdateCurrentMaterialRed,
-                        data1: self.color[0].to_bits(),
-                        data2: self.color[0].to_bits(),
-                    });
-                    translated_commands.push(Command {
-                        command_type: CommandType::UpdateCurrentMaterialGreen,
-                        data1: self.color[1].to_bits(),
-                        data2: self.color[1].to_bits(),
-                    });
-                    translated_commands.push(Command {
-                        command_type: CommandType::UpdateCurrentMaterialBlue,
-                        data1: self.color[2].to_bits(),
-                        data2: self.color[2].to_bits(),
-                    });
-                    translated_commands.push(Command {
-                        command_type: CommandType::UpdateCurrentMaterialAlpha,
-                        data1: self.color[3].to_bits(),
-                        data2: self.color[3].to_bits(),
+                        command_type,
+                        data1: cell as u32,
+                        data2: 0,
                     });
                 }
             }
-            CommandType::CurrentMaterialRed => {
-                if command.data2 == self.index as u32 {
-                    self.color[0] = f32::from_bits(command.data1);
-                }
+            CommandType::MouseHover => {
+                self.mouse = (f32::from_bits(command.data1), f32::from_bits(command.data2));
             }
-            CommandType::CurrentMaterialGreen => {
-                if command.data2 == self.index as u32 {
-                    self.color[1] = f32::from_bits(command.data1);
+            CommandType::MouseScroll => {
+                let amount = f32::from_bits(command.data2);
+                if self.contains(self.mouse.0, self.mouse.1) {
+                    if amount > 0.0 {
+                        self.scroll = self.scroll.saturating_sub(1);
+                    } else if amount < 0.0 {
+                        self.scroll = (self.scroll + 1).min(self.max_scroll());
+                    }
                 }
             }
-            CommandType::CurrentMaterialBlue => {
-                if command.data2 == self.index as u32 {
-                    self.color[2] = f32::from_bits(command.data1);
-                }
+            CommandType::ModifiersChanged => {
+                self.modifiers = command.data1;
+            }
+            CommandType::PaletteSizeChanged => {
+                self.swatches.resize(
+                    command.data1 as usize,
+                    PaletteSwatch {
+                        color: [0.8, 0.8, 0.8, 1.0],
+                        pattern: Pattern::Solid,
+                        fluid: false,
+                        usage: 0,
+                    },
+                );
+                self.scroll = self.scroll.min(self.max_scroll());
             }
-            CommandType::CurrentMaterialAlpha => {
-                if command.data2 == self.index as u32 {
-                    self.color[3] = f32::from_bits(command.data1);
+            CommandType::SwatchColorChanged => {
+                if let Some(swatch) = self.swatches.get_mut(command.data1 as usize) {
+                    swatch.color = unpack_color(command.data2);
                 }
             }
-            CommandType::CurrentMaterialNoise => {
-                if command.data2 == self.index as u32 {
-                    self.noise = command.data1 == 1;
+            CommandType::SwatchPatternChanged => {
+                if let Some(swatch) = self.swatches.get_mut(command.data1 as usize) {
+                    swatch.pattern = Pattern::from_index(command.data2 & !MATERIAL_FLUID);
+                    swatch.fluid = command.data2 & MATERIAL_FLUID != 0;
                 }
             }
-            CommandType::CurrentMaterialFluid => {
-                if command.data2 == self.index as u32 {
-                    self.fluid = command.data1 == 1;
+            CommandType::SwatchUsageChanged => {
+                if let Some(swatch) = self.swatches.get_mut(command.data1 as usize) {
+                    swatch.usage = command.data2;
                 }
             }
             _ => (),
         }
         translated_commands
     }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.position.0
+            && x <= self.position.0 + self.size.0
+            && y >= self.position.1
+            && y <= self.position.1 + self.size.1
+    }
+
+    fn draw_overlay(
+        &mut self,
+        display: &Display<WindowSurface>,
+        frame: &mut Frame,
+        icons: &mut IconCache,
+    ) {
+        let Some(cell) = self.cell_at(self.mouse.0, self.mouse.1) else {
+            return;
+        };
+        let tooltip = match self.swatches.get(cell) {
+            Some(swatch) => {
+                let [red, green, blue, alpha] = swatch
+                    .color
+                    .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8);
+                let fluid = if swatch.fluid { " fluid" } else { "" };
+                format!(
+                    "#{red:02X}{green:02X}{blue:02X}{alpha:02X} {:?}{fluid}, {} voxels | Click: paint, Shift: replace, Alt: select all, Ctrl: remove",
+                    swatch.pattern, swatch.usage
+                )
+            }
+            None => Action::AddSwatch.description(),
+        };
+        let mut canvas = Canvas::new(display, frame, icons);
+        canvas.draw_tooltip(self.position, self.size, &tooltip);
+    }
 }
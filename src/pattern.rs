@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+/// The coarsest pattern, repeating once every eight voxels.
+pub const MIN_PATTERN_SCALE: f32 = 0.125;
+
+/// The procedural patterns the camera shader can paint on a voxel.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Pattern {
+    /// No pattern, just the material color.
+    #[default]
+    Solid,
+    /// Random speckles.
+    Noise,
+    /// Wavy stripes along the x axis.
+    StripesX,
+    /// Wavy stripes along the y axis.
+    StripesY,
+    /// Wavy stripes along the z axis.
+    StripesZ,
+    /// Smooth cloudy Perlin noise.
+    Perlin,
+    /// Alternating cubes of the two colors.
+    Checker,
+    /// Rings around the y axis, like the end of a log.
+    WoodRings,
+    /// Veins twisted by turbulence.
+    Marble,
+}
+
+impl Pattern {
+    /// Get the pattern that follows this one.
+    pub fn next(&self) -> Pattern {
+        match self {
+            Pattern::Solid => Pattern::Noise,
+            Pattern::Noise => Pattern::StripesX,
+            Pattern::StripesX => Pattern::StripesY,
+            Pattern::StripesY => Pattern::StripesZ,
+            Pattern::StripesZ => Pattern::Perlin,
+            Pattern::Perlin => Pattern::Checker,
+            Pattern::Checker => Pattern::WoodRings,
+            Pattern::WoodRings => Pattern::Marble,
+            Pattern::Marble => Pattern::Solid,
+        }
+    }
+
+    /// The pattern as a number the camera shader understands.
+    pub fn index(&self) -> i32 {
+        *self as i32
+    }
+
+    /// Find the pattern from its shader number.
+    pub fn from_index(index: u32) -> Pattern {
+        match index {
+            1 => Pattern::Noise,
+            2 => Pattern::StripesX,
+            3 => Pattern::StripesY,
+            4 => Pattern::StripesZ,
+            5 => Pattern::Perlin,
+            6 => Pattern::Checker,
+            7 => Pattern::WoodRings,
+            8 => Pattern::Marble,
+            _ => Pattern::Solid,
+        }
+    }
+
+    /// Convert the noise flags from scenes saved before patterns existed.
+    pub const fn from_noise_flags(noise: i32, noise_x: i32, noise_y: i32, noise_z: i32) -> Pattern {
+        if noise != 0 {
+            Pattern::Noise
+        } else if noise_x != 0 {
+            Pattern::StripesX
+        } else if noise_y != 0 {
+            Pattern::StripesY
+        } else if noise_z != 0 {
+            Pattern::StripesZ
+        } else {
+            Pattern::Solid
+        }
+    }
+}
+
+/// A pattern and the settings that shape it.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PatternSettings {
+    /// Which pattern to draw.
+    pub pattern: Pattern,
+    /// How many times the pattern repeats per voxel. Bigger numbers give finer detail.
+    pub scale: f32,
+    /// How far the pattern blends towards the secondary color, from 0 to 1.
+    pub contrast: f32,
+    /// The color the pattern blends towards.
+    pub secondary_color: [f32; 3],
+    /// Brighten and darken the material color by up to the contrast instead of blending
+    /// towards the secondary color, the way noise looked before patterns had settings.
+    pub shade: bool,
+}
+
+impl Default for PatternSettings {
+    fn default() -> Self {
+        PatternSettings::new()
+    }
+}
+
+impl PatternSettings {
+    /// A solid material with settings that suit the noise patterns.
+    pub const fn new() -> PatternSettings {
+        PatternSettings {
+            pattern: Pattern::Solid,
+            scale: 1.0,
+            contrast: 0.4,
+            secondary_color: [0.0, 0.0, 0.0],
+            shade: false,
+        }
+    }
+
+    /// Settings that draw the noise flags from scenes saved before patterns existed the
+    /// way they looked then. Cubes without noise stay plain.
+    pub const fn from_noise_flags(
+        noise: i32,
+        noise_x: i32,
+        noise_y: i32,
+        noise_z: i32,
+    ) -> PatternSettings {
+        let pattern = Pattern::from_noise_flags(noise, noise_x, noise_y, noise_z);
+        if matches!(pattern, Pattern::Solid) {
+            return PatternSettings::new();
+        }
+        PatternSettings {
+            pattern,
+            scale: 1.0,
+            // Speckles swung the brightness by 30% and stripes by 20%.
+            contrast: match pattern {
+                Pattern::Noise => 0.3,
+                _ => 0.2,
+            },
+            secondary_color: [0.0, 0.0, 0.0],
+            shade: true,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use crate::cube::Cube;
+use crate::vertex::Vertex;
+
+/// The width of a render chunk in voxels.
+pub const CHUNK_SIZE: f32 = 32.0;
+
+/// The vertices for one block of the scene and the box that holds them,
+/// so whole blocks can be skipped when they are off screen.
+pub struct RenderChunk {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub vertices: Vec<Vertex>,
+}
+
+impl RenderChunk {
+    /// Create an empty chunk.
+    pub const fn new() -> RenderChunk {
+        RenderChunk {
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Find which chunk a cube belongs to.
+    pub fn key(cube: &Cube) -> [i32; 3] {
+        cube.translation
+            .map(|position| (position / CHUNK_SIZE).floor() as i32)
+    }
+
+    /// Add the vertices for a cube and grow the box to hold it.
+    pub fn add(&mut self, cube: &Cube, vertices: Vec<Vertex>) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(cube.translation[axis]);
+            self.max[axis] = self.max[axis].max(cube.translation[axis] + cube.scale);
+        }
+        self.vertices.extend(vertices);
+    }
+}
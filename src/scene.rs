@@ -1,16 +1,31 @@
-use crate::command::{Command, CommandType};
+use crate::atmosphere::{Atmosphere, Background, MAX_FOG_DISTANCE, MIN_FOG_DISTANCE};
+use crate::bookmark::{Bookmark, CameraTransition};
+use crate::camera::{MAX_FRAME_DISTANCE, MAX_PITCH, MIN_ORBIT_DISTANCE};
+use crate::command::{
+    Command, CommandType, EYEDROPPER, KEY_REPEAT, MATERIAL_FLUID, MODIFIER_CONTROL, MODIFIER_SUPER,
+    MODIFIERS, pack_color,
+};
 use crate::command_queue::CommandQueue;
+use crate::config::config_path;
 use crate::drawable::Drawable;
+use crate::fluid::{FluidSettings, MIN_FLUID_WAVE_SCALE};
+use crate::frustum::Frustum;
 use crate::graphics::Graphics;
 use crate::grid::Grid;
-use crate::key_bindings::KeyBindings;
+use crate::key_bindings::{KEY_BINDINGS_FILE, KeyBindings, VirtualKeyCode};
 use crate::key_bindings::Action;
+use crate::lighting::{Lighting, LightingPreset, MAX_SHADOW_SOFTNESS, PointLight};
 use crate::material::Material;
+use crate::material_palette::{MaterialPalette, PALETTE_EXTENSIONS, PALETTE_FILE};
 use crate::model::Model;
 use crate::mouse::Mouse;
 use crate::ocnode::Ocnode;
+use crate::pattern::{MIN_PATTERN_SCALE, Pattern, PatternSettings};
+use crate::render_chunk::RenderChunk;
+use crate::texture_atlas::TextureSet;
 use crate::vertex::Vertex;
-use crate::{camera::Camera, cube::Cube};
+use crate::viewport::{Layout, Viewport};
+use crate::{camera::Camera, camera::Projection, cube::Cube};
 use glium::Frame;
 use glium::backend::glutin::Display;
 use glutin::surface::WindowSurface;
@@ -18,10 +33,12 @@ use nalgebra::*;
 use rfd::FileDialog;
 use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 /// Simple list of supported selection shapes.
-#[derive(PartialEq, Eq, Copy, Clone)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum SelectionShape {
     Sphere,
     Pyramid,
@@ -37,6 +54,84 @@ pub enum SelectionShape {
     CircleYZ,
 }
 
+impl SelectionShape {
+    /// The shape as a number that can be sent in a command.
+    pub fn index(&self) -> u32 {
+        *self as u32
+    }
+
+    /// Find the shape from its command number.
+    pub fn from_index(index: u32) -> SelectionShape {
+        match index {
+            1 => SelectionShape::Pyramid,
+            2 => SelectionShape::Cube,
+            3 => SelectionShape::PoleXZ,
+            4 => SelectionShape::PoleXY,
+            5 => SelectionShape::PoleYZ,
+            6 => SelectionShape::SquareXZ,
+            7 => SelectionShape::SquareXY,
+            8 => SelectionShape::SquareYZ,
+            9 => SelectionShape::CircleXZ,
+            10 => SelectionShape::CircleXY,
+            11 => SelectionShape::CircleYZ,
+            _ => SelectionShape::Sphere,
+        }
+    }
+}
+
+/// The parts of the scene shown in the status bar.
+#[derive(Copy, Clone, PartialEq)]
+struct SceneStatus {
+    selection_position: [i32; 3],
+    selection_radius: u32,
+    selection_shape: SelectionShape,
+    material_color: [f32; 4],
+    pattern: Pattern,
+    fluid: bool,
+    voxel_count: usize,
+    unsaved_changes: bool,
+}
+
+impl SceneStatus {
+    /// The commands that bring the status bar up to date.
+    fn commands(&self) -> Vec<Command> {
+        let mut commands = Vec::new();
+        for (axis, position) in self.selection_position.iter().enumerate() {
+            commands.push(Command {
+                command_type: CommandType::SelectionPositionChanged,
+                data1: axis as u32,
+                data2: *position as u32,
+            });
+        }
+        commands.push(Command {
+            command_type: CommandType::SelectionSizeChanged,
+            data1: self.selection_radius,
+            data2: 0,
+        });
+        commands.push(Command {
+            command_type: CommandType::SelectionShapeChanged,
+            data1: self.selection_shape.index(),
+            data2: 0,
+        });
+        commands.push(Command {
+            command_type: CommandType::MaterialChanged,
+            data1: pack_color(self.material_color),
+            data2: self.pattern.index() as u32 | if self.fluid { MATERIAL_FLUID } else { 0 },
+        });
+        commands.push(Command {
+            command_type: CommandType::VoxelCountChanged,
+            data1: self.voxel_count as u32,
+            data2: 0,
+        });
+        commands.push(Command {
+            command_type: CommandType::UnsavedChangesChanged,
+            data1: if self.unsaved_changes { 1 } else { 0 },
+            data2: 0,
+        });
+        commands
+    }
+}
+
 /// This represents the data and the links to input/output required to render the scene.
 pub struct Scene {
     /// The current camera.
@@ -45,6 +140,8 @@ pub struct Scene {
     pub light: Camera,
     /// The mouse info.
     mouse: Mouse,
+    /// The keys the user has bound to each action.
+    key_bindings: KeyBindings,
     /// A queue of commands waiting to be processed.
     command_input: CommandQueue,
     /// A cube that is used to draw the selection shape.
@@ -61,6 +158,8 @@ pub struct Scene {
     selection_shape: SelectionShape,
     /// What colour will we fill if the selection is toggled.
     material_color: [f32; 4],
+    /// Does the next click sample the color of a voxel instead of painting it?
+    eyedropper: bool,
     /// Are we currently drawing a frame?
     drawing: bool,
     /// Should we skip the next frame?
@@ -71,30 +170,90 @@ pub struct Scene {
     loading: bool,
     /// Is the material fluid?
     fluid: bool,
-    /// Is the material noisy?
-    noise: bool,
-    /// Is the material noisy in the x direction?
-    noise_x: bool,
-    /// Is the material noisy in the y direction?
-    noise_y: bool,
-    /// Is the material noisy in the z direction?
-    noise_z: bool,
+    /// How the fluid material moves.
+    fluid_settings: FluidSettings,
+    /// The procedural pattern painted on the material.
+    pattern: PatternSettings,
+    /// Does dragging orbit the camera around its target instead of looking around?
+    orbit: bool,
+    /// The modifier keys currently held, as MODIFIER_ bits.
+    modifiers: u32,
+    /// Whether the camera draws in perspective or orthographic.
+    projection: Projection,
+    /// The width of the window over its height, kept from the last frame for picking.
+    aspect: f32,
+    /// How the window is split between viewports.
+    layout: Layout,
+    /// The cameras for every pane. The active one is copied into camera and projection
+    /// while it is being controlled.
+    viewports: Vec<Viewport>,
+    /// The viewport the mouse and keyboard control.
+    active_viewport: usize,
+    /// The camera positions saved with the scene.
+    bookmarks: Vec<Bookmark>,
+    /// The camera gliding to a bookmark, if it is on its way.
+    camera_transition: Option<CameraTransition>,
+    /// The slot of the bookmark being named and the name typed so far.
+    naming_bookmark: Option<(u32, String)>,
+    /// The materials in the palette panel, saved with the scene. Kept in PALETTE_FILE
+    /// between sessions unless it came from a scene.
+    palette: MaterialPalette,
+    /// The user's own palette, put aside while the panel shows one from a scene.
+    user_palette: Option<MaterialPalette>,
+    /// Does the palette panel need to hear about every swatch again?
+    palette_changed: bool,
+    /// How many voxels use each material, kept up to date as voxels change so the
+    /// palette panel does not have to count the whole model.
+    material_usage: Option<HashMap<Material, usize>>,
+    /// The voxels picked by material from the palette. Used instead of the selection
+    /// shape until the shape moves or changes.
+    material_selection: Option<Vec<[i32; 3]>>,
+    /// How strongly the material glows, from 0 to 1.
+    emissive: f32,
+    /// How rough the material is, from 0 to 1.
+    roughness: f32,
+    /// How metallic the material is, from 0 to 1.
+    metalness: f32,
+    /// The file the scene was last opened from or saved to.
+    file_name: Option<String>,
+    /// Has the scene changed since it was last opened or saved?
+    unsaved_changes: bool,
+    /// How many voxels are filled in, counted whenever the model changes.
+    voxel_count: usize,
+    /// What the status bar was last told, so it only hears about changes.
+    reported_status: Option<SceneStatus>,
+    /// The last thing that happened, shown in the status line.
+    status_message: String,
     /// Will the frame match the last rendered frame?
     dirty: bool,
     /// Approximation of time
     elapsed: f32,
     /// Render the grid
     grid_visible: bool,
+    /// Light direction, color and strength. Saved with the scene.
+    lighting: Lighting,
+    /// The last lighting preset we applied.
+    lighting_preset: LightingPreset,
+    /// Background and fog. Saved with the scene.
+    atmosphere: Atmosphere,
+    /// The images available to textured voxels. Saved with the scene.
+    textures: Vec<TextureSet>,
+    /// The texture set for the material, 0 for none, otherwise the set number plus one.
+    texture: u32,
     /// Speed of re-drawing when screen is idle.
     target_fps: u32,
     /// Only recalculate the drawables cache if the scene has changed.
     drawables_cache: Vec<Cube>,
     /// Clear the drawables cache.
     invalidate_drawables_cache: bool,
+    /// Lights given off by glowing voxels, rebuilt with the drawables cache.
+    point_lights: Vec<PointLight>,
+    /// The box around every voxel, rebuilt with the drawables cache.
+    shadow_bounds: Option<([f32; 3], [f32; 3])>,
     /// Start time of the scene.
     start_time: Option<Instant>,
     /// Hashmap to store rendered vertices for each material.
-    render_cache: Option<HashMap<Material, Vec<Vertex>>>,
+    render_cache: Option<HashMap<Material, HashMap<[i32; 3], RenderChunk>>>,
     /// Invalidate the render cache.
     invalidate_render_cache: bool,
     /// Invalidate a single material from the render cache.
@@ -103,6 +262,11 @@ pub struct Scene {
     invalidate_selection_render_cache: bool,
     /// Vec of selection vertices.
     selection_vertices_cache: Option<Vec<Vertex>>,
+    /// Translucent triangles for each viewport, sorted back to front and grouped into runs
+    /// of the same material.
+    translucent_batches: Vec<Vec<(Material, Vec<Vertex>)>>,
+    /// The camera position each viewport's translucent batches were sorted for.
+    translucent_sort_eyes: Vec<Option<[f32; 3]>>,
 }
 
 impl Scene {
@@ -111,6 +275,7 @@ impl Scene {
             camera: Camera::new(),
             light: Camera::new(),
             mouse: Mouse::new(),
+            key_bindings: KeyBindings::new(),
             command_input: CommandQueue::new(),
             selection_cube: Cube::new(),
             grid_xz: Grid::new(),
@@ -119,31 +284,62 @@ impl Scene {
             selection_radius: 1,
             selection_shape: SelectionShape::Sphere,
             material_color: [0.8, 0.8, 0.8, 1.0],
+            eyedropper: false,
             drawing: false,
             throttle: 10,
             loading: true,
             fluid: false,
-            noise: false,
-            noise_x: false,
-            noise_y: false,
-            noise_z: false,
+            fluid_settings: FluidSettings::new(),
+            pattern: PatternSettings::new(),
+            orbit: false,
+            modifiers: 0,
+            projection: Projection::Perspective,
+            aspect: 1.0,
+            layout: Layout::Single,
+            viewports: Vec::new(),
+            active_viewport: 0,
+            bookmarks: Vec::new(),
+            naming_bookmark: None,
+            camera_transition: None,
+            palette: MaterialPalette::new(),
+            user_palette: None,
+            material_usage: None,
+            palette_changed: false,
+            material_selection: None,
+            emissive: 0.0,
+            roughness: 1.0,
+            metalness: 0.0,
+            file_name: None,
+            unsaved_changes: false,
+            voxel_count: 0,
+            reported_status: None,
+            status_message: String::new(),
             dirty: true,
             elapsed: 0.0,
             last_draw: None,
             grid_visible: true,
+            lighting: Lighting::preset(LightingPreset::Day),
+            lighting_preset: LightingPreset::Day,
+            atmosphere: Atmosphere::new(),
+            textures: Vec::new(),
+            texture: 0,
             target_fps: 30,
             drawables_cache: Vec::new(),
             invalidate_drawables_cache: false,
+            point_lights: Vec::new(),
+            shadow_bounds: None,
             start_time: None,
             render_cache: None,
             invalidate_render_cache: false,
             invalidate_render_material: None,
             invalidate_selection_render_cache: false,
             selection_vertices_cache: None,
+            translucent_batches: Vec::new(),
+            translucent_sort_eyes: Vec::new(),
         }
     }
 
-    fn select_file_to_open(&mut self) {
+    fn select_file_to_open(&mut self) -> Vec<Command> {
         let file = FileDialog::new()
             .set_directory(".") // Optional: set the starting directory
             .add_filter("Scene", &["scn"])
@@ -151,16 +347,47 @@ impl Scene {
 
         if let Some(path) = file {
             println!("The user picked: {:?}", path);
-            let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-
-            self.model
-                .load(path.as_path().to_str().unwrap(), camera_eye);
+            let camera_eyes = self.camera_eyes();
+
+            let palette;
+            (
+                self.lighting,
+                self.atmosphere,
+                self.textures,
+                self.bookmarks,
+                palette,
+            ) = self
+                .model
+                .load(path.as_path().to_str().unwrap(), &camera_eyes);
+            // Scenes saved before the fog was limited to the far plane.
+            self.atmosphere.fog_distance = self
+                .atmosphere
+                .fog_distance
+                .clamp(MIN_FOG_DISTANCE, MAX_FOG_DISTANCE);
+            // Scenes saved without a palette keep the one we have.
+            if !palette.materials.is_empty() {
+                self.show_scene_palette(palette);
+            }
+            self.texture = 0;
+            self.naming_bookmark = None;
             self.invalidate_drawables_cache = true;
-
+            self.file_name = Some(path.as_path().to_str().unwrap().to_string());
+            self.unsaved_changes = false;
+            self.voxel_count = self.model.voxel_count();
+            // The swatches show how many voxels use them.
+            self.recount_material_usage();
+            self.material_selection = None;
+            self.set_status_message(format!("Opened {}", path.display()));
+
+            self.lighting_preset = LightingPreset::closest(&self.lighting);
             self.model.recalculate_occlusion();
             self.invalidate_render_cache = true;
+            let mut commands = self.lighting_commands();
+            commands.extend(self.atmosphere_commands());
+            commands
         } else {
             println!("The user canceled the operation.");
+            Vec::new()
         }
     }
 
@@ -173,7 +400,106 @@ impl Scene {
         if let Some(path) = file {
             println!("The user picked: {:?}", path);
 
-            self.model.save(path.as_path().to_str().unwrap());
+            self.model.save(
+                path.as_path().to_str().unwrap(),
+                &self.lighting,
+                &self.atmosphere,
+                &self.textures,
+                &self.bookmarks,
+                &self.palette,
+            );
+            self.file_name = Some(path.as_path().to_str().unwrap().to_string());
+            self.unsaved_changes = false;
+            self.set_status_message(format!("Saved {}", path.display()));
+        } else {
+            println!("The user canceled the operation.");
+        }
+    }
+
+    /// Choose an image to wrap around the scene as a cube map.
+    fn select_skybox_to_open(&mut self) {
+        let file = FileDialog::new()
+            .set_directory(".")
+            .add_filter("Cube map", &["png"])
+            .pick_file();
+
+        if let Some(path) = file {
+            println!("The user picked: {:?}", path);
+            self.atmosphere.cube_map = Some(path.as_path().to_str().unwrap().to_string());
+            self.atmosphere.background = Background::CubeMap;
+            self.set_status_message(format!("Background {}", path.display()));
+        } else {
+            println!("The user canceled the operation.");
+        }
+    }
+
+    /// Choose the images for a new voxel texture. Files named with top, side or bottom
+    /// are used for those faces, and the first file is used for any face left over.
+    fn select_texture_to_open(&mut self) {
+        let files = FileDialog::new()
+            .set_directory(".")
+            .add_filter("Texture", &["png"])
+            .pick_files();
+
+        let paths: Vec<String> = files
+            .unwrap_or_default()
+            .iter()
+            .map(|path| path.as_path().to_str().unwrap().to_string())
+            .collect();
+        if let Some(set) = TextureSet::from_files(&paths) {
+            self.set_status_message(format!("Added texture {}", set.top));
+            self.unsaved_changes = true;
+            self.textures.push(set);
+            self.texture = self.textures.len() as u32;
+            self.invalidate_selection_render_cache = true;
+        } else {
+            println!("The user canceled the operation.");
+        }
+    }
+
+    /// Add the colors or materials from a palette file to the end of the palette.
+    fn select_palette_to_import(&mut self) {
+        let file = FileDialog::new()
+            .set_directory(".")
+            .add_filter("Palette", &PALETTE_EXTENSIONS)
+            .pick_file();
+
+        if let Some(path) = file {
+            println!("The user picked: {:?}", path);
+            match MaterialPalette::import(path.as_path().to_str().unwrap()) {
+                Ok(imported) => {
+                    let count = imported.materials.len();
+                    self.palette.materials.extend(imported.materials);
+                    self.palette_edited();
+                    self.set_status_message(format!(
+                        "Imported {count} swatches from {}",
+                        path.display()
+                    ));
+                }
+                Err(error) => {
+                    self.set_status_message(format!("Could not import {}: {error}", path.display()))
+                }
+            }
+        } else {
+            println!("The user canceled the operation.");
+        }
+    }
+
+    /// Save the palette to a file, in the format that matches its extension.
+    fn select_palette_to_export(&mut self) {
+        let file = FileDialog::new()
+            .set_directory(".")
+            .add_filter("Palette", &PALETTE_EXTENSIONS)
+            .save_file();
+
+        if let Some(path) = file {
+            println!("The user picked: {:?}", path);
+            match self.palette.export(path.as_path().to_str().unwrap()) {
+                Ok(()) => self.set_status_message(format!("Exported {}", path.display())),
+                Err(error) => {
+                    self.set_status_message(format!("Could not export {}: {error}", path.display()))
+                }
+            }
         } else {
             println!("The user canceled the operation.");
         }
@@ -189,7 +515,8 @@ impl Scene {
     }
 
     /// Process a mouse down event.
-    pub fn handle_mouse_down(&mut self) {
+    pub fn handle_mouse_down(&mut self, command: &Command) {
+        self.press_viewport(command);
         if self.mouse.last_position[0] > -0.4
             && self.mouse.last_position[0] < 0.4
             && self.mouse.last_position[1] > -0.4
@@ -199,18 +526,77 @@ impl Scene {
         }
     }
 
+    /// Make the viewport under a button press the active one, unless the press is over the UI.
+    fn press_viewport(&mut self, command: &Command) {
+        let point = Point2::new(f32::from_bits(command.data1), f32::from_bits(command.data2));
+        if !self.mouse.over_ui {
+            self.activate_viewport(self.layout.viewport_at(point));
+        }
+        self.mouse.last_position = self.layout.point_in_viewport(self.active_viewport, point);
+    }
+
     /// Process a mouse up event.
     pub fn handle_mouse_up(&mut self) {
         self.mouse.is_pressed = false;
     }
 
+    /// Start panning when the middle button goes down over the scene.
+    pub fn handle_middle_mouse_down(&mut self, command: &Command) {
+        self.press_viewport(command);
+        if !self.mouse.over_ui {
+            self.mouse.middle_pressed = true;
+        }
+    }
+
+    /// Stop panning.
+    pub fn handle_middle_mouse_up(&mut self) {
+        self.mouse.middle_pressed = false;
+    }
+
+    /// Where the cameras of all the visible viewports are.
+    fn camera_eyes(&self) -> Vec<[f32; 3]> {
+        (0..self.layout.count())
+            .map(|index| {
+                let camera = if index == self.active_viewport {
+                    self.camera
+                } else {
+                    self.viewports[index].camera
+                };
+                [camera.eye.x, camera.eye.y, camera.eye.z]
+            })
+            .collect()
+    }
+
+    /// Update the level of detail and drawables after the eye moves.
+    /// Detail is kept near every visible camera, not only the active one.
+    fn camera_moved(&mut self) {
+        if self.model.optimize(&self.camera_eyes()) {
+            self.invalidate_drawables_cache = true;
+            self.invalidate_render_cache = true;
+        }
+    }
+
     /// Process a mouse moved event.
     pub fn handle_mouse_moved(&mut self, command: &Command) {
         let x = f32::from_bits(command.data1);
         let y = f32::from_bits(command.data2);
-        let current_position = Point2::new(x, y);
+        // Drags move the camera in the active viewport even when they wander over another.
+        let current_position = self
+            .layout
+            .point_in_viewport(self.active_viewport, Point2::new(x, y));
 
-        if self.mouse.is_pressed && (y > -0.6) {
+        if self.mouse.middle_pressed {
+            let dx = current_position.x - self.mouse.last_position.x;
+            let dy = current_position.y - self.mouse.last_position.y;
+            self.camera.pan(dx, dy, self.projection);
+            self.camera_moved();
+        } else if self.mouse.is_pressed && !self.mouse.over_ui && self.orbit {
+            let sensitivity = 2.0;
+            let dx = current_position.x - self.mouse.last_position.x;
+            let dy = current_position.y - self.mouse.last_position.y;
+            self.camera.orbit(-dx * sensitivity, dy * sensitivity);
+            self.camera_moved();
+        } else if self.mouse.is_pressed && !self.mouse.over_ui {
             let sensitivity = 0.5; // "Blunting"
             let dx = current_position.x - self.mouse.last_position.x;
             let dy = current_position.y - self.mouse.last_position.y;
@@ -220,7 +606,7 @@ impl Scene {
             self.camera.pitch -= dy * sensitivity;
 
             // 2. Clamp pitch to prevent flipping (approx 89 degrees)
-            self.camera.pitch = self.camera.pitch.clamp(-1.54, 1.54);
+            self.camera.pitch = self.camera.pitch.clamp(-MAX_PITCH, MAX_PITCH);
 
             // 3. Calculate direction vector using Spherical Coordinates
             let direction = Vector3::new(
@@ -248,9 +634,7 @@ impl Scene {
             self.camera.target.z,
         );
 
-        let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-        self.model.optimize(camera_eye);
-        self.invalidate_drawables_cache = true;
+        self.camera_moved();
     }
 
     /// The key was pressed to move down.
@@ -265,73 +649,73 @@ impl Scene {
             self.camera.target.y - 0.3_f32,
             self.camera.target.z,
         );
-        let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-        self.model.optimize(camera_eye);
-        self.invalidate_drawables_cache = true;
+        self.camera_moved();
     }
 
     /// The key was pressed to move left.
     pub fn handle_move_left(&mut self) {
-        let diff = self.camera.target - self.camera.eye;
+        let diff = self.camera.direction();
         let blunting = 10.0;
         //To rotate a vector 90 degrees clockwise, you can change the coordinates from (x,y) to (y,−x).
         let projection = Vector3::new(diff.z, 0.0, -diff.x) / blunting;
 
         self.camera.eye += projection;
         self.camera.target += projection;
-        let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-        self.model.optimize(camera_eye);
-        self.invalidate_drawables_cache = true;
+        self.camera_moved();
     }
 
     /// The key was pressed to move right.
     pub fn handle_move_right(&mut self) {
-        let diff = self.camera.target - self.camera.eye;
+        let diff = self.camera.direction();
         let blunting = 10.0;
         //To rotate a vector 90 degrees clockwise, you can change the coordinates from (x,y) to (y,−x).
         let projection = Vector3::new(diff.z, 0.0, -diff.x) / blunting;
 
         self.camera.eye -= projection;
         self.camera.target -= projection;
-        let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-        self.model.optimize(camera_eye);
-        self.invalidate_drawables_cache = true;
+        self.camera_moved();
     }
 
     /// The key was pressed to move forward.
     pub fn handle_move_forward(&mut self) {
-        let diff = self.camera.target - self.camera.eye;
+        let diff = self.camera.direction();
         let blunting = 4.0;
         let projection = Vector3::new(diff.x, 0.0, diff.z) / blunting;
 
         self.camera.eye += projection;
         self.camera.target += projection;
-        let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-        self.model.optimize(camera_eye);
-        self.invalidate_drawables_cache = true;
+        self.camera_moved();
     }
 
     /// The key was pressed to move backwards.
     pub fn handle_move_backward(&mut self) {
-        let diff = self.camera.target - self.camera.eye;
+        let diff = self.camera.direction();
         let blunting = 4.0;
         let projection = Vector3::new(-diff.x, 0.0, -diff.z) / blunting;
 
         self.camera.eye += projection;
         self.camera.target += projection;
-        let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-        self.model.optimize(camera_eye);
-        self.invalidate_drawables_cache = true;
+        self.camera_moved();
+    }
+
+    /// The material that will be used when painting or filling voxels.
+    fn current_material(&self) -> Material {
+        let color = [
+            (self.material_color[0]).clamp(0.0, 1.0),
+            (self.material_color[1]).clamp(0.0, 1.0),
+            (self.material_color[2]).clamp(0.0, 1.0),
+            (self.material_color[3]).clamp(0.0, 1.0),
+        ];
+        Material::new(color, &self.pattern, self.fluid as i32, self.emissive)
+            .with_surface(self.roughness, self.metalness)
+            .with_texture(self.texture)
+            .with_fluid(&self.fluid_settings)
     }
 
     /// The key was pressed to toggle the state of the current selection.
     pub fn handle_toggle_voxel(&mut self) {
         log::info!("Start toggling voxel");
-        let selections = Self::selection_voxels(
-            &self.selection_position,
-            self.selection_radius as i32,
-            self.selection_shape,
-        );
+        let selections = self.selected_voxels();
 
         log::info!("Checking if all voxels are active");
         let value: bool = self.model.all_voxels_active(&selections);
@@ -339,46 +723,64 @@ impl Scene {
 
         let count = selections.len();
         let fluid = self.fluid;
-        let noise = self.noise;
-        let noise_x = self.noise_x;
-        let noise_y = self.noise_y;
-        let noise_z = self.noise_z;
+        let pattern = self.pattern.pattern;
         if value {
-            log::info!(
-                "Toggle all voxels active: FALSE {count} {fluid} {noise} {noise_x} {noise_y} {noise_z}"
-            );
+            log::info!("Toggle all voxels active: FALSE {count} {fluid} {pattern:?}");
         } else {
-            log::info!(
-                "Toggle all voxels active: TRUE {count} {fluid} {noise} {noise_x} {noise_y} {noise_z}"
-            );
+            log::info!("Toggle all voxels active: TRUE {count} {fluid} {pattern:?}");
         }
-        let color = [
-            (self.material_color[0]).clamp(0.0, 1.0),
-            (self.material_color[1]).clamp(0.0, 1.0),
-            (self.material_color[2]).clamp(0.0, 1.0),
-            (self.material_color[3]).clamp(0.0, 1.0),
-        ];
-        let camera_eye = [self.camera.eye.x, self.camera.eye.y, self.camera.eye.z];
-        let fluid = if self.fluid { 1 } else { 0 };
-        let noise = if self.noise { 1 } else { 0 };
-        let noise_x = if self.noise_x { 1 } else { 0 };
-        let noise_y = if self.noise_y { 1 } else { 0 };
-        let noise_z = if self.noise_z { 1 } else { 0 };
+        let material = self.current_material();
+        let camera_eyes = self.camera_eyes();
+        let before = self.model.filled_materials(&selections);
         println!("Scene toggle voxels");
-        self.model.toggle_voxels(
-            selections, !value, color, camera_eye, fluid, noise, noise_x, noise_y, noise_z,
-        );
+        let changed = self
+            .model
+            .toggle_voxels(selections, !value, &material, &camera_eyes);
         println!("Scene toggle voxels done");
         self.invalidate_drawables_cache = true;
-        let selections = Self::selection_voxels(
-            &self.selection_position,
-            self.selection_radius as i32,
-            self.selection_shape,
-        );
+        let selections = self.selected_voxels();
+        let after = self.model.filled_materials(&selections);
+        self.update_material_usage(before, after);
         println!("Scene recalculate_occlusion_for_selections");
         self.model.recalculate_occlusion_for_selections(selections);
         println!("Scene recalculate_occlusion_for_selections DONE");
         self.invalidate_render_cache = true;
+        // Only the toggled voxels changed, so adjust the count instead of walking the tree.
+        if value {
+            self.voxel_count = self.voxel_count.saturating_sub(changed);
+        } else {
+            self.voxel_count += changed;
+        }
+        self.unsaved_changes = true;
+    }
+
+    /// The voxels the selection covers, either picked by material or inside the
+    /// selection shape.
+    fn selected_voxels(&self) -> Vec<[i32; 3]> {
+        match &self.material_selection {
+            Some(voxels) => voxels.clone(),
+            None => Self::selection_voxels(
+                &self.selection_position,
+                self.selection_radius as i32,
+                self.selection_shape,
+            ),
+        }
+    }
+
+    /// Paint the filled voxels in the selection with the current material.
+    pub fn paint_selection(&mut self) {
+        let selections = self.selected_voxels();
+        let material = self.current_material();
+        let camera_eyes = self.camera_eyes();
+        let before = self.model.filled_materials(&selections);
+        self.model
+            .paint_voxels(&selections, &material, &camera_eyes);
+        let after = self.model.filled_materials(&selections);
+        self.update_material_usage(before, after);
+        self.model.recalculate_occlusion_for_selections(selections);
+        self.invalidate_drawables_cache = true;
+        self.invalidate_render_cache = true;
+        self.unsaved_changes = true;
     }
 
     /// Save the scene to the browser.
@@ -386,6 +788,7 @@ impl Scene {
     /// Move the selection shape left.
     pub fn handle_move_selection_left(&mut self) {
         self.selection_cube.translate([-1.0, 0.0, 0.0]);
+        self.material_selection = None;
         self.selection_position[0] -= 1;
         self.invalidate_selection_render_cache = true;
     }
@@ -393,6 +796,7 @@ impl Scene {
     /// Move the selection shape right.
     pub fn handle_move_selection_right(&mut self) {
         self.selection_cube.translate([1.0, 0.0, 0.0]);
+        self.material_selection = None;
         self.selection_position[0] += 1;
         self.invalidate_selection_render_cache = true;
     }
@@ -400,6 +804,7 @@ impl Scene {
     /// Move the selection shape forward.
     pub fn handle_move_selection_forward(&mut self) {
         self.selection_cube.translate([0.0, 0.0, 1.0]);
+        self.material_selection = None;
         self.selection_position[2] += 1;
         self.invalidate_selection_render_cache = true;
     }
@@ -407,6 +812,7 @@ impl Scene {
     /// Move the selection shape backward.
     pub fn handle_move_selection_backward(&mut self) {
         self.selection_cube.translate([0.0, 0.0, -1.0]);
+        self.material_selection = None;
         self.selection_position[2] -= 1;
         self.invalidate_selection_render_cache = true;
     }
@@ -414,6 +820,7 @@ impl Scene {
     /// Move the selection shape up.
     pub fn handle_move_selection_up(&mut self) {
         self.selection_cube.translate([0.0, 1.0, 0.0]);
+        self.material_selection = None;
         self.selection_position[1] += 1;
         self.invalidate_selection_render_cache = true;
     }
@@ -421,6 +828,7 @@ impl Scene {
     /// Move the selection shape down.
     pub fn handle_move_selection_down(&mut self) {
         self.selection_cube.translate([0.0, -1.0, 0.0]);
+        self.material_selection = None;
         self.selection_position[1] -= 1;
         self.invalidate_selection_render_cache = true;
     }
@@ -452,6 +860,7 @@ impl Scene {
         } else {
             SelectionShape::Sphere
         };
+        self.material_selection = None;
         self.invalidate_selection_render_cache = true;
     }
 
@@ -502,30 +911,109 @@ impl Scene {
                     data2: 3,
                 });
             }
+            4 => {
+                // Value is the light azimuth in degrees from 0 to 360
+                self.lighting.azimuth = (command.data2 as f32).to_radians();
+            }
+            5 => {
+                // Value is the light elevation in degrees from 0 to 90
+                self.lighting.elevation = (command.data2 as f32).to_radians();
+            }
+            6 => {
+                // Value is the light intensity as a percentage from 0 to 200
+                self.lighting.intensity = command.data2 as f32 / 100.0;
+            }
+            7 => {
+                // Value is the ambient light as a percentage from 0 to 100
+                self.lighting.ambient = command.data2 as f32 / 100.0;
+            }
+            8 => {
+                // Value is the material glow from 0 to 255
+                self.emissive = command.data2 as f32 / 255.0;
+            }
+            9 => {
+                // Value is the material roughness from 0 to 255
+                self.roughness = command.data2 as f32 / 255.0;
+            }
+            10 => {
+                // Value is the material metalness from 0 to 255
+                self.metalness = command.data2 as f32 / 255.0;
+            }
+            11 => {
+                // Value is the shadow bias in ten thousandths from 0 to 50
+                self.lighting.shadow_bias = command.data2 as f32 / 10000.0;
+            }
+            12 => {
+                // Value is the shadow softness in texels
+                self.lighting.shadow_softness = command.data2.min(MAX_SHADOW_SOFTNESS);
+            }
+            13 => {
+                // Value is the fog distance from 0 to 200
+                self.atmosphere.fog_distance =
+                    (command.data2 as f32).clamp(MIN_FOG_DISTANCE, MAX_FOG_DISTANCE);
+            }
+            14 => {
+                // Value is the pattern scale in 32nds from 0 to 256
+                self.pattern.scale = (command.data2 as f32 / 32.0).max(MIN_PATTERN_SCALE);
+            }
+            15 => {
+                // Value is the pattern contrast from 0 to 255
+                self.pattern.contrast = command.data2 as f32 / 255.0;
+            }
+            16 => {
+                // Value is the fluid speed in tenths from 0 to 255
+                self.fluid_settings.speed = command.data2 as f32 / 10.0;
+            }
+            17 => {
+                // Value is the fluid wave scale in 32nds from 0 to 256
+                self.fluid_settings.wave_scale =
+                    (command.data2 as f32 / 32.0).max(MIN_FLUID_WAVE_SCALE);
+            }
+            18 => {
+                // Value is the fluid color shift from 0 to 255
+                self.fluid_settings.color_shift = command.data2 as f32 / 255.0;
+            }
+            19 => {
+                // Value is the fluid displacement from 0 to 255
+                self.fluid_settings.displacement = command.data2 as f32 / 255.0;
+            }
+            20 => {
+                // Value is the level of detail distance from 0 (off) to 400
+                self.model.set_lod_distance(command.data2 as f32);
+                let camera_eyes = self.camera_eyes();
+                self.model.optimize(&camera_eyes);
+                self.invalidate_drawables_cache = true;
+                self.invalidate_render_cache = true;
+            }
 
             _ => {}
         }
+        // The lighting and fog are saved with the scene.
+        if matches!(command.data1, 4..=7 | 11..=13) {
+            self.unsaved_changes = true;
+        }
         self.invalidate_selection_render_cache = true;
         translated_commands
     }
 
     /// Get the view from the camera.
     pub fn build_camera_projection(&self) -> Matrix4<f32> {
-        Perspective3::new(
-            1.0,
-            std::f32::consts::PI / 4.0, // 45 degrees
-            1.0,
-            200.0,
-        )
-        .into_inner()
+        let [_, _, width, height] = self.layout.area(self.active_viewport);
+        self.camera
+            .projection_matrix(self.projection, self.aspect * width / height)
+    }
+
+    /// Find what the camera can see. This uses the projection from graphics because
+    /// it knows the shape of the window.
+    fn camera_frustum(camera: &Camera, graphics: &Graphics) -> Frustum {
+        let view = camera.view();
+        Frustum::from_matrix(&(graphics.build_camera_projection(camera) * view.to_homogeneous()))
     }
 
     // Convert from 2d window coordinates to 3d world coordinates
     pub fn unproject(&self, x: f32, y: f32) -> Option<(Point3<f32>, Point3<f32>)> {
         // We need to calculate the model matrix for the drawable object
-        let eye = self.camera.eye;
-        let target = self.camera.target;
-        let view = Isometry3::look_at_rh(&eye, &target, &Vector3::y());
+        let view = self.camera.view();
 
         let model: Isometry3<f32> = Isometry3::identity();
         let projection_matrix = self.build_camera_projection();
@@ -534,124 +1022,112 @@ impl Scene {
 
         let transform_matrix = (projection_matrix * (model_view)).try_inverse().unwrap();
 
-        let near = transform_matrix.transform_point(&Point3::new(x, y, 0.0));
+        // Depth runs from -1 at the near plane to 1 at the far plane. An orthographic near plane
+        // is behind the eye, so this also picks voxels the eye has flown past.
+        let near = transform_matrix.transform_point(&Point3::new(x, y, -1.0));
         let far = transform_matrix.transform_point(&Point3::new(x, y, 1.0));
 
         Some((near, far))
     }
 
-    pub fn handle_mouse_click(&mut self, command: &Command) {
-        let current_position =
+    pub fn handle_mouse_click(&mut self, command: &Command) -> Vec<Command> {
+        let window_position =
             Point2::new(f32::from_bits(command.data1), f32::from_bits(command.data2));
-        if current_position.y > -0.6 {
+        if !self.mouse.over_ui {
+            self.activate_viewport(self.layout.viewport_at(window_position));
+            let current_position = self
+                .layout
+                .point_in_viewport(self.active_viewport, window_position);
             println!("Mouse clicked at position: {:?}", current_position);
             let maybe_near_far = self.unproject(current_position.x, current_position.y);
             if let Some((near, far)) = maybe_near_far {
                 println!("Near: {:?}, Far: {:?}", near, far);
 
-                self.model.paint_first_collision(
-                    near,
-                    far,
-                    self.material_color,
-                    self.noise as i32,
-                    self.noise_x as i32,
-                    self.noise_y as i32,
-                    self.noise_z as i32,
-                    self.fluid as i32,
-                );
+                if self.eyedropper {
+                    return self.sample_color(near, far);
+                }
+                let material = self.current_material();
+                let replaced = self.model.paint_first_collision(near, far, &material);
+                let painted = vec![material; replaced.len()];
+                self.update_material_usage(replaced, painted);
                 self.invalidate_drawables_cache = true;
+                self.unsaved_changes = true;
                 self.model.recalculate_occlusion();
                 self.invalidate_render_cache = true;
             }
         }
+        Vec::new()
     }
 
-    pub fn handle_pick_material(&mut self, command: &Command) -> Vec<Command> {
-        let mut translated_commands = Vec::new();
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialRed,
-            data1: self.material_color[0].to_bits(),
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialGreen,
-            data1: self.material_color[1].to_bits(),
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialBlue,
-            data1: self.material_color[2].to_bits(),
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialAlpha,
-            data1: self.material_color[3].to_bits(),
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialNoise,
-            data1: if self.noise { 1 } else { 0 },
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialNoiseX,
-            data1: if self.noise_x { 1 } else { 0 },
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialNoiseY,
-            data1: if self.noise_y { 1 } else { 0 },
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialNoiseZ,
-            data1: if self.noise_z { 1 } else { 0 },
-            data2: command.data2,
-        });
-        translated_commands.push(Command {
-            command_type: CommandType::CurrentMaterialFluid,
-            data1: if self.fluid { 1 } else { 0 },
-            data2: command.data2,
-        });
-        translated_commands
+    /// Answer the eyedropper with the color of the voxel under the mouse. Misses leave
+    /// the eyedropper waiting for another click.
+    fn sample_color(&mut self, near: Point3<f32>, far: Point3<f32>) -> Vec<Command> {
+        let Some(color) = self.model.color_at_first_collision(near, far) else {
+            return Vec::new();
+        };
+        self.eyedropper = false;
+        [
+            CommandType::CurrentMaterialRed,
+            CommandType::CurrentMaterialGreen,
+            CommandType::CurrentMaterialBlue,
+            CommandType::CurrentMaterialAlpha,
+        ]
+        .iter()
+        .zip(color)
+        .map(|(command_type, value)| Command {
+            command_type: *command_type,
+            data1: value.to_bits(),
+            data2: EYEDROPPER,
+        })
+        .collect()
+    }
+
+    /// Wait for a click on a voxel to sample its color.
+    pub fn handle_pick_material(&mut self, command: &Command) {
+        if command.data1 == EYEDROPPER {
+            self.eyedropper = true;
+        }
     }
 
-    /// Handle the mouse scroll.
+    /// Handle the mouse scroll. Scrolling changes the selection size, or zooms
+    /// the camera while control or command is held.
     pub fn handle_mouse_scroll(&mut self, command: &Command) {
-        let direction: u32 = command.data2;
+        let direction = f32::from_bits(command.data2);
+        if direction == 0.0 {
+            return;
+        }
+        if self.modifiers & (MODIFIER_CONTROL | MODIFIER_SUPER) != 0 {
+            let zoom_in = direction > 0.0;
+            let factor = if zoom_in { 0.9 } else { 1.0 / 0.9 };
+            if self.projection == Projection::Orthographic {
+                self.camera.zoom_view(factor);
+            } else if self.orbit {
+                self.camera.zoom(factor);
+            } else {
+                self.camera.dolly(if zoom_in { 2.0 } else { -2.0 });
+            }
+            self.camera_moved();
+            return;
+        }
         let max_selection_radius: u32 = 128;
         let min_selection_radius: u32 = 1;
-        if direction > 0 {
+        if direction > 0.0 {
             self.selection_radius = min(self.selection_radius + 1, max_selection_radius);
         } else {
             self.selection_radius = max(self.selection_radius - 1, min_selection_radius);
         }
+        self.material_selection = None;
         self.invalidate_render_cache = true;
     }
 
     pub fn print_keyboard_bindings(&self) {
         println!("");
         println!("Keyboard Bindings:");
-        println!("W or <up>: Move forward");
-        println!("S or <down>: Move backward");
-        println!("A or <left>: Move left");
-        println!("D or <right>: Move right");
-        println!("Q: Move up");
-        println!("E: Move down");
-        println!("I or 8: Move selection forward");
-        println!("K or 5: Move selection backward");
-        println!("J or 4: Move selection left");
-        println!("L or 6: Move selection right");
-        println!("U or 7: Move selection up");
-        println!("O or 9: Move selection down");
-        println!("Space: Create/Destroy voxels in the current selection");
-        println!("T: Cycle the selection shape");
-        println!("F: Toggle fluid mode");
-        println!("G: Toggle grid visibility");
-        println!("N: Toggle material noise");
-        println!("M: Toggle material noise X");
-        println!(",: Toggle material noise Y");
-        println!(".: Toggle material noise Z");
+        for line in self.key_bindings.descriptions() {
+            println!("{line}");
+        }
+        println!("Middle drag: Pan the camera");
+        println!("Ctrl or Cmd + scroll: Zoom the camera");
     }
 
     pub fn more_red(&mut self) {
@@ -687,16 +1163,28 @@ impl Scene {
     }
 
     /// Handle a key press.
-    pub fn handle_key_down(&mut self, command: &Command) {
+    pub fn handle_key_down(&mut self, command: &Command) -> Vec<Command> {
+        let mut translated_commands = Vec::new();
         let key = command.data1;
 
         println!("Key pressed: {}", key);
 
-        let bindings: KeyBindings = KeyBindings::new();
+        let modifiers = command.data2 & MODIFIERS;
+        let repeat = command.data2 & KEY_REPEAT != 0;
+        let action = self.key_bindings.action(key, modifiers, repeat);
+        // Let the buttons know when their action came from the keyboard, so they stay in step.
+        if VirtualKeyCode::from_u32(key).is_none()
+            && let Some(virtual_key) = self.key_bindings.virtual_key(action)
+        {
+            translated_commands.push(Command {
+                command_type: CommandType::KeyDown,
+                data1: virtual_key as u32,
+                data2: 0,
+            });
+        }
 
-        match bindings.action(key) {
-            Some(Action::Forward) => self.handle_move_forward(),
-            Some(Action::OpenScene) => self.select_file_to_open(),
+        match action {
+            Some(Action::OpenScene) => translated_commands.extend(self.select_file_to_open()),
             Some(Action::SaveScene) => self.select_file_to_save(),
             Some(Action::MoveUp) => self.handle_move_up(),
             Some(Action::MoveDown) => self.handle_move_down(),
@@ -715,8 +1203,63 @@ impl Scene {
             Some(Action::ToggleFluid) => self.toggle_fluid(),
             Some(Action::ToggleShowGrid) => self.toggle_show_grid(),
             Some(Action::ToggleNoise) => self.toggle_noise(),
+            Some(Action::ToggleAmbientOcclusion) => self.toggle_ambient_occlusion(),
+            Some(Action::ToggleShadows) => self.toggle_shadows(),
+            Some(Action::CycleLightingPreset) => {
+                translated_commands.extend(self.cycle_lighting_preset())
+            }
+            Some(Action::ApplyLightColor) => self.apply_light_color(),
+            Some(Action::CycleBackground) => self.cycle_background(),
+            Some(Action::ToggleFog) => self.toggle_fog(),
+            Some(Action::OpenSkybox) => self.select_skybox_to_open(),
+            Some(Action::ApplySkyColor) => self.apply_sky_color(),
+            Some(Action::ApplyHorizonColor) => self.apply_horizon_color(),
+            Some(Action::OpenTexture) => self.select_texture_to_open(),
+            Some(Action::CycleTexture) => self.cycle_texture(),
+            Some(Action::ApplySecondaryColor) => self.apply_secondary_color(),
+            Some(Action::ToggleOrbit) => self.toggle_orbit(),
+            Some(Action::FrameSelection) => self.frame_selection(),
+            Some(Action::FrameAll) => self.frame_all(),
+            Some(Action::ToggleProjection) => self.toggle_projection(),
+            Some(Action::ViewFront) => self.view_along_axis(PI, 0.0),
+            Some(Action::ViewBack) => self.view_along_axis(0.0, 0.0),
+            Some(Action::ViewLeft) => self.view_along_axis(FRAC_PI_2, 0.0),
+            Some(Action::ViewRight) => self.view_along_axis(-FRAC_PI_2, 0.0),
+            Some(Action::ViewTop) => self.view_along_axis(PI, -FRAC_PI_2),
+            Some(Action::ViewBottom) => self.view_along_axis(PI, FRAC_PI_2),
+            Some(Action::RecallBookmark(slot)) => self.recall_bookmark(slot),
+            Some(Action::StoreBookmark(slot)) => self.store_bookmark(slot),
+            Some(Action::CycleLayout) => self.cycle_layout(),
+            Some(Action::AddSwatch) => self.store_swatch(self.palette.materials.len()),
+            Some(Action::ImportPalette) => self.select_palette_to_import(),
+            Some(Action::ExportPalette) => self.select_palette_to_export(),
+            Some(Action::PaletteFromScene) => self.palette_from_scene(),
+            Some(Action::PaintSelection) => self.paint_selection(),
+            Some(Action::ReloadKeyBindings) => {
+                let path = config_path(KEY_BINDINGS_FILE);
+                self.key_bindings.load(&path);
+                self.print_keyboard_bindings();
+                self.set_status_message(format!("Reloaded {path}"));
+            }
             _ => ()
         }
+        // The lighting and background are saved with the scene.
+        if matches!(
+            action,
+            Some(
+                Action::ToggleAmbientOcclusion
+                    | Action::ToggleShadows
+                    | Action::CycleLightingPreset
+                    | Action::ApplyLightColor
+                    | Action::CycleBackground
+                    | Action::ToggleFog
+                    | Action::OpenSkybox
+                    | Action::ApplySkyColor
+                    | Action::ApplyHorizonColor
+            )
+        ) {
+            self.unsaved_changes = true;
+        }
         /*
         match key {
             1 => self.select_file_to_open(),
@@ -768,6 +1311,7 @@ impl Scene {
             _ => log::info!("Unhandled key press: {}", key),
         }
         */
+        translated_commands
     }
 
     pub fn update_current_material_red(&mut self, command: &Command) -> Vec<Command> {
@@ -857,46 +1401,136 @@ impl Scene {
                     translated_commands.extend(self.handle_slider_moved(&command));
                 }
                 CommandType::MouseDown => {
-                    self.handle_mouse_down();
+                    self.handle_mouse_down(&command);
                 }
                 CommandType::MouseUp => {
                     self.handle_mouse_up();
                 }
+                CommandType::MiddleMouseDown => {
+                    self.handle_middle_mouse_down(&command);
+                }
+                CommandType::MiddleMouseUp => {
+                    self.handle_middle_mouse_up();
+                }
+                CommandType::ModifiersChanged => {
+                    self.modifiers = command.data1;
+                }
                 CommandType::MouseClick => {
-                    self.handle_mouse_click(&command);
+                    translated_commands.extend(self.handle_mouse_click(&command));
                 }
                 CommandType::MouseMoved => {
                     self.handle_mouse_moved(&command);
                 }
+                CommandType::PointerOverUi => {
+                    self.mouse.over_ui = command.data1 != 0;
+                }
                 CommandType::KeyDown => {
-                    self.handle_key_down(&command);
+                    translated_commands.extend(self.handle_key_down(&command));
+                }
+                CommandType::TextInput => {
+                    if let Some(character) = char::from_u32(command.data1) {
+                        self.handle_text_input(character);
+                    }
                 }
                 CommandType::MouseScroll => {
                     self.handle_mouse_scroll(&command);
                 }
                 CommandType::PickMaterial => {
-                    translated_commands.extend(self.handle_pick_material(&command));
+                    self.handle_pick_material(&command);
                 }
-                CommandType::UpdateCurrentMaterialRed => {
+                CommandType::SetMaterialRed => {
                     translated_commands.extend(self.update_current_material_red(&command));
                 }
-                CommandType::UpdateCurrentMaterialGreen => {
+                CommandType::SetMaterialGreen => {
                     translated_commands.extend(self.update_current_material_green(&command));
                 }
-                CommandType::UpdateCurrentMaterialBlue => {
+                CommandType::SetMaterialBlue => {
                     translated_commands.extend(self.update_current_material_blue(&command));
                 }
-                CommandType::UpdateCurrentMaterialAlpha => {
+                CommandType::SetMaterialAlpha => {
                     translated_commands.extend(self.update_current_material_alpha(&command));
                 }
+                CommandType::ApplySwatch => {
+                    translated_commands.extend(self.apply_swatch(command.data1 as usize));
+                }
+                CommandType::StoreSwatch => {
+                    self.store_swatch(command.data1 as usize);
+                }
+                CommandType::RemoveSwatch => {
+                    self.remove_swatch(command.data1 as usize);
+                }
+                CommandType::SelectSwatchVoxels => {
+                    self.select_swatch_voxels(command.data1 as usize);
+                }
                 _ => {}
             }
 
             command_opt = self.command_input.next();
         }
+        translated_commands.extend(self.status_commands());
+        translated_commands.extend(self.palette_commands());
         translated_commands
     }
 
+    /// Tell the status bar about anything that changed since it was last told.
+    fn status_commands(&mut self) -> Vec<Command> {
+        let status = SceneStatus {
+            selection_position: self.selection_position,
+            selection_radius: self.selection_radius,
+            selection_shape: self.selection_shape,
+            material_color: self.material_color,
+            pattern: self.pattern.pattern,
+            fluid: self.fluid,
+            voxel_count: self.voxel_count,
+            unsaved_changes: self.unsaved_changes,
+        };
+        if self.reported_status == Some(status) {
+            return Vec::new();
+        }
+        self.reported_status = Some(status);
+        status.commands()
+    }
+
+    /// Tell the palette panel about every swatch after the palette changes.
+    fn palette_commands(&mut self) -> Vec<Command> {
+        if !self.palette_changed {
+            return Vec::new();
+        }
+        self.palette_changed = false;
+        let mut commands = vec![Command {
+            command_type: CommandType::PaletteSizeChanged,
+            data1: self.palette.materials.len() as u32,
+            data2: 0,
+        }];
+        for (index, material) in self.palette.materials.iter().enumerate() {
+            commands.push(Command {
+                command_type: CommandType::SwatchColorChanged,
+                data1: index as u32,
+                data2: pack_color(material.upscale_color()),
+            });
+            let fluid = if material.fluid != 0 {
+                MATERIAL_FLUID
+            } else {
+                0
+            };
+            commands.push(Command {
+                command_type: CommandType::SwatchPatternChanged,
+                data1: index as u32,
+                data2: material.pattern.index() as u32 | fluid,
+            });
+            commands.push(Command {
+                command_type: CommandType::SwatchUsageChanged,
+                data1: index as u32,
+                data2: self
+                    .material_usage
+                    .as_ref()
+                    .and_then(|usage| usage.get(material).copied())
+                    .unwrap_or(0) as u32,
+            });
+        }
+        commands
+    }
+
     /// Should we render the current frame?
     pub fn throttle(&mut self) -> bool {
         if !self.dirty {
@@ -930,26 +1564,33 @@ impl Scene {
         false
     }
 
-    /// Change the active color for this scene.
+    /// Print a message and keep it for the status line.
+    fn set_status_message(&mut self, message: String) {
+        println!("{message}");
+        self.status_message = message;
+    }
 
-    /// Load a scene from the browser.
+    /// A line describing the scene and the last thing that happened to it.
+    pub fn status(&self) -> String {
+        let file_name = match &self.file_name {
+            Some(path) => Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.clone()),
+            None => String::from("Untitled"),
+        };
+        let mut status = format!("{file_name} | {:?}", self.projection);
+        if !self.status_message.is_empty() {
+            status.push_str(" | ");
+            status.push_str(&self.status_message);
+        }
+        status
+    }
 
-    /// Enable color noise.
+    /// Switch the material to the next procedural pattern.
     pub fn toggle_noise(&mut self) {
-        if self.noise {
-            self.noise = false;
-            self.noise_x = true;
-        } else if self.noise_x {
-            self.noise_x = false;
-            self.noise_y = true;
-        } else if self.noise_y {
-            self.noise_y = false;
-            self.noise_z = true;
-        } else if self.noise_z {
-            self.noise_z = false;
-        } else {
-            self.noise = true;
-        }
+        self.pattern.pattern = self.pattern.pattern.next();
+        self.set_status_message(format!("Pattern: {:?}", self.pattern.pattern));
 
         self.invalidate_render_cache = true;
     }
@@ -959,6 +1600,541 @@ impl Scene {
         self.grid_visible = !self.grid_visible;
     }
 
+    /// Show or hide the ambient occlusion shading.
+    pub fn toggle_ambient_occlusion(&mut self) {
+        self.lighting.ambient_occlusion = !self.lighting.ambient_occlusion;
+    }
+
+    /// Enable or disable shadows from the light.
+    pub fn toggle_shadows(&mut self) {
+        self.lighting.shadows = !self.lighting.shadows;
+    }
+
+    /// Switch to the next lighting preset.
+    pub fn cycle_lighting_preset(&mut self) -> Vec<Command> {
+        self.lighting_preset = self.lighting_preset.next();
+        self.lighting = Lighting::preset(self.lighting_preset);
+        self.set_status_message(format!("Lighting preset: {:?}", self.lighting_preset));
+        self.lighting_commands()
+    }
+
+    /// Tint the light with the current material color.
+    pub fn apply_light_color(&mut self) {
+        self.lighting.color = [
+            self.material_color[0].clamp(0.0, 1.0),
+            self.material_color[1].clamp(0.0, 1.0),
+            self.material_color[2].clamp(0.0, 1.0),
+        ];
+    }
+
+    /// Switch to the next kind of background.
+    pub fn cycle_background(&mut self) {
+        self.atmosphere.background = self.atmosphere.background.next();
+        self.set_status_message(format!("Background: {:?}", self.atmosphere.background));
+    }
+
+    /// Enable or disable distance fog.
+    pub fn toggle_fog(&mut self) {
+        self.atmosphere.fog = !self.atmosphere.fog;
+    }
+
+    /// Use the current material color for the top of the sky.
+    pub fn apply_sky_color(&mut self) {
+        self.atmosphere.sky_color = [
+            self.material_color[0].clamp(0.0, 1.0),
+            self.material_color[1].clamp(0.0, 1.0),
+            self.material_color[2].clamp(0.0, 1.0),
+        ];
+    }
+
+    /// Use the current material color for the horizon and the fog.
+    pub fn apply_horizon_color(&mut self) {
+        self.atmosphere.horizon_color = [
+            self.material_color[0].clamp(0.0, 1.0),
+            self.material_color[1].clamp(0.0, 1.0),
+            self.material_color[2].clamp(0.0, 1.0),
+        ];
+    }
+
+    /// The middle of the voxel at the center of the selection.
+    fn selection_center(&self) -> Point3<f32> {
+        Point3::from(
+            self.selection_position
+                .map(|position| position as f32 + 0.5),
+        )
+    }
+
+    /// Switch between flying around and orbiting. Orbiting starts around the selection.
+    pub fn toggle_orbit(&mut self) {
+        self.orbit = !self.orbit;
+        if self.orbit {
+            self.camera.look_at(self.selection_center());
+        } else {
+            self.camera.target = self.camera.eye + self.camera.direction();
+        }
+    }
+
+    /// How far the camera is from what it is looking at. Flying has no real target,
+    /// so the selection is used instead.
+    fn focus_distance(&self) -> f32 {
+        if self.orbit {
+            self.camera.distance()
+        } else {
+            (self.selection_center() - self.camera.eye).norm()
+        }
+    }
+
+    /// Switch between perspective and orthographic. The orthographic view starts
+    /// showing about as much as the perspective view did.
+    pub fn toggle_projection(&mut self) {
+        self.projection = self.projection.next();
+        if self.projection == Projection::Orthographic {
+            self.camera.match_perspective(self.focus_distance());
+        }
+        self.camera_moved();
+    }
+
+    /// Look at the selection straight along one of the axes, in orthographic
+    /// so voxels line up exactly.
+    pub fn view_along_axis(&mut self, yaw: f32, pitch: f32) {
+        let distance = self
+            .focus_distance()
+            .clamp(MIN_ORBIT_DISTANCE, MAX_FRAME_DISTANCE);
+        if self.projection == Projection::Perspective {
+            self.camera.match_perspective(distance);
+            self.projection = Projection::Orthographic;
+        }
+        self.camera
+            .look_along(yaw, pitch, self.selection_center(), distance);
+        if !self.orbit {
+            self.camera.target = self.camera.eye + self.camera.direction();
+        }
+        self.camera_moved();
+    }
+
+    /// Point the camera at the selection and back away until it all fits.
+    pub fn frame_selection(&mut self) {
+        let voxels = Self::selection_voxels(
+            &self.selection_position,
+            self.selection_radius as i32,
+            self.selection_shape,
+        );
+        if voxels.is_empty() {
+            return;
+        }
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for voxel in voxels.iter() {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(voxel[axis] as f32);
+                max[axis] = max[axis].max(voxel[axis] as f32 + 1.0);
+            }
+        }
+        self.camera.frame(min, max);
+        self.camera_moved();
+    }
+
+    /// Point the camera at the whole model and back away until it all fits.
+    pub fn frame_all(&mut self) {
+        let (min, max) = self.shadow_bounds.unwrap_or(([-8.0; 3], [8.0; 3]));
+        self.camera.frame(min, max);
+        self.camera_moved();
+    }
+
+    /// Keep the active camera in its viewport so it can be drawn with the others.
+    fn store_active_viewport(&mut self) {
+        self.viewports[self.active_viewport] = Viewport {
+            camera: self.camera,
+            projection: self.projection,
+        };
+    }
+
+    /// Hand the mouse and keyboard to another viewport.
+    fn activate_viewport(&mut self, index: usize) {
+        if index == self.active_viewport {
+            return;
+        }
+        self.store_active_viewport();
+        self.active_viewport = index;
+        self.camera = self.viewports[index].camera;
+        self.projection = self.viewports[index].projection;
+        self.camera_transition = None;
+        self.camera_moved();
+    }
+
+    /// Switch between one, two and four viewports.
+    pub fn cycle_layout(&mut self) {
+        self.layout = self.layout.next();
+        if self.active_viewport >= self.layout.count() {
+            self.activate_viewport(0);
+        }
+        // The level of detail follows the cameras that are showing.
+        self.camera_moved();
+    }
+
+    /// Remember where the camera is on a number key, replacing any bookmark already there.
+    pub fn store_bookmark(&mut self, slot: u32) {
+        let name = match self.bookmarks.iter().find(|bookmark| bookmark.slot == slot) {
+            Some(bookmark) => bookmark.name.clone(),
+            None => format!("Bookmark {slot}"),
+        };
+        self.bookmarks.retain(|bookmark| bookmark.slot != slot);
+        self.bookmarks
+            .push(Bookmark::from_camera(name, slot, &self.camera));
+        self.unsaved_changes = true;
+        self.naming_bookmark = Some((slot, String::new()));
+        self.set_status_message(format!(
+            "Stored the camera on {slot}, type a name and press enter"
+        ));
+    }
+
+    /// Is the scene waiting for a bookmark name to be typed?
+    pub fn has_focus(&self) -> bool {
+        self.naming_bookmark.is_some()
+    }
+
+    /// Type a character of the bookmark name. Enter keeps the name and escape keeps the old one.
+    fn handle_text_input(&mut self, character: char) {
+        let Some((slot, mut entry)) = self.naming_bookmark.take() else {
+            return;
+        };
+        match character {
+            '\u{8}' => {
+                entry.pop();
+            }
+            '\u{1b}' => {
+                self.set_status_message(format!("Stored the camera on {slot}"));
+                return;
+            }
+            '\r' => {
+                let name = entry.trim();
+                if !name.is_empty() {
+                    for bookmark in self.bookmarks.iter_mut() {
+                        if bookmark.slot == slot {
+                            bookmark.name = name.to_string();
+                        }
+                    }
+                    self.set_status_message(format!("Stored the camera on {slot} as {name}"));
+                } else {
+                    self.set_status_message(format!("Stored the camera on {slot}"));
+                }
+                return;
+            }
+            _ if !character.is_control() => entry.push(character),
+            _ => (),
+        }
+        self.set_status_message(format!("Bookmark {slot} name: {entry}"));
+        self.naming_bookmark = Some((slot, entry));
+    }
+
+    /// Glide the camera to the bookmark on a number key.
+    pub fn recall_bookmark(&mut self, slot: u32) {
+        let Some(bookmark) = self.bookmarks.iter().find(|bookmark| bookmark.slot == slot) else {
+            return;
+        };
+        let message = format!("Going to {}", bookmark.name);
+        let to = bookmark.camera(&self.camera);
+        self.set_status_message(message);
+        self.camera_transition = Some(CameraTransition::new(self.camera, to));
+    }
+
+    /// Move the camera along its way to a bookmark.
+    fn update_camera_transition(&mut self) {
+        let Some(transition) = self.camera_transition else {
+            return;
+        };
+        let (camera, arrived) = transition.camera();
+        self.camera = camera;
+        if arrived {
+            self.camera_transition = None;
+        }
+        self.camera_moved();
+    }
+
+    /// Use the current material color as the color the pattern blends towards.
+    pub fn apply_secondary_color(&mut self) {
+        self.pattern.secondary_color = [
+            self.material_color[0].clamp(0.0, 1.0),
+            self.material_color[1].clamp(0.0, 1.0),
+            self.material_color[2].clamp(0.0, 1.0),
+        ];
+        // Old noise picked up from a swatch starts blending like the other patterns.
+        self.pattern.shade = false;
+        self.invalidate_selection_render_cache = true;
+    }
+
+    /// Switch the material to the next texture, going back to untextured after the last one.
+    pub fn cycle_texture(&mut self) {
+        self.texture = (self.texture + 1) % (self.textures.len() as u32 + 1);
+        println!("Texture: {}", self.texture);
+        self.invalidate_selection_render_cache = true;
+    }
+
+    /// Commands to move the fog slider and the fog and background buttons to match the
+    /// current atmosphere.
+    pub fn atmosphere_commands(&self) -> Vec<Command> {
+        let fog_distance =
+            self.atmosphere.fog_distance.clamp(0.0, MAX_FOG_DISTANCE) / MAX_FOG_DISTANCE;
+
+        vec![
+            Command {
+                command_type: CommandType::SetFogDistance,
+                data1: fog_distance.to_bits(),
+                data2: 13,
+            },
+            Command {
+                command_type: CommandType::SetButtonState,
+                data1: VirtualKeyCode::ToggleFog as u32,
+                data2: self.atmosphere.fog as u32,
+            },
+            Command {
+                command_type: CommandType::SetButtonState,
+                data1: VirtualKeyCode::CycleBackground as u32,
+                data2: self.atmosphere.background_index() as u32,
+            },
+        ]
+    }
+
+    /// Commands to move the lighting sliders and buttons to match the current lighting.
+    pub fn lighting_commands(&self) -> Vec<Command> {
+        let azimuth = self.lighting.azimuth.to_degrees().rem_euclid(360.0) / 360.0;
+        let elevation = self.lighting.elevation.to_degrees().clamp(0.0, 90.0) / 90.0;
+        let intensity = self.lighting.intensity.clamp(0.0, 2.0) / 2.0;
+        let ambient = self.lighting.ambient.clamp(0.0, 1.0);
+        let shadow_bias = (self.lighting.shadow_bias * 10000.0).clamp(0.0, 50.0) / 50.0;
+        let shadow_softness = self.lighting.shadow_softness.min(MAX_SHADOW_SOFTNESS) as f32
+            / MAX_SHADOW_SOFTNESS as f32;
+
+        vec![
+            Command {
+                command_type: CommandType::SetLightAzimuth,
+                data1: azimuth.to_bits(),
+                data2: 4,
+            },
+            Command {
+                command_type: CommandType::SetLightElevation,
+                data1: elevation.to_bits(),
+                data2: 5,
+            },
+            Command {
+                command_type: CommandType::SetLightIntensity,
+                data1: intensity.to_bits(),
+                data2: 6,
+            },
+            Command {
+                command_type: CommandType::SetAmbientLight,
+                data1: ambient.to_bits(),
+                data2: 7,
+            },
+            Command {
+                command_type: CommandType::SetShadowBias,
+                data1: shadow_bias.to_bits(),
+                data2: 11,
+            },
+            Command {
+                command_type: CommandType::SetShadowSoftness,
+                data1: shadow_softness.to_bits(),
+                data2: 12,
+            },
+            // The first state of the toggle buttons shows the setting on.
+            Command {
+                command_type: CommandType::SetButtonState,
+                data1: VirtualKeyCode::ToggleShadows as u32,
+                data2: !self.lighting.shadows as u32,
+            },
+            Command {
+                command_type: CommandType::SetButtonState,
+                data1: VirtualKeyCode::ToggleAmbientOcclusion as u32,
+                data2: !self.lighting.ambient_occlusion as u32,
+            },
+            Command {
+                command_type: CommandType::SetButtonState,
+                data1: VirtualKeyCode::CycleLightingPreset as u32,
+                data2: self.lighting_preset as u32,
+            },
+        ]
+    }
+
+    /// Use the material in a palette swatch, moving the sliders and buttons to match.
+    pub fn apply_swatch(&mut self, index: usize) -> Vec<Command> {
+        let Some(material) = self.palette.materials.get(index).cloned() else {
+            return Vec::new();
+        };
+        self.material_color = material.upscale_color();
+        self.pattern = material.pattern_settings();
+        self.fluid = material.fluid != 0;
+        self.fluid_settings = material.fluid_settings();
+        self.emissive = material.upscale_emissive();
+        self.roughness = material.upscale_roughness();
+        self.metalness = material.upscale_metalness();
+        // A swatch kept from another scene may name a texture this scene does not have.
+        self.texture = if material.texture as usize <= self.textures.len() {
+            material.texture
+        } else {
+            0
+        };
+        self.selection_cube.color = [
+            self.material_color[0],
+            self.material_color[1],
+            self.material_color[2],
+            0.5,
+        ];
+        self.invalidate_selection_render_cache = true;
+        self.material_commands()
+    }
+
+    /// Keep the current material in a swatch, or in a new swatch after the last one
+    /// when the index is past the end.
+    pub fn store_swatch(&mut self, index: usize) {
+        let material = self.current_material();
+        match self.palette.materials.get_mut(index) {
+            Some(swatch) => *swatch = material,
+            None => self.palette.materials.push(material),
+        }
+        self.palette_edited();
+    }
+
+    /// Take a swatch out of the palette.
+    pub fn remove_swatch(&mut self, index: usize) {
+        if index < self.palette.materials.len() {
+            self.palette.materials.remove(index);
+            self.palette_edited();
+        }
+    }
+
+    /// Switch the palette between every material in the scene, the most used first,
+    /// and the user's own palette.
+    pub fn palette_from_scene(&mut self) {
+        if let Some(palette) = self.user_palette.take() {
+            self.palette = palette;
+            self.palette_changed = true;
+            self.unsaved_changes = true;
+            self.set_status_message(String::from("Back to your own palette"));
+            return;
+        }
+        let materials = self
+            .model
+            .material_usage()
+            .into_iter()
+            .map(|(material, _)| material)
+            .collect();
+        self.show_scene_palette(MaterialPalette { materials });
+        self.unsaved_changes = true;
+        self.set_status_message(format!(
+            "Found {} materials in the scene",
+            self.palette.materials.len()
+        ));
+    }
+
+    /// Count every voxel's material again, after the whole model changes.
+    fn recount_material_usage(&mut self) {
+        self.material_usage = Some(self.model.material_usage().into_iter().collect());
+        self.palette_changed = true;
+    }
+
+    /// Move voxels from the materials they had to the ones they have now in the usage
+    /// counts shown on the swatches.
+    fn update_material_usage(&mut self, before: Vec<Material>, after: Vec<Material>) {
+        let usage = self
+            .material_usage
+            .as_mut()
+            .expect("Material usage should be initialized");
+        for material in before {
+            if let Some(count) = usage.get_mut(&material) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        for material in after {
+            *usage.entry(material).or_default() += 1;
+        }
+        usage.retain(|_, count| *count > 0);
+        self.palette_changed = true;
+    }
+
+    /// Select every voxel painted with the material in a swatch, in place of the
+    /// selection shape.
+    pub fn select_swatch_voxels(&mut self, index: usize) {
+        let Some(material) = self.palette.materials.get(index) else {
+            return;
+        };
+        let voxels = self.model.voxels_with_material(material);
+        self.set_status_message(format!("Selected {} voxels", voxels.len()));
+        self.material_selection = if voxels.is_empty() {
+            None
+        } else {
+            Some(voxels)
+        };
+        self.invalidate_selection_render_cache = true;
+    }
+
+    /// Keep the palette for the next session and mark the scene, which saves it too.
+    /// A palette from a scene is only saved with the scene, leaving the user's own alone.
+    fn palette_edited(&mut self) {
+        if self.user_palette.is_none() {
+            self.palette.save(&config_path(PALETTE_FILE));
+        }
+        self.palette_changed = true;
+        self.unsaved_changes = true;
+    }
+
+    /// Show a palette that came from a scene, putting the user's own palette aside.
+    fn show_scene_palette(&mut self, palette: MaterialPalette) {
+        let shown = std::mem::replace(&mut self.palette, palette);
+        if self.user_palette.is_none() {
+            self.user_palette = Some(shown);
+        }
+        self.palette_changed = true;
+    }
+
+    /// Commands to move the material sliders and buttons to match the current material.
+    fn material_commands(&self) -> Vec<Command> {
+        let mut commands: Vec<Command> = [
+            CommandType::SetMaterialRed,
+            CommandType::SetMaterialGreen,
+            CommandType::SetMaterialBlue,
+            CommandType::SetMaterialAlpha,
+        ]
+        .iter()
+        .zip(self.material_color)
+        .enumerate()
+        .map(|(slider, (command_type, value))| Command {
+            command_type: *command_type,
+            data1: value.clamp(0.0, 1.0).to_bits(),
+            data2: slider as u32,
+        })
+        .collect();
+
+        // The slider index and its position from 0 to 1. The scale sliders count
+        // 32nds up to 8 and the fluid speed slider counts tenths up to 255.
+        let settings = [
+            (8, self.emissive),
+            (9, self.roughness),
+            (10, self.metalness),
+            (14, self.pattern.scale / 8.0),
+            (15, self.pattern.contrast),
+            (16, self.fluid_settings.speed * 10.0 / 255.0),
+            (17, self.fluid_settings.wave_scale / 8.0),
+            (18, self.fluid_settings.color_shift),
+            (19, self.fluid_settings.displacement),
+        ];
+        commands.extend(settings.iter().map(|(slider, value)| Command {
+            command_type: CommandType::SetMaterialSetting,
+            data1: value.clamp(0.0, 1.0).to_bits(),
+            data2: *slider,
+        }));
+        commands.push(Command {
+            command_type: CommandType::SetButtonState,
+            data1: VirtualKeyCode::ToggleNoise as u32,
+            data2: self.pattern.pattern.index() as u32,
+        });
+        commands.push(Command {
+            command_type: CommandType::SetButtonState,
+            data1: VirtualKeyCode::ToggleFluid as u32,
+            data2: self.fluid as u32,
+        });
+        commands
+    }
+
     /// Enable fluid.
     pub fn toggle_fluid(&mut self) {
         self.fluid = !self.fluid;
@@ -971,7 +2147,13 @@ impl Scene {
     pub fn init(&mut self) {
         self.render_cache = Some(HashMap::new());
         self.selection_vertices_cache = Some(Vec::new());
-        self.light.eye = Point3::new(60.0, 60.0, 60.0);
+        self.viewports = Viewport::defaults();
+        self.key_bindings.load(&config_path(KEY_BINDINGS_FILE));
+        if let Some(palette) = MaterialPalette::load(&config_path(PALETTE_FILE)) {
+            self.palette = palette;
+        }
+        self.palette_changed = true;
+        self.light.eye = self.lighting.position();
         self.light.target = Point3::new(0.0, 0.0, 0.0);
         self.selection_cube.scale = 0.8f32;
         self.selection_cube.color = [0.8, 0.8, 0.8, 0.5];
@@ -980,6 +2162,8 @@ impl Scene {
         self.grid_xz.rotate([90.0_f32.to_radians(), 0.0, 0.0]);
 
         self.model.init();
+        self.voxel_count = self.model.voxel_count();
+        self.recount_material_usage();
         self.start_time = Some(Instant::now());
 
         self.print_keyboard_bindings();
@@ -1166,6 +2350,63 @@ impl Scene {
         voxels
     }
 
+    /// Find the box that holds all of the cubes, or None if there are none.
+    fn bounds(cubes: &[Cube]) -> Option<([f32; 3], [f32; 3])> {
+        let first = cubes.first()?;
+        let mut min = first.translation;
+        let mut max = first.translation;
+        for cube in cubes {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(cube.translation[axis]);
+                max[axis] = max[axis].max(cube.translation[axis] + cube.scale);
+            }
+        }
+        Some((min, max))
+    }
+
+    /// Sort every translucent triangle from furthest to nearest the camera, then group
+    /// neighbouring triangles that share a material so they can be drawn together.
+    /// Overlapping glass and water blend correctly as long as triangles don't intersect.
+    fn sort_translucent_batches(
+        render_cache: &HashMap<Material, HashMap<[i32; 3], RenderChunk>>,
+        camera_eye: [f32; 3],
+        max_alpha: i32,
+    ) -> Vec<(Material, Vec<Vertex>)> {
+        let mut triangles: Vec<(f32, &Material, &[Vertex])> = Vec::new();
+        for (material, chunks) in render_cache.iter() {
+            if material.color[3] > max_alpha {
+                continue;
+            }
+            let triangles_in_material = chunks
+                .values()
+                .flat_map(|chunk| chunk.vertices.chunks_exact(3));
+            for triangle in triangles_in_material {
+                let distance: f32 = camera_eye
+                    .iter()
+                    .enumerate()
+                    .map(|(axis, eye)| {
+                        let center = (triangle[0].position[axis]
+                            + triangle[1].position[axis]
+                            + triangle[2].position[axis])
+                            / 3.0;
+                        (center - eye).powi(2)
+                    })
+                    .sum();
+                triangles.push((distance, material, triangle));
+            }
+        }
+        triangles.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut batches: Vec<(Material, Vec<Vertex>)> = Vec::new();
+        for (_, material, triangle) in triangles {
+            match batches.last_mut() {
+                Some((last, vertices)) if last == material => vertices.extend_from_slice(triangle),
+                _ => batches.push((material.clone(), triangle.to_vec())),
+            }
+        }
+        batches
+    }
+
     /// Draw the scene.
     pub fn draw(
         &mut self,
@@ -1180,6 +2421,7 @@ impl Scene {
             .elapsed()
             .as_secs_f32()
             * animation_speed;
+        self.update_camera_transition();
 
         if self.invalidate_render_cache
             || self.invalidate_selection_render_cache
@@ -1197,15 +2439,18 @@ impl Scene {
                 self.invalidate_selection_render_cache = false;
                 self.selection_vertices_cache.as_mut().unwrap().clear();
 
-                for selection in &Self::selection_voxels(
-                    &self.selection_position,
-                    self.selection_radius as i32,
-                    self.selection_shape,
-                ) {
+                // Voxels picked by material are filled, so wrap the selection around them.
+                let (scale, offset) = if self.material_selection.is_some() {
+                    (1.1, -0.05)
+                } else {
+                    (0.8, 0.1)
+                };
+                self.selection_cube.scale = scale;
+                for selection in &self.selected_voxels() {
                     self.selection_cube.translation = [
-                        selection[0] as f32 + 0.1,
-                        selection[1] as f32 + 0.1,
-                        selection[2] as f32 + 0.1,
+                        selection[0] as f32 + offset,
+                        selection[1] as f32 + offset,
+                        selection[2] as f32 + offset,
                     ];
 
                     let vertices = self.selection_cube.vertices_world();
@@ -1230,20 +2475,18 @@ impl Scene {
                     b_dist.partial_cmp(&a_dist).unwrap()
                 });
                 self.drawables_cache = drawables;
-                self.drawables_cache = self.model.drawables();
+                self.point_lights = PointLight::from_cubes(&self.drawables_cache);
+                self.shadow_bounds = Self::bounds(&self.drawables_cache);
             }
 
             if self.invalidate_render_cache || self.invalidate_render_material.is_some() {
                 for voxel in self.drawables_cache.iter() {
                     let vertices = voxel.vertices_world();
-                    let material = Material::new(
-                        voxel.color,
-                        voxel.noise,
-                        voxel.noise_x,
-                        voxel.noise_y,
-                        voxel.noise_z,
-                        voxel.fluid,
-                    );
+                    let material =
+                        Material::new(voxel.color, &voxel.pattern, voxel.fluid, voxel.emissive)
+                            .with_surface(voxel.roughness, voxel.metalness)
+                            .with_texture(voxel.texture)
+                            .with_fluid(&voxel.fluid_settings);
                     if self.invalidate_render_material.is_none()
                         || self.invalidate_render_material.as_ref().unwrap() == &material
                     {
@@ -1252,11 +2495,16 @@ impl Scene {
                             .as_mut()
                             .expect("Render cache should be initialized")
                             .entry(material)
-                            .or_insert_with(Vec::new)
-                            .extend(vertices);
+                            .or_default()
+                            .entry(RenderChunk::key(voxel))
+                            .or_insert_with(RenderChunk::new)
+                            .add(voxel, vertices);
                     }
                 }
             }
+            if self.invalidate_render_cache || self.invalidate_render_material.is_some() {
+                self.translucent_sort_eyes.clear();
+            }
             self.invalidate_render_cache = false;
             self.invalidate_render_material = None;
         }
@@ -1264,25 +2512,24 @@ impl Scene {
         let opaque = 255;
         let tolerance = 10;
 
+        self.light.eye = self.lighting.position();
+        graphics.lighting = self.lighting;
+        self.aspect = graphics.canvas_width as f32 / graphics.canvas_height as f32;
+        graphics.shadow_bounds = self.shadow_bounds;
+        graphics.set_atmosphere(display, &self.atmosphere);
+        graphics.set_texture_atlas(display, &self.textures);
         graphics.prepare_shadow_frame();
-        // Render shadows
-        for material in self
+        // Render shadows. Chunks off screen can still cast shadows onto the screen.
+        for (material, chunks) in self
             .render_cache
             .as_ref()
             .expect("Render cache should be initialized")
-            .keys()
+            .iter()
         {
-            if material.color[3] > (opaque - tolerance) {
-                // Process each material here
-                graphics.draw_shadow_vertices(
-                    display,
-                    self.render_cache
-                        .as_ref()
-                        .expect("Render cache should be initialized")
-                        .get(material)
-                        .unwrap(),
-                    self.light,
-                );
+            if self.lighting.shadows && material.color[3] > (opaque - tolerance) {
+                for chunk in chunks.values() {
+                    graphics.draw_shadow_vertices(display, &chunk.vertices, self.light);
+                }
             }
         }
 
@@ -1291,47 +2538,79 @@ impl Scene {
             self.start_time = Some(Instant::now());
         }
         graphics.prepare_camera_frame(frame);
+        self.store_active_viewport();
+        let count = self.layout.count();
+        self.translucent_batches.resize_with(count, Vec::new);
+        self.translucent_sort_eyes.resize(count, None);
+        for index in 0..count {
+            graphics.set_viewport(self.layout.area(index));
+            self.draw_viewport(display, frame, graphics, index);
+        }
+        graphics.finish_camera_frame();
 
-        // Render opaques.
-        for material in self
+        // We are only rendering when idle, so we can skip the throttling.
+        // Continuous rendering is needed to animate the fluid.
+        //scene.dirty = false;
+    }
+
+    /// Draw the scene from one viewport's camera into its part of the window.
+    fn draw_viewport(
+        &mut self,
+        display: &Display<WindowSurface>,
+        frame: &mut Frame,
+        graphics: &mut Graphics,
+        index: usize,
+    ) {
+        let opaque = 255;
+        let tolerance = 10;
+        let camera = self.viewports[index].camera;
+        let camera_eye = [camera.eye.x, camera.eye.y, camera.eye.z];
+        graphics.projection = self.viewports[index].projection;
+        graphics.set_point_lights(&PointLight::nearest(&self.point_lights, camera_eye));
+        graphics.draw_background(frame, camera);
+
+        // Render opaques, skipping the chunks that are off screen.
+        let frustum = Self::camera_frustum(&camera, graphics);
+        for (material, chunks) in self
             .render_cache
             .as_ref()
             .expect("Render cache should be initialized")
-            .keys()
+            .iter()
         {
             if material.color[3] > (opaque - tolerance) {
-                // Process each material here
-                graphics.draw_vertices(
-                    display,
-                    frame,
-                    material,
-                    self.render_cache
-                        .as_ref()
-                        .expect("Render cache should be initialized")
-                        .get(material)
-                        .unwrap(),
-                    self.camera,
-                    self.light,
-                    self.elapsed,
-                );
+                for chunk in chunks.values() {
+                    if !frustum.intersects_box(chunk.min, chunk.max) {
+                        continue;
+                    }
+                    graphics.draw_vertices(
+                        display,
+                        frame,
+                        material,
+                        &chunk.vertices,
+                        camera,
+                        self.light,
+                        self.elapsed,
+                    );
+                }
             }
         }
 
         let material = Material::new(
             self.material_color,
-            self.noise as i32,
-            self.noise_x as i32,
-            self.noise_y as i32,
-            self.noise_z as i32,
+            &self.pattern,
             self.fluid as i32,
-        );
+            self.emissive,
+        )
+        .with_surface(self.roughness, self.metalness)
+        .with_texture(self.texture)
+        .with_fluid(&self.fluid_settings);
 
         graphics.draw_vertices(
             display,
             frame,
             &material,
             self.selection_vertices_cache.as_mut().expect("Some"),
-            self.camera,
+            camera,
             self.light,
             self.elapsed,
         );
@@ -1341,41 +2620,117 @@ impl Scene {
                 display,
                 frame,
                 &self.grid_xz,
-                self.camera,
+                camera,
                 self.light,
                 self.elapsed,
             );
         }
 
-        // Render translucents.
-        for material in self
-            .render_cache
-            .as_ref()
-            .expect("Render cache should be initialized")
-            .keys()
-        {
-            // Process each material here
-            if material.color[3] <= (opaque - tolerance) {
-                graphics.draw_vertices(
-                    display,
-                    frame,
-                    material,
-                    self.render_cache
-                        .as_ref()
-                        .expect("Render cache should be initialized")
-                        .get(material)
-                        .unwrap(),
-                    self.camera,
-                    self.light,
-                    self.elapsed,
-                );
+        // Render translucents from the back to the front.
+        if self.translucent_sort_eyes[index] != Some(camera_eye) {
+            self.translucent_batches[index] = Self::sort_translucent_batches(
+                self.render_cache
+                    .as_ref()
+                    .expect("Render cache should be initialized"),
+                camera_eye,
+                opaque - tolerance,
+            );
+            self.translucent_sort_eyes[index] = Some(camera_eye);
+        }
+        for (material, vertices) in self.translucent_batches[index].iter() {
+            graphics.draw_vertices(
+                display,
+                frame,
+                material,
+                vertices,
+                camera,
+                self.light,
+                self.elapsed,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::PatternSettings;
+
+    /// A small triangle centred on a point.
+    fn triangle_at(center: [f32; 3]) -> Vec<Vertex> {
+        [[-0.1, -0.1], [0.1, -0.1], [0.0, 0.2]]
+            .iter()
+            .map(|[x, y]| Vertex {
+                position: [center[0] + x, center[1] + y, center[2]],
+                normal: [0.0, 0.0, 1.0],
+                occlusion: 1.0,
+                tex_coords: [0.0, 0.0],
+                sink: 0.0,
+            })
+            .collect()
+    }
+
+    fn material(red: f32, alpha: f32) -> Material {
+        Material::new([red, 0.0, 0.0, alpha], &PatternSettings::new(), 0, 0.0)
+    }
+
+    /// One chunk per material, holding a triangle at each of the centers.
+    fn render_cache(
+        materials: &[(Material, Vec<[f32; 3]>)],
+    ) -> HashMap<Material, HashMap<[i32; 3], RenderChunk>> {
+        let mut cache: HashMap<Material, HashMap<[i32; 3], RenderChunk>> = HashMap::new();
+        for (material, centers) in materials {
+            let mut chunk = RenderChunk::new();
+            for center in centers {
+                chunk.vertices.extend(triangle_at(*center));
             }
+            cache
+                .entry(material.clone())
+                .or_default()
+                .insert([0, 0, 0], chunk);
         }
+        cache
+    }
 
-        graphics.finish_camera_frame();
+    #[test]
+    fn translucent_triangles_are_sorted_back_to_front() {
+        let glass = material(1.0, 0.5);
+        let water = material(0.0, 0.5);
+        let stone = material(0.5, 1.0);
+        let cache = render_cache(&[
+            (glass.clone(), vec![[0.0, 0.0, -2.0], [0.0, 0.0, -10.0]]),
+            (water.clone(), vec![[0.0, 0.0, -5.0]]),
+            (stone, vec![[0.0, 0.0, -20.0]]),
+        ]);
+
+        let batches = Scene::sort_translucent_batches(&cache, [0.0, 0.0, 0.0], 245);
+
+        let order: Vec<(Material, f32)> = batches
+            .iter()
+            .flat_map(|(material, vertices)| {
+                vertices
+                    .chunks_exact(3)
+                    .map(move |triangle| (material.clone(), triangle[0].position[2]))
+            })
+            .collect();
+        assert_eq!(
+            order,
+            vec![(glass.clone(), -10.0), (water, -5.0), (glass, -2.0)]
+        );
+    }
 
-        // We are only rendering when idle, so we can skip the throttling.
-        // Continuous rendering is needed to animate the fluid.
-        //scene.dirty = false;
+    #[test]
+    fn translucent_triangles_follow_the_eye() {
+        let glass = material(1.0, 0.5);
+        let water = material(0.0, 0.5);
+        let cache = render_cache(&[
+            (glass.clone(), vec![[0.0, 0.0, -2.0]]),
+            (water.clone(), vec![[0.0, 0.0, -5.0]]),
+        ]);
+
+        let batches = Scene::sort_translucent_batches(&cache, [0.0, 0.0, -10.0], 245);
+
+        let materials: Vec<Material> = batches.into_iter().map(|(material, _)| material).collect();
+        assert_eq!(materials, vec![glass, water]);
     }
 }
@@ -1,5 +1,6 @@
 use crate::canvas::Canvas;
 use crate::command::{Command, CommandType};
+use crate::icon_cache::IconCache;
 use glium::Frame;
 use glium::backend::glutin::Display;
 use glutin::surface::WindowSurface;
@@ -11,6 +12,8 @@ pub struct Slider {
     pub current_value: usize,
     pub range: (usize, usize),
     pub slider_index: u32,
+    /// Turns the current value into the text shown above the slider.
+    pub format: fn(usize) -> String,
 }
 
 impl Slider {
@@ -29,15 +32,22 @@ impl Slider {
             current_value,
             range,
             slider_index,
+            format: |value| value.to_string(),
         }
     }
+
+    /// Show the value in the units of the setting it controls instead of slider steps.
+    pub fn with_format(mut self, format: fn(usize) -> String) -> Self {
+        self.format = format;
+        self
+    }
 }
 
 use crate::widget::Widget;
 
 impl Widget for Slider {
-    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame) {
-        let mut canvas = Canvas::new(display, frame);
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache) {
+        let mut canvas = Canvas::new(display, frame, icons);
 
         let border_color = [0.1, 0.1, 0.1, 0.8];
         let color = self.background_color;
@@ -56,6 +66,25 @@ impl Widget for Slider {
             0.01,
             [0.1, 0.1, 0.1, 0.8],
         );
+
+        // Show the value centred above the slider.
+        let value = (self.format)(self.current_value);
+        let text_height = 0.04;
+        let width = canvas.text_width(text_height, &value);
+        canvas.draw_text(
+            (
+                self.position.0 + (self.size.0 - width) / 2.0,
+                self.position.1 + self.size.1 + 0.005,
+            ),
+            text_height,
+            &value,
+            [1.0, 1.0, 1.0, 0.9],
+        );
+    }
+
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32)) {
+        self.position = position;
+        self.size = size;
     }
 
     fn process_command(&mut self, command: &Command) -> Vec<Command> {
@@ -137,6 +166,21 @@ impl Widget for Slider {
                     self.current_value = new_value as usize;
                 }
             }
+            CommandType::SetLightAzimuth
+            | CommandType::SetLightElevation
+            | CommandType::SetLightIntensity
+            | CommandType::SetAmbientLight
+            | CommandType::SetShadowBias
+            | CommandType::SetShadowSoftness
+            | CommandType::SetFogDistance
+            | CommandType::SetMaterialSetting
+                if command.data2 == self.slider_index =>
+            {
+                let percentage = f32::from_bits(command.data1);
+                let new_value =
+                    percentage * (self.range.1 - self.range.0) as f32 + self.range.0 as f32;
+                self.current_value = new_value as usize;
+            }
             _ => (),
         }
         translated_commands
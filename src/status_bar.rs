@@ -0,0 +1,129 @@
+use crate::canvas::Canvas;
+use crate::command::{Command, CommandType, MATERIAL_FLUID, unpack_color};
+use crate::icon_cache::IconCache;
+use crate::pattern::Pattern;
+use crate::scene::SelectionShape;
+use glium::Frame;
+use glium::backend::glutin::Display;
+use glutin::surface::WindowSurface;
+
+/// A strip showing the selection, material, voxel count and frame rate. It keeps up to
+/// date from the commands the scene sends when any of them change.
+pub struct StatusBar {
+    pub position: (f32, f32),
+    pub size: (f32, f32),
+    selection_position: [i32; 3],
+    selection_radius: u32,
+    selection_shape: SelectionShape,
+    material_color: [f32; 4],
+    pattern: Pattern,
+    fluid: bool,
+    voxel_count: u32,
+    unsaved_changes: bool,
+    frames_per_second: f32,
+}
+
+impl StatusBar {
+    pub fn new(position: (f32, f32), size: (f32, f32)) -> Self {
+        StatusBar {
+            position,
+            size,
+            selection_position: [0, 0, 0],
+            selection_radius: 1,
+            selection_shape: SelectionShape::Sphere,
+            material_color: [0.8, 0.8, 0.8, 1.0],
+            pattern: Pattern::Solid,
+            fluid: false,
+            voxel_count: 0,
+            unsaved_changes: false,
+            frames_per_second: 0.0,
+        }
+    }
+
+    /// Everything the bar shows, as one line.
+    fn text(&self) -> String {
+        let [x, y, z] = self.selection_position;
+        let [red, green, blue, alpha] = self
+            .material_color
+            .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        let fluid = if self.fluid { " fluid" } else { "" };
+        let saved = if self.unsaved_changes {
+            "Unsaved changes"
+        } else {
+            "Saved"
+        };
+        format!(
+            "{x}, {y}, {z} | Radius {} | {:?} | #{red:02X}{green:02X}{blue:02X}{alpha:02X} {:?}{fluid} | {} voxels | {:.1} fps | {saved}",
+            self.selection_radius,
+            self.selection_shape,
+            self.pattern,
+            self.voxel_count,
+            self.frames_per_second,
+        )
+    }
+}
+
+use crate::widget::Widget;
+
+impl Widget for StatusBar {
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache) {
+        let mut canvas = Canvas::new(display, frame, icons);
+
+        canvas.draw_rectangle(self.position, self.size, [0.1, 0.1, 0.1, 0.6]);
+        let padding = self.size.1 * 0.15;
+        let text_height = self.size.1 - padding * 2.0;
+
+        // A little swatch of the material before the text.
+        canvas.draw_rectangle(
+            (self.position.0 + padding, self.position.1 + padding),
+            (text_height * 0.6, text_height),
+            self.material_color,
+        );
+        canvas.draw_text(
+            (
+                self.position.0 + padding * 2.0 + text_height * 0.6,
+                self.position.1 + padding,
+            ),
+            text_height,
+            &self.text(),
+            [1.0, 1.0, 1.0, 1.0],
+        );
+    }
+
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32)) {
+        self.position = position;
+        self.size = size;
+    }
+
+    fn process_command(&mut self, command: &Command) -> Vec<Command> {
+        match command.command_type {
+            CommandType::SelectionPositionChanged => {
+                if let Some(position) = self.selection_position.get_mut(command.data1 as usize) {
+                    *position = command.data2 as i32;
+                }
+            }
+            CommandType::SelectionSizeChanged => {
+                self.selection_radius = command.data1;
+            }
+            CommandType::SelectionShapeChanged => {
+                self.selection_shape = SelectionShape::from_index(command.data1);
+            }
+            CommandType::MaterialChanged => {
+                self.material_color = unpack_color(command.data1);
+                self.pattern = Pattern::from_index(command.data2 & !MATERIAL_FLUID);
+                self.fluid = command.data2 & MATERIAL_FLUID != 0;
+            }
+            CommandType::VoxelCountChanged => {
+                self.voxel_count = command.data1;
+            }
+            CommandType::UnsavedChangesChanged => {
+                self.unsaved_changes = command.data1 == 1;
+            }
+            CommandType::FramesPerSecond => {
+                self.frames_per_second = f32::from_bits(command.data1);
+            }
+            _ => (),
+        }
+        Vec::new()
+    }
+}
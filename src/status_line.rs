@@ -0,0 +1,49 @@
+use crate::canvas::Canvas;
+use crate::command::Command;
+use crate::icon_cache::IconCache;
+use glium::Frame;
+use glium::backend::glutin::Display;
+use glutin::surface::WindowSurface;
+
+/// A strip along the top of the window describing what the scene is doing.
+pub struct StatusLine {
+    pub position: (f32, f32),
+    pub size: (f32, f32),
+    pub text: String,
+}
+
+impl StatusLine {
+    pub const fn new(position: (f32, f32), size: (f32, f32)) -> Self {
+        StatusLine {
+            position,
+            size,
+            text: String::new(),
+        }
+    }
+}
+
+use crate::widget::Widget;
+
+impl Widget for StatusLine {
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache) {
+        let mut canvas = Canvas::new(display, frame, icons);
+
+        canvas.draw_rectangle(self.position, self.size, [0.1, 0.1, 0.1, 0.6]);
+        let padding = self.size.1 * 0.15;
+        canvas.draw_text(
+            (self.position.0 + padding, self.position.1 + padding),
+            self.size.1 - padding * 2.0,
+            &self.text,
+            [1.0, 1.0, 1.0, 1.0],
+        );
+    }
+
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32)) {
+        self.position = position;
+        self.size = size;
+    }
+
+    fn process_command(&mut self, _command: &Command) -> Vec<Command> {
+        Vec::new()
+    }
+}
@@ -1,8 +1,28 @@
+use crate::atmosphere::Atmosphere;
+use crate::bookmark::Bookmark;
+use crate::lighting::Lighting;
+use crate::material_palette::MaterialPalette;
 use crate::ocnode::Ocnode;
+use crate::texture_atlas::TextureSet;
 use serde::{Deserialize, Serialize};
 
 /// Used to serialize a scene.
 #[derive(Serialize, Deserialize)]
 pub struct StoredOctree {
     pub active_nodes: Vec<Ocnode>,
+    /// Scenes saved before lighting was configurable get the default lighting.
+    #[serde(default)]
+    pub lighting: Lighting,
+    /// Scenes saved before the background was configurable get the default atmosphere.
+    #[serde(default)]
+    pub atmosphere: Atmosphere,
+    /// The images used by textured voxels.
+    #[serde(default)]
+    pub textures: Vec<TextureSet>,
+    /// The camera positions saved with the scene.
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    /// The palette the scene was painted with.
+    #[serde(default)]
+    pub palette: MaterialPalette,
 }
@@ -1,5 +1,6 @@
 use crate::canvas::Canvas;
 use crate::command::{Command, CommandType};
+use crate::icon_cache::IconCache;
 use glium::Frame;
 use glium::backend::glutin::Display;
 use glutin::surface::WindowSurface;
@@ -23,8 +24,8 @@ impl Swatch {
 use crate::widget::Widget;
 
 impl Widget for Swatch {
-    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame) {
-        let mut canvas = Canvas::new(display, frame);
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache) {
+        let mut canvas = Canvas::new(display, frame, icons);
 
         let border_color = [0.1, 0.1, 0.1, 0.8];
         let border = 0.01;
@@ -37,6 +38,11 @@ impl Widget for Swatch {
         );
     }
 
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32)) {
+        self.position = position;
+        self.size = size;
+    }
+
     fn process_command(&mut self, command: &Command) -> Vec<Command> {
         let translated_commands = Vec::new();
 
@@ -0,0 +1,71 @@
+use image::RgbaImage;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+
+/// Every texture is scaled to a square tile this many pixels wide.
+pub const TILE_SIZE: u32 = 32;
+
+/// How many tiles fit across the atlas. Each texture set uses three tiles in a row.
+pub const ATLAS_COLUMNS: u32 = 12;
+
+/// The images used on the faces of a textured voxel.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextureSet {
+    /// PNG used on the top face.
+    pub top: String,
+    /// PNG used on the four side faces.
+    pub side: String,
+    /// PNG used on the bottom face.
+    pub bottom: String,
+}
+
+impl TextureSet {
+    /// Build a texture set from a list of PNG files. Files with top, side or bottom
+    /// in their name are used for those faces and the first file fills any gaps.
+    pub fn from_files(paths: &[String]) -> Option<TextureSet> {
+        let first = paths.first()?;
+        let find = |face: &str| {
+            paths
+                .iter()
+                .find(|path| path.to_lowercase().contains(face))
+                .unwrap_or(first)
+                .clone()
+        };
+        Some(TextureSet {
+            top: find("top"),
+            side: find("side"),
+            bottom: find("bottom"),
+        })
+    }
+}
+
+/// Pack every texture set into one image so all textured voxels share a single texture.
+/// Texture set n uses tiles 3n (top), 3n + 1 (side) and 3n + 2 (bottom), counting across the rows.
+pub fn build_atlas(sets: &[TextureSet]) -> RgbaImage {
+    let tiles = (sets.len() as u32 * 3).max(1);
+    let rows = tiles.div_ceil(ATLAS_COLUMNS);
+    let mut atlas = RgbaImage::from_pixel(
+        ATLAS_COLUMNS * TILE_SIZE,
+        rows * TILE_SIZE,
+        image::Rgba([255, 255, 255, 255]),
+    );
+
+    for (index, set) in sets.iter().enumerate() {
+        for (face, path) in [&set.top, &set.side, &set.bottom].iter().enumerate() {
+            let tile = index as u32 * 3 + face as u32;
+            let x = (tile % ATLAS_COLUMNS) * TILE_SIZE;
+            let y = (tile / ATLAS_COLUMNS) * TILE_SIZE;
+            match image::open(path) {
+                Ok(image) => {
+                    // Nearest keeps pixel art crisp.
+                    let tile_image =
+                        image::imageops::resize(&image, TILE_SIZE, TILE_SIZE, FilterType::Nearest);
+                    image::imageops::replace(&mut atlas, &tile_image, x as i64, y as i64);
+                }
+                Err(error) => log::error!("Could not load the texture {path}: {error}"),
+            }
+        }
+    }
+
+    atlas
+}
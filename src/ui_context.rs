@@ -1,27 +1,68 @@
 use crate::button::Button;
+use crate::canvas::Canvas;
+use crate::color_picker::ColorPicker;
 use crate::command::Command;
 use crate::command_queue::CommandQueue;
+use crate::icon_cache::IconCache;
 use crate::palette::Palette;
 use crate::slider::Slider;
+use crate::status_bar::StatusBar;
+use crate::status_line::StatusLine;
 use crate::swatch::Swatch;
 use crate::widget::Widget;
-use crate::key_bindings::VirtualKeyCode;
+use crate::key_bindings::{Action, VirtualKeyCode};
+use crate::ui_layout::{Anchor, Placement, Row};
 
 use glium::Frame;
 use glium::backend::glutin::Display;
 use glutin::surface::WindowSurface;
 
+// Sizes in logical pixels, so the UI keeps its shape whatever the window's size and scale.
+/// The gap between the edge of the window and the widgets.
+const MARGIN: f32 = 8.0;
+/// The gap between neighbouring widgets.
+const SPACING: f32 = 4.0;
+const BUTTON: (f32, f32) = (44.0, 44.0);
+/// How far apart the rows of the toolbar are.
+const ROW_HEIGHT: f32 = BUTTON.1 + SPACING;
+/// Reaches through all three rows of the toolbar.
+const TALL_SLIDER: (f32, f32) = (20.0, BUTTON.1 * 3.0 + SPACING * 2.0);
+/// Reaches through the top two rows of the toolbar.
+const SLIDER: (f32, f32) = (20.0, BUTTON.1 * 2.0 + SPACING);
+const PALETTE: (f32, f32) = (176.0, BUTTON.1);
+/// The toolbar along the bottom, with room for the values above the sliders.
+const TOOLBAR_HEIGHT: f32 = MARGIN + TALL_SLIDER.1 + 24.0;
+const STATUS_HEIGHT: f32 = 36.0;
+const STATUS_LINE: Placement = Placement::new(Anchor::Top, (0.0, 0.0), (0.0, STATUS_HEIGHT));
+
 pub struct UiContext {
-    widgets: Vec<Box<dyn Widget>>,
+    /// Each widget with where it goes in the window.
+    widgets: Vec<(Placement, Box<dyn Widget>)>,
+    /// Backgrounds behind groups of widgets. The scene leaves the mouse alone over them.
+    panels: Vec<Placement>,
+    /// Drawn across the top of the window, on top of the scene.
+    status_line: StatusLine,
+    /// The window size in physical pixels.
+    window_size: (u32, u32),
+    /// Physical pixels per logical pixel.
+    scale_factor: f64,
+    /// The icons and shaders shared by every widget.
+    icons: IconCache,
     /// A queue of commands waiting to be processed.
     command_input: CommandQueue,
 }
 
 impl UiContext {
-    /// Creates a ui context.
-    pub const fn new() -> UiContext {
+    /// Creates a ui context for a window of this many physical pixels.
+    pub fn new(window_size: (u32, u32), scale_factor: f64) -> UiContext {
+        let (position, size) = STATUS_LINE.resolve(window_size, scale_factor);
         UiContext {
             widgets: Vec::new(),
+            panels: Vec::new(),
+            status_line: StatusLine::new(position, size),
+            window_size,
+            scale_factor,
+            icons: IconCache::new(),
             command_input: CommandQueue::new(),
         }
     }
@@ -32,7 +73,7 @@ impl UiContext {
         let mut translated_commands = Vec::<Command>::new();
 
         while let Some(command) = command_opt {
-            for widget in &mut self.widgets {
+            for (_, widget) in &mut self.widgets {
                 translated_commands.extend(widget.process_command(&command));
             }
 
@@ -43,23 +84,46 @@ impl UiContext {
     }
 
     pub fn create_default_ui(&mut self) {
-        let mut button = Button::new((-0.96, -0.95), (0.1, 0.1), VirtualKeyCode::OpenScene as u32);
+        let placement = Placement::new(Anchor::Top, (0.0, STATUS_HEIGHT), (0.0, STATUS_HEIGHT));
+        let (position, size) = self.bounds(&placement);
+        let status_bar = StatusBar::new(position, size);
+
+        self.add_widget(Box::new(status_bar), placement);
+
+        self.panels.push(Placement::new(Anchor::Bottom, (0.0, 0.0), (0.0, TOOLBAR_HEIGHT)));
+
+        // The bottom row: the scene, the brush and the material.
+        let mut row = Row::new(Anchor::BottomLeft, (MARGIN, MARGIN), SPACING);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::OpenScene as u32);
+        button.set_tooltip(Action::OpenScene.description());
         button.add_state(String::from("resources/file-open.png"));
 
-        self.add_widget(Box::new(button));
+        self.add_widget(Box::new(button), placement);
 
-        let mut button = Button::new((-0.85, -0.95), (0.1, 0.1), VirtualKeyCode::SaveScene as u32);
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::SaveScene as u32);
+        button.set_tooltip(Action::SaveScene.description());
         button.add_state(String::from("resources/file-save.png"));
 
-        self.add_widget(Box::new(button));
+        self.add_widget(Box::new(button), placement);
 
-        let mut button = Button::new((-0.74, -0.95), (0.1, 0.1), VirtualKeyCode::ToggleShowGrid as u32);
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleShowGrid as u32);
+        button.set_tooltip(Action::ToggleShowGrid.description());
         button.add_state(String::from("resources/show-grid.png"));
         button.add_state(String::from("resources/hide-grid.png"));
 
-        self.add_widget(Box::new(button));
+        self.add_widget(Box::new(button), placement);
 
-        let mut button = Button::new((-0.63, -0.95), (0.1, 0.1), VirtualKeyCode::ToggleSelectionShape as u32);
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleSelectionShape as u32);
+        button.set_tooltip(Action::ToggleSelectionShape.description());
         button.add_state(String::from("resources/shape-sphere.png"));
         button.add_state(String::from("resources/shape-pyramid.png"));
         button.add_state(String::from("resources/shape-cube.png"));
@@ -73,138 +137,568 @@ impl UiContext {
         button.add_state(String::from("resources/shape-circle-xy.png"));
         button.add_state(String::from("resources/shape-circle-yz.png"));
 
-        self.add_widget(Box::new(button));
+        self.add_widget(Box::new(button), placement);
 
-        let mut button = Button::new((-0.52, -0.95), (0.1, 0.1), VirtualKeyCode::ToggleFluid as u32);
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleFluid as u32);
+        button.set_tooltip(Action::ToggleFluid.description());
         button.add_state(String::from("resources/material-solid.png"));
         button.add_state(String::from("resources/material-fluid.png"));
 
-        self.add_widget(Box::new(button));
+        self.add_widget(Box::new(button), placement);
 
-        let mut button = Button::new((-0.41, -0.95), (0.1, 0.1), VirtualKeyCode::ToggleNoise as u32);
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleNoise as u32);
+        button.set_tooltip(Action::ToggleNoise.description());
         button.add_state(String::from("resources/shader-solid.png"));
         button.add_state(String::from("resources/shader-noise.png"));
         button.add_state(String::from("resources/shader-noise-x.png"));
         button.add_state(String::from("resources/shader-noise-y.png"));
         button.add_state(String::from("resources/shader-noise-z.png"));
+        button.add_state(String::from("resources/shader-perlin.png"));
+        button.add_state(String::from("resources/shader-checker.png"));
+        button.add_state(String::from("resources/shader-wood.png"));
+        button.add_state(String::from("resources/shader-marble.png"));
 
-        self.add_widget(Box::new(button));
+        self.add_widget(Box::new(button), placement);
 
         // Red slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
         let slider = Slider::new(
-            (-0.3, -0.95),
-            (0.05, 0.3),
+            position,
+            size,
             [1.0, 0.0, 0.0, 1.0],
             204,
             (0, 255),
             0,
         );
 
-        self.add_widget(Box::new(slider));
+        self.add_widget(Box::new(slider), placement);
 
         // Green slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
         let slider = Slider::new(
-            (-0.25, -0.95),
-            (0.05, 0.3),
+            position,
+            size,
             [0.0, 1.0, 0.0, 1.0],
             204,
             (0, 255),
             1,
         );
 
-        self.add_widget(Box::new(slider));
+        self.add_widget(Box::new(slider), placement);
 
         // Blue slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
         let slider = Slider::new(
-            (-0.2, -0.95),
-            (0.05, 0.3),
+            position,
+            size,
             [0.0, 0.0, 1.0, 1.0],
             204,
             (0, 255),
             2,
         );
-        self.add_widget(Box::new(slider));
+
+        self.add_widget(Box::new(slider), placement);
+
         // Alpha slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
         let slider = Slider::new(
-            (-0.15, -0.95),
-            (0.05, 0.3),
+            position,
+            size,
             [0.5, 0.5, 0.5, 1.0],
             255,
             (0, 255),
             3,
         );
 
-        self.add_widget(Box::new(slider));
+        self.add_widget(Box::new(slider), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let swatch = Swatch::new(position, size, [0.8, 0.8, 0.8, 1.0]);
+
+        self.add_widget(Box::new(swatch), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ApplySecondaryColor as u32);
+        button.set_tooltip(Action::ApplySecondaryColor.description());
+        button.add_state(String::from("resources/secondary-color.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(PALETTE);
+        let (position, size) = self.bounds(&placement);
+        let palette = Palette::new(position, size);
+
+        self.add_widget(Box::new(palette), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ImportPalette as u32);
+        button.set_tooltip(Action::ImportPalette.description());
+        button.add_state(String::from("resources/palette-import.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ExportPalette as u32);
+        button.set_tooltip(Action::ExportPalette.description());
+        button.add_state(String::from("resources/palette-export.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        // The middle row: the surroundings, the texture and the material settings, which
+        // reach up through the top row.
+        let mut row = Row::new(Anchor::BottomLeft, (MARGIN, MARGIN + ROW_HEIGHT), SPACING);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleAmbientOcclusion as u32);
+        button.set_tooltip(Action::ToggleAmbientOcclusion.description());
+        button.add_state(String::from("resources/ambient-occlusion-on.png"));
+        button.add_state(String::from("resources/ambient-occlusion-off.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::CycleBackground as u32);
+        button.set_tooltip(Action::CycleBackground.description());
+        button.add_state(String::from("resources/background-solid.png"));
+        button.add_state(String::from("resources/background-gradient.png"));
+        button.add_state(String::from("resources/background-cube-map.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleFog as u32);
+        button.set_tooltip(Action::ToggleFog.description());
+        button.add_state(String::from("resources/fog-off.png"));
+        button.add_state(String::from("resources/fog-on.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::OpenSkybox as u32);
+        button.set_tooltip(Action::OpenSkybox.description());
+        button.add_state(String::from("resources/background-open.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ApplySkyColor as u32);
+        button.set_tooltip(Action::ApplySkyColor.description());
+        button.add_state(String::from("resources/sky-color.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ApplyHorizonColor as u32);
+        button.set_tooltip(Action::ApplyHorizonColor.description());
+        button.add_state(String::from("resources/horizon-color.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        // Leave room for the color sliders.
+        for _ in 0..4 {
+            row.skip(TALL_SLIDER.0);
+        }
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::OpenTexture as u32);
+        button.set_tooltip(Action::OpenTexture.description());
+        button.add_state(String::from("resources/texture-open.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        // Emissive slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [1.0, 0.6, 0.1, 1.0],
+            0,
+            (0, 255),
+            8,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 255.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Roughness slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.6, 0.5, 0.4, 1.0],
+            255,
+            (0, 255),
+            9,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 255.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Metalness slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.75, 0.75, 0.8, 1.0],
+            0,
+            (0, 255),
+            10,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 255.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Pattern scale slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.5, 0.7, 0.5, 1.0],
+            32,
+            (0, 256),
+            14,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 32.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Pattern contrast slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.4, 0.4, 0.4, 1.0],
+            102,
+            (0, 255),
+            15,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 255.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Fluid speed slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.3, 0.5, 0.9, 1.0],
+            100,
+            (0, 255),
+            16,
+        )
+        .with_format(|value| format!("{:.1}", value as f32 / 10.0));
+
+        self.add_widget(Box::new(slider), placement);
 
-        let swatch = Swatch::new((-0.09, -0.95), (0.1, 0.1), [0.8, 0.8, 0.8, 1.0]);
+        // Fluid wave scale slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.3, 0.7, 0.8, 1.0],
+            128,
+            (0, 256),
+            17,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 32.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Fluid color shift slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.9, 0.4, 0.6, 1.0],
+            0,
+            (0, 255),
+            18,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 255.0));
 
-        self.add_widget(Box::new(swatch));
+        self.add_widget(Box::new(slider), placement);
 
-        let palette = Palette::new(
-            (0.02, -0.95),
-            (0.1, 0.1),
-            [0.8, 0.8, 0.8, 1.0],
-            false,
-            false,
+        // Fluid displacement slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.4, 0.6, 0.7, 1.0],
             0,
+            (0, 255),
+            19,
+        )
+        .with_format(|value| format!("{:.2}", value as f32 / 255.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Fog distance slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.8, 0.8, 0.85, 1.0],
+            190,
+            (0, 200),
+            13,
         );
 
-        self.add_widget(Box::new(palette));
-        let palette = Palette::new(
-            (0.13, -0.95),
-            (0.1, 0.1),
-            [0.8, 0.8, 0.8, 1.0],
-            false,
-            false,
+        self.add_widget(Box::new(slider), placement);
+
+        // Shadow bias slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.4, 0.4, 0.5, 1.0],
+            5,
+            (0, 50),
+            11,
+        )
+        .with_format(|value| format!("{:.4}", value as f32 / 10000.0));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Shadow softness slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.3, 0.3, 0.4, 1.0],
             1,
+            (0, 4),
+            12,
         );
 
-        self.add_widget(Box::new(palette));
-        let palette = Palette::new(
-            (0.24, -0.95),
-            (0.1, 0.1),
-            [0.8, 0.8, 0.8, 1.0],
-            false,
-            false,
-            2,
-        );
+        self.add_widget(Box::new(slider), placement);
 
-        self.add_widget(Box::new(palette));
-        let palette = Palette::new(
-            (0.35, -0.95),
-            (0.1, 0.1),
-            [0.8, 0.8, 0.8, 1.0],
-            false,
-            false,
-            3,
+        // Level of detail distance slider
+        let placement = row.next(SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.6, 0.6, 0.5, 1.0],
+            80,
+            (0, 400),
+            20,
+        )
+        .with_format(|value| match value {
+            0 => String::from("Off"),
+            _ => value.to_string(),
+        });
+
+        self.add_widget(Box::new(slider), placement);
+
+        // The top row: the camera and the tools.
+        let mut row = Row::new(Anchor::BottomLeft, (MARGIN, MARGIN + ROW_HEIGHT * 2.0), SPACING);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleOrbit as u32);
+        button.set_tooltip(Action::ToggleOrbit.description());
+        button.add_state(String::from("resources/camera-fly.png"));
+        button.add_state(String::from("resources/camera-orbit.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::FrameSelection as u32);
+        button.set_tooltip(Action::FrameSelection.description());
+        button.add_state(String::from("resources/frame-selection.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::FrameAll as u32);
+        button.set_tooltip(Action::FrameAll.description());
+        button.add_state(String::from("resources/frame-all.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::CycleLayout as u32);
+        button.set_tooltip(Action::CycleLayout.description());
+        button.add_state(String::from("resources/layout-single.png"));
+        button.add_state(String::from("resources/layout-split.png"));
+        button.add_state(String::from("resources/layout-quad.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let color_picker = ColorPicker::new(position, size, [0.8, 0.8, 0.8, 1.0]);
+
+        self.add_widget(Box::new(color_picker), placement);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::PaletteFromScene as u32);
+        button.set_tooltip(Action::PaletteFromScene.description());
+        button.add_state(String::from("resources/palette-scene.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        for _ in 0..4 {
+            row.skip(TALL_SLIDER.0);
+        }
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::CycleTexture as u32);
+        button.set_tooltip(Action::CycleTexture.description());
+        button.add_state(String::from("resources/texture-cycle.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        // The lighting, from the right hand edge in.
+        let mut row = Row::new(Anchor::BottomRight, (MARGIN, MARGIN), SPACING);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ToggleShadows as u32);
+        button.set_tooltip(Action::ToggleShadows.description());
+        button.add_state(String::from("resources/shadows-on.png"));
+        button.add_state(String::from("resources/shadows-off.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        // Ambient light slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [0.6, 0.6, 0.7, 1.0],
+            50,
+            (0, 100),
+            7,
+        )
+        .with_format(|value| format!("{value}%"));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Light intensity slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [1.0, 1.0, 1.0, 1.0],
+            60,
+            (0, 200),
+            6,
+        )
+        .with_format(|value| format!("{value}%"));
+
+        self.add_widget(Box::new(slider), placement);
+
+        // Light elevation slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [1.0, 0.8, 0.4, 1.0],
+            35,
+            (0, 90),
+            5,
         );
 
-        self.add_widget(Box::new(palette));
-        let palette = Palette::new(
-            (0.46, -0.95),
-            (0.1, 0.1),
-            [0.8, 0.8, 0.8, 1.0],
-            false,
-            false,
+        self.add_widget(Box::new(slider), placement);
+
+        // Light azimuth slider
+        let placement = row.next(TALL_SLIDER);
+        let (position, size) = self.bounds(&placement);
+        let slider = Slider::new(
+            position,
+            size,
+            [1.0, 0.9, 0.5, 1.0],
+            45,
+            (0, 360),
             4,
         );
 
-        self.add_widget(Box::new(palette));
-        let palette = Palette::new(
-            (0.57, -0.95),
-            (0.1, 0.1),
-            [0.8, 0.8, 0.8, 1.0],
-            false,
-            false,
-            5,
-        );
+        self.add_widget(Box::new(slider), placement);
+
+        let mut row = Row::new(Anchor::BottomRight, (MARGIN, MARGIN + ROW_HEIGHT), SPACING);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::CycleLightingPreset as u32);
+        button.set_tooltip(Action::CycleLightingPreset.description());
+        button.add_state(String::from("resources/light-day.png"));
+        button.add_state(String::from("resources/light-sunset.png"));
+        button.add_state(String::from("resources/light-night.png"));
+        button.add_state(String::from("resources/light-studio.png"));
+
+        self.add_widget(Box::new(button), placement);
+
+        let mut row = Row::new(Anchor::BottomRight, (MARGIN, MARGIN + ROW_HEIGHT * 2.0), SPACING);
+
+        let placement = row.next(BUTTON);
+        let (position, size) = self.bounds(&placement);
+        let mut button = Button::new(position, size, VirtualKeyCode::ApplyLightColor as u32);
+        button.set_tooltip(Action::ApplyLightColor.description());
+        button.add_state(String::from("resources/light-color.png"));
+
+        self.add_widget(Box::new(button), placement);
+    }
+
+    /// Adds a widget to the UI context, to be kept at the placement.
+    pub fn add_widget(&mut self, widget: Box<dyn Widget>, placement: Placement) {
+        self.widgets.push((placement, widget));
+    }
 
-        self.add_widget(Box::new(palette));
+    /// Where a placement is in the window as it is now.
+    fn bounds(&self, placement: &Placement) -> ((f32, f32), (f32, f32)) {
+        placement.resolve(self.window_size, self.scale_factor)
     }
 
-    /// Adds a widget to the UI context.
-    pub fn add_widget(&mut self, widget: Box<dyn Widget>) {
-        self.widgets.push(widget);
+    /// Move every widget to its placement in a window of this many physical pixels.
+    pub fn resize(&mut self, window_size: (u32, u32), scale_factor: f64) {
+        self.window_size = window_size;
+        self.scale_factor = scale_factor;
+        let (position, size) = self.bounds(&STATUS_LINE);
+        self.status_line.set_bounds(position, size);
+        for (placement, widget) in &mut self.widgets {
+            let (position, size) = placement.resolve(window_size, scale_factor);
+            widget.set_bounds(position, size);
+        }
     }
 
     /// Add a command to the queue of commands to process later.
@@ -212,9 +706,44 @@ impl UiContext {
         self.command_input.queue_command(command);
     }
 
+    /// Compile the shaders the widgets draw with.
+    pub fn setup_shaders(&mut self, display: &Display<WindowSurface>) {
+        self.icons.setup_shaders(display);
+    }
+
+    /// Is any widget or panel drawn over this point, in window coordinates from -1 to 1?
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.panels.iter().any(|panel| {
+            let (position, size) = self.bounds(panel);
+            x >= position.0
+                && x <= position.0 + size.0
+                && y >= position.1
+                && y <= position.1 + size.1
+        }) || self.widgets.iter().any(|(_, widget)| widget.contains(x, y))
+    }
+
+    /// Is a widget waiting for typing?
+    pub fn has_focus(&self) -> bool {
+        self.widgets.iter().any(|(_, widget)| widget.has_focus())
+    }
+
+    /// Replace the text in the status line.
+    pub fn set_status(&mut self, text: String) {
+        self.status_line.text = text;
+    }
+
     pub fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame) {
-        for widget in &mut self.widgets {
-            widget.draw(display, frame);
+        for panel in &self.panels {
+            let (position, size) = panel.resolve(self.window_size, self.scale_factor);
+            let mut canvas = Canvas::new(display, frame, &mut self.icons);
+            canvas.draw_rectangle(position, size, [0.1, 0.1, 0.1, 0.4]);
+        }
+        for (_, widget) in &mut self.widgets {
+            widget.draw(display, frame, &mut self.icons);
+        }
+        self.status_line.draw(display, frame, &mut self.icons);
+        for (_, widget) in &mut self.widgets {
+            widget.draw_overlay(display, frame, &mut self.icons);
         }
     }
 }
@@ -0,0 +1,89 @@
+/// Which corner or edge of the window a placement is measured from. Top and Bottom
+/// stretch across the window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Anchor {
+    BottomLeft,
+    BottomRight,
+    Top,
+    Bottom,
+}
+
+/// Where a widget sits, in logical pixels from its anchor, so it keeps its size when
+/// the window is resized or moved to a screen with a different scale factor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub anchor: Anchor,
+    /// From the anchor to the nearest corner of the widget. Placements that stretch
+    /// across the window keep offset.0 clear on both sides.
+    pub offset: (f32, f32),
+    /// The width is ignored for placements that stretch across the window.
+    pub size: (f32, f32),
+}
+
+impl Placement {
+    pub const fn new(anchor: Anchor, offset: (f32, f32), size: (f32, f32)) -> Placement {
+        Placement {
+            anchor,
+            offset,
+            size,
+        }
+    }
+
+    /// The bottom left corner and size in window coordinates from -1 to 1, for a window
+    /// of this many physical pixels.
+    pub fn resolve(&self, window_size: (u32, u32), scale_factor: f64) -> ((f32, f32), (f32, f32)) {
+        let scale_factor = scale_factor as f32;
+        // How far one logical pixel reaches in window coordinates.
+        let pixel = (
+            2.0 * scale_factor / window_size.0.max(1) as f32,
+            2.0 * scale_factor / window_size.1.max(1) as f32,
+        );
+        let offset = (self.offset.0 * pixel.0, self.offset.1 * pixel.1);
+        let mut size = (self.size.0 * pixel.0, self.size.1 * pixel.1);
+
+        let x = match self.anchor {
+            Anchor::BottomLeft => -1.0 + offset.0,
+            Anchor::BottomRight => 1.0 - offset.0 - size.0,
+            Anchor::Top | Anchor::Bottom => {
+                size.0 = 2.0 - offset.0 * 2.0;
+                -1.0 + offset.0
+            }
+        };
+        let y = match self.anchor {
+            Anchor::BottomLeft | Anchor::BottomRight | Anchor::Bottom => -1.0 + offset.1,
+            Anchor::Top => 1.0 - offset.1 - size.1,
+        };
+        ((x, y), size)
+    }
+}
+
+/// Places widgets one after another along a row, away from the row's anchor. Rows
+/// anchored on the right fill from right to left.
+pub struct Row {
+    anchor: Anchor,
+    /// Where the next widget goes, in logical pixels from the anchor.
+    offset: (f32, f32),
+    spacing: f32,
+}
+
+impl Row {
+    pub const fn new(anchor: Anchor, offset: (f32, f32), spacing: f32) -> Row {
+        Row {
+            anchor,
+            offset,
+            spacing,
+        }
+    }
+
+    /// The placement for the next widget in the row.
+    pub fn next(&mut self, size: (f32, f32)) -> Placement {
+        let placement = Placement::new(self.anchor, self.offset, size);
+        self.skip(size.0);
+        placement
+    }
+
+    /// Leave a gap, like the room taken by a widget from another row.
+    pub fn skip(&mut self, width: f32) {
+        self.offset.0 += width + self.spacing;
+    }
+}
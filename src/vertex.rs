@@ -4,7 +4,14 @@ use glium::implement_vertex;
 pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
+    /// Ambient light reaching this vertex, 1.0 is fully open and lower values are darker corners.
+    pub occlusion: f32,
+    /// Texture position across the face, in voxels. The shader wraps this into the atlas tile.
+    pub tex_coords: [f32; 2],
+    /// How far the waves sink this vertex when it is fluid, 1.0 across an open top face and
+    /// falling to 0.0 down its sides so they meet the sunken top.
+    pub sink: f32,
 }
 
 // you must pass the list of members to the macro
-implement_vertex!(Vertex, position, normal);
+implement_vertex!(Vertex, position, normal, occlusion, tex_coords, sink);
@@ -0,0 +1,111 @@
+use crate::camera::{Camera, Projection};
+use nalgebra::{Point2, Point3};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// How far the axis views start from the origin.
+const AXIS_VIEW_DISTANCE: f32 = 60.0;
+
+/// How the window is split between viewports.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Layout {
+    /// One view filling the window.
+    Single,
+    /// Two views side by side.
+    Split,
+    /// Four views, two above two.
+    Quad,
+}
+
+impl Layout {
+    /// Get the layout that follows this one.
+    pub fn next(&self) -> Layout {
+        match self {
+            Layout::Single => Layout::Split,
+            Layout::Split => Layout::Quad,
+            Layout::Quad => Layout::Single,
+        }
+    }
+
+    /// How many viewports are shown.
+    pub fn count(&self) -> usize {
+        match self {
+            Layout::Single => 1,
+            Layout::Split => 2,
+            Layout::Quad => 4,
+        }
+    }
+
+    /// The part of the window a viewport covers, as fractions of the window
+    /// measured from the bottom left: left, bottom, width and height.
+    pub fn area(&self, index: usize) -> [f32; 4] {
+        match self {
+            Layout::Single => [0.0, 0.0, 1.0, 1.0],
+            Layout::Split => [0.5 * index as f32, 0.0, 0.5, 1.0],
+            Layout::Quad => [
+                0.5 * (index % 2) as f32,
+                if index < 2 { 0.5 } else { 0.0 },
+                0.5,
+                0.5,
+            ],
+        }
+    }
+
+    /// Find the viewport under a point in window coordinates, from -1 to 1.
+    pub fn viewport_at(&self, point: Point2<f32>) -> usize {
+        (0..self.count())
+            .find(|index| {
+                let [left, bottom, width, height] = self.area(*index);
+                let x = (point.x + 1.0) / 2.0;
+                let y = (point.y + 1.0) / 2.0;
+                x >= left && x <= left + width && y >= bottom && y <= bottom + height
+            })
+            .unwrap_or(0)
+    }
+
+    /// Convert a point in window coordinates to coordinates inside a viewport,
+    /// so -1 to 1 spans the viewport instead of the window.
+    pub fn point_in_viewport(&self, index: usize, point: Point2<f32>) -> Point2<f32> {
+        let [left, bottom, width, height] = self.area(index);
+        Point2::new(
+            ((point.x + 1.0) / 2.0 - left) / width * 2.0 - 1.0,
+            ((point.y + 1.0) / 2.0 - bottom) / height * 2.0 - 1.0,
+        )
+    }
+}
+
+/// One view into the scene with its own camera.
+#[derive(Copy, Clone)]
+pub struct Viewport {
+    pub camera: Camera,
+    pub projection: Projection,
+}
+
+impl Viewport {
+    /// A perspective view from the starting camera position.
+    pub const fn new() -> Viewport {
+        Viewport {
+            camera: Camera::new(),
+            projection: Projection::Perspective,
+        }
+    }
+
+    /// An orthographic view looking at the origin along an axis.
+    pub fn along_axis(yaw: f32, pitch: f32) -> Viewport {
+        let mut camera = Camera::new();
+        camera.look_along(yaw, pitch, Point3::origin(), AXIS_VIEW_DISTANCE);
+        Viewport {
+            camera,
+            projection: Projection::Orthographic,
+        }
+    }
+
+    /// The views shown in the four panes: perspective, front, top and right.
+    pub fn defaults() -> Vec<Viewport> {
+        vec![
+            Viewport::new(),
+            Viewport::along_axis(PI, 0.0),
+            Viewport::along_axis(PI, -FRAC_PI_2),
+            Viewport::along_axis(-FRAC_PI_2, 0.0),
+        ]
+    }
+}
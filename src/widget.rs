@@ -1,10 +1,34 @@
 use crate::command::Command;
+use crate::icon_cache::IconCache;
 
 use glium::Frame;
 use glium::backend::glutin::Display;
 use glutin::surface::WindowSurface;
 
 pub trait Widget {
-    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame);
+    fn draw(&mut self, display: &Display<WindowSurface>, frame: &mut Frame, icons: &mut IconCache);
     fn process_command(&mut self, command: &Command) -> Vec<Command>;
+
+    /// Move the widget, in window coordinates from -1 to 1, after the window changes size.
+    fn set_bounds(&mut self, position: (f32, f32), size: (f32, f32));
+
+    /// Does the widget cover this point? Widgets drawn over the scene use this to keep
+    /// clicks on them away from the scene.
+    fn contains(&self, _x: f32, _y: f32) -> bool {
+        false
+    }
+
+    /// Should typing go to this widget instead of the scene?
+    fn has_focus(&self) -> bool {
+        false
+    }
+
+    /// Draw anything that should sit on top of every widget, like a tooltip.
+    fn draw_overlay(
+        &mut self,
+        _display: &Display<WindowSurface>,
+        _frame: &mut Frame,
+        _icons: &mut IconCache,
+    ) {
+    }
 }